@@ -0,0 +1,113 @@
+//! The on-disk formats of event logs, as written by the `log` and `compressed-log`
+//! [`BackendKind`](crate::runtime::backend::BackendKind)s.
+//!
+//! A plain log is just a sequence of bincode-serialized [`Event`]s.
+//! A compressed log is the same sequence, split into chunks of about [`CHUNK_SIZE`] bytes,
+//! each of which is compressed as its own zstd frame.
+//! Compressed logs can only be written and read with the `compressed-log` feature enabled.
+//! Event logs can be tens of GB, so they are read with [`EventReader`],
+//! which decompresses and deserializes one [`Event`] at a time, rather than all at once.
+
+#[cfg(feature = "compressed-log")]
+use std::io::Write;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::events::Event;
+
+/// The number of uncompressed bytes of [`Event`]s in each chunk of a compressed log.
+///
+/// Each chunk is written once it is full, so a program that crashes loses at most one chunk.
+#[cfg(feature = "compressed-log")]
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+/// The magic number that every zstd frame, and so every compressed log, starts with.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Writes a compressed log, one chunk at a time.
+#[cfg(feature = "compressed-log")]
+pub struct ChunkWriter<W: Write> {
+    writer: W,
+    chunk: Vec<u8>,
+}
+
+#[cfg(feature = "compressed-log")]
+impl<W: Write> ChunkWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    pub fn write(&mut self, event: &Event) -> bincode::Result<()> {
+        bincode::serialize_into(&mut self.chunk, event)?;
+        if self.chunk.len() >= CHUNK_SIZE {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Compress and write the current chunk, even if it isn't full.
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let frame = zstd::bulk::compress(&self.chunk, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        self.writer.write_all(&frame)?;
+        self.chunk.clear();
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_chunk()?;
+        self.writer.flush()
+    }
+}
+
+/// Reads the [`Event`]s of a plain or compressed log, detecting which it is from its first bytes.
+///
+/// Iteration stops at the end of the log.  An [`Event`] that can't be read,
+/// such as one truncated by the instrumented program crashing, is yielded as an error.
+pub struct EventReader {
+    reader: Box<dyn BufRead>,
+}
+
+impl EventReader {
+    pub fn new(reader: impl Read + 'static) -> io::Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let reader: Box<dyn BufRead> = if reader.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+            Self::decompress(reader)?
+        } else {
+            Box::new(reader)
+        };
+        Ok(Self { reader })
+    }
+
+    #[cfg(feature = "compressed-log")]
+    fn decompress(reader: BufReader<impl Read + 'static>) -> io::Result<Box<dyn BufRead>> {
+        // Concatenated frames are decoded as one stream.
+        Ok(Box::new(BufReader::new(
+            zstd::stream::read::Decoder::with_buffer(reader)?,
+        )))
+    }
+
+    #[cfg(not(feature = "compressed-log"))]
+    fn decompress(_reader: BufReader<impl Read + 'static>) -> io::Result<Box<dyn BufRead>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "reading a compressed event log requires the `compressed-log` feature",
+        ))
+    }
+}
+
+impl Iterator for EventReader {
+    type Item = bincode::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.fill_buf() {
+            Ok([]) => None,
+            Ok(_) => Some(bincode::deserialize_from(&mut self.reader)),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
@@ -1,26 +1,65 @@
-use crate::mir_loc::{Local, MirLocId};
+use crate::mir_loc::{DefPathHash, Local, MirLocId};
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Formatter;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 pub type Pointer = usize;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Identifies the thread that an [`Event`] happened on.
+///
+/// Unlike [`std::thread::ThreadId`], this can be serialized.
+/// Threads are numbered from 0 in the order they send their first [`Event`].
+pub type ThreadId = u64;
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// The current thread's [`ThreadId`], once it has one,
+    /// and the [`Event::seq`] of its next [`Event`].
+    ///
+    /// This has no destructor, so it can be used before and after `main`.
+    static THREAD_EVENTS: Cell<(Option<ThreadId>, u64)> = const { Cell::new((None, 0)) };
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Event {
     pub mir_loc: MirLocId,
     pub kind: EventKind,
+    /// The thread that this [`Event`] happened on.
+    pub thread: ThreadId,
+    /// The index of this [`Event`] among the [`Event`]s created on its thread.
+    ///
+    /// Gaps mean that [`Event`]s were skipped, such as those before `main`.
+    pub seq: u64,
 }
 
 impl Event {
-    pub fn done() -> Self {
+    /// Create an [`Event`] that happened on the current thread.
+    pub fn new(mir_loc: MirLocId, kind: EventKind) -> Self {
+        let (thread, seq) = THREAD_EVENTS.with(|state| {
+            let (thread, seq) = state.get();
+            let thread = thread.unwrap_or_else(|| NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+            state.set((Some(thread), seq + 1));
+            (thread, seq)
+        });
         Self {
-            mir_loc: 0,
-            kind: EventKind::Done,
+            mir_loc,
+            kind,
+            thread,
+            seq,
         }
     }
+
+    pub fn done() -> Self {
+        Self::new(0, EventKind::Done)
+    }
 }
 
+/// Event logs are bincode, which identifies variants by their index,
+/// so new variants must be added at the end to keep reading existing logs.
 #[derive(Serialize, Deserialize, Copy, Clone)]
 pub enum EventKind {
     /// A copy from one local to another. This also covers casts such as `&mut
@@ -83,6 +122,25 @@ pub enum EventKind {
     BeginFuncBody,
 
     Done,
+
+    /// The pointer that appears as the address result of addr_of(Static)
+    AddrOfStatic(Pointer, DefPathHash),
+
+    /// The pointer, which is argument `arg` of a hooked call,
+    /// appears as the address of a load of `size` bytes, such as by `memcpy` or `strlen`.
+    LoadRange {
+        arg: u32,
+        ptr: Pointer,
+        size: usize,
+    },
+
+    /// The pointer, which is argument `arg` of a hooked call,
+    /// appears as the address of a store of `size` bytes, such as by `memcpy` or `memset`.
+    StoreRange {
+        arg: u32,
+        ptr: Pointer,
+        size: usize,
+    },
 }
 
 impl Debug for EventKind {
@@ -105,9 +163,12 @@ impl Debug for EventKind {
             BeginFuncBody => write!(f, "begin func body"),
             LoadAddr(ptr) => write!(f, "load(0x{:x})", ptr),
             StoreAddr(ptr) => write!(f, "store(0x{:x})", ptr),
+            LoadRange { ptr, size, .. } => write!(f, "load(0x{:x}, {})", ptr, size),
+            StoreRange { ptr, size, .. } => write!(f, "store(0x{:x}, {})", ptr, size),
             StoreAddrTaken(ptr) => write!(f, "store(0x{:x})", ptr),
             CopyRef => write!(f, "copy_ref"),
             AddrOfLocal(ptr, _) => write!(f, "addr_of_local = 0x{:x}", ptr),
+            AddrOfStatic(ptr, _) => write!(f, "addr_of_static = 0x{:x}", ptr),
             ToInt(ptr) => write!(f, "to_int(0x{:x})", ptr),
             FromInt(ptr) => write!(f, "from_int(0x{:x})", ptr),
             LoadValue(ptr) => write!(f, "load_value(0x{:x})", ptr),
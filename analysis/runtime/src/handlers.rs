@@ -1,58 +1,52 @@
 use crate::events::{Event, EventKind};
-use crate::mir_loc::MirLocId;
+use crate::mir_loc::{DefPathHash, MirLocId};
 use crate::runtime::global_runtime::RUNTIME;
 
 /// A hook function (see [`HOOK_FUNCTIONS`]).
 ///
 /// Instruments 64-bit `c2rust transpile`d `malloc`, which is similar to `libc::malloc`.
 pub fn malloc(mir_loc: MirLocId, size: u64, ptr: usize) {
-    RUNTIME.send_event(Event {
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::Alloc {
+        EventKind::Alloc {
             size: size as usize,
             ptr,
         },
-    });
+    ));
 }
 
 /// A hook function (see [`HOOK_FUNCTIONS`]).
 ///
 /// Instruments 64-bit `c2rust transpile`d `free`, which is similar to `libc::free`.
 pub fn free(mir_loc: MirLocId, ptr: usize, _free_ret_val: ()) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Free { ptr },
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Free { ptr }));
 }
 
 /// A hook function (see [`HOOK_FUNCTIONS`]).
 ///
 /// Instruments 64-bit `c2rust transpile`d `calloc`, which is similar to `libc::calloc`.
 pub fn calloc(mir_loc: MirLocId, nmemb: u64, size: u64, ptr: usize) {
-    RUNTIME.send_event(Event {
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::Alloc {
+        EventKind::Alloc {
             size: (nmemb * size) as usize,
             ptr,
         },
-    });
+    ));
 }
 
 /// A hook function (see [`HOOK_FUNCTIONS`]).
 ///
 /// Instruments 64-bit `c2rust transpile`d `realloc`, which is similar to `libc::realloc`.
 pub fn realloc(mir_loc: MirLocId, old_ptr: usize, size: u64, new_ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Free { ptr: old_ptr },
-    });
-    RUNTIME.send_event(Event {
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Free { ptr: old_ptr }));
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::Alloc {
+        EventKind::Alloc {
             size: size as usize,
             ptr: new_ptr,
         },
-    });
+    ));
 }
 
 /// A hook function (see [`HOOK_FUNCTIONS`]).
@@ -64,6 +58,129 @@ pub fn reallocarray(mir_loc: MirLocId, old_ptr: usize, nmemb: u64, size: u64, ne
     realloc(mir_loc, old_ptr, size * nmemb, new_ptr)
 }
 
+/// Record a load of `size` bytes from `ptr`, which is argument `arg` of the hooked call.
+fn load_range(mir_loc: MirLocId, arg: u32, ptr: usize, size: usize) {
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::LoadRange { arg, ptr, size }));
+}
+
+/// Record a store of `size` bytes to `ptr`, which is argument `arg` of the hooked call.
+fn store_range(mir_loc: MirLocId, arg: u32, ptr: usize, size: usize) {
+    RUNTIME.send_event(Event::new(
+        mir_loc,
+        EventKind::StoreRange { arg, ptr, size },
+    ));
+}
+
+/// The number of bytes `strnlen(ptr, max) + 1` reads: up to and including the nul terminator,
+/// but at most `max`.
+///
+/// # Safety
+///
+/// `ptr` must be readable for that many bytes.
+/// The `str*` hooks only call this on strings the hooked function has just read or written.
+unsafe fn str_read_len(ptr: usize, max: usize) -> usize {
+    let ptr = ptr as *const u8;
+    (0..max).find(|&i| *ptr.add(i) == 0).map_or(max, |i| i + 1)
+}
+
+/// The number of bytes `strncmp(a, b, max)` reads from each string:
+/// up to and including the first byte that differs or is the nul terminator, but at most `max`.
+///
+/// # Safety
+///
+/// `a` and `b` must be readable for that many bytes.
+/// The `str*` hooks only call this on strings the hooked function has just read.
+unsafe fn str_cmp_len(a: usize, b: usize, max: usize) -> usize {
+    let (a, b) = (a as *const u8, b as *const u8);
+    (0..max)
+        .find(|&i| *a.add(i) != *b.add(i) || *a.add(i) == 0)
+        .map_or(max, |i| i + 1)
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `memcpy`, which is similar to `libc::memcpy`.
+pub fn memcpy(mir_loc: MirLocId, dest: usize, src: usize, n: u64, ret: usize) {
+    load_range(mir_loc, 1, src, n as usize);
+    store_range(mir_loc, 0, dest, n as usize);
+    ptr_copy(mir_loc, ret);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `memmove`, which is similar to `libc::memmove`.
+pub fn memmove(mir_loc: MirLocId, dest: usize, src: usize, n: u64, ret: usize) {
+    memcpy(mir_loc, dest, src, n, ret)
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `memset`, which is similar to `libc::memset`.
+pub fn memset(mir_loc: MirLocId, dest: usize, _c: i32, n: u64, ret: usize) {
+    store_range(mir_loc, 0, dest, n as usize);
+    ptr_copy(mir_loc, ret);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `memcmp`, which is similar to `libc::memcmp`.
+///
+/// `memcmp` may stop at the first difference, but we record all `n` bytes as read.
+pub fn memcmp(mir_loc: MirLocId, s1: usize, s2: usize, n: u64, _ret: i32) {
+    load_range(mir_loc, 0, s1, n as usize);
+    load_range(mir_loc, 1, s2, n as usize);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strlen`, which is similar to `libc::strlen`.
+pub fn strlen(mir_loc: MirLocId, s: usize, len: u64) {
+    load_range(mir_loc, 0, s, len as usize + 1);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strcpy`, which is similar to `libc::strcpy`.
+pub fn strcpy(mir_loc: MirLocId, dest: usize, src: usize, ret: usize) {
+    // SAFETY: `strcpy` just copied a nul-terminated string to `dest`.
+    let size = unsafe { str_read_len(dest, usize::MAX) };
+    load_range(mir_loc, 1, src, size);
+    store_range(mir_loc, 0, dest, size);
+    ptr_copy(mir_loc, ret);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strncpy`, which is similar to `libc::strncpy`.
+pub fn strncpy(mir_loc: MirLocId, dest: usize, src: usize, n: u64, ret: usize) {
+    // SAFETY: `strncpy` just read `src` up to its nul terminator or `n` bytes.
+    let size = unsafe { str_read_len(src, n as usize) };
+    load_range(mir_loc, 1, src, size);
+    // `strncpy` pads `dest` with nuls, so it always writes `n` bytes.
+    store_range(mir_loc, 0, dest, n as usize);
+    ptr_copy(mir_loc, ret);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strcmp`, which is similar to `libc::strcmp`.
+pub fn strcmp(mir_loc: MirLocId, s1: usize, s2: usize, _ret: i32) {
+    // SAFETY: `strcmp` just read both strings up to this length.
+    let size = unsafe { str_cmp_len(s1, s2, usize::MAX) };
+    load_range(mir_loc, 0, s1, size);
+    load_range(mir_loc, 1, s2, size);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strncmp`, which is similar to `libc::strncmp`.
+pub fn strncmp(mir_loc: MirLocId, s1: usize, s2: usize, n: u64, _ret: i32) {
+    // SAFETY: `strncmp` just read both strings up to this length.
+    let size = unsafe { str_cmp_len(s1, s2, n as usize) };
+    load_range(mir_loc, 0, s1, size);
+    load_range(mir_loc, 1, s2, size);
+}
+
 /// A hook function (see [`HOOK_FUNCTIONS`]).
 ///
 /// Instruments [`pointer::offset`](https://doc.rust-lang.org/std/primitive.pointer.html#method.offset).
@@ -77,10 +194,7 @@ pub fn reallocarray(mir_loc: MirLocId, old_ptr: usize, nmemb: u64, size: u64, ne
 ///   = note: rustdoc does not allow disambiguating between `*const` and `*mut`, and pointers are unstable until it does
 /// ```
 pub fn offset(mir_loc: MirLocId, ptr: usize, offset: isize, new_ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Offset(ptr, offset, new_ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Offset(ptr, offset, new_ptr)));
 }
 
 macro_rules! hook_fn {
@@ -102,89 +216,72 @@ pub const HOOK_FUNCTIONS: &[&str] = &[
     hook_fn!(calloc),
     hook_fn!(realloc),
     hook_fn!(reallocarray),
+    hook_fn!(memcpy),
+    hook_fn!(memmove),
+    hook_fn!(memset),
+    hook_fn!(memcmp),
+    hook_fn!(strlen),
+    hook_fn!(strcpy),
+    hook_fn!(strncpy),
+    hook_fn!(strcmp),
+    hook_fn!(strncmp),
     hook_fn!(offset),
 ];
 
 pub fn ptr_field(mir_loc: MirLocId, ptr: usize, field_id: u32) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Field(ptr, field_id),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Field(ptr, field_id)));
 }
 
 pub fn ptr_copy(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::CopyPtr(ptr as usize),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::CopyPtr(ptr as usize)));
 }
 
 pub fn ptr_contrive(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::FromInt(ptr as usize),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::FromInt(ptr as usize)));
 }
 
 pub fn ptr_to_int(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::ToInt(ptr as usize),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::ToInt(ptr as usize)));
 }
 
 pub fn addr_of_local(mir_loc: MirLocId, ptr: usize, local: u32) {
-    RUNTIME.send_event(Event {
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::AddrOfLocal(ptr, local.into()),
-    });
+        EventKind::AddrOfLocal(ptr, local.into()),
+    ));
 }
 
-pub fn load_value(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
+pub fn addr_of_static(mir_loc: MirLocId, ptr: usize, static_hash_0: u64, static_hash_1: u64) {
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::LoadValue(ptr),
-    });
+        EventKind::AddrOfStatic(ptr, DefPathHash::from((static_hash_0, static_hash_1))),
+    ));
+}
+
+pub fn load_value(mir_loc: MirLocId, ptr: usize) {
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::LoadValue(ptr)));
 }
 
 pub fn store_value(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::StoreValue(ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::StoreValue(ptr)));
 }
 
 pub fn ptr_ret(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Ret(ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Ret(ptr)));
 }
 
 pub fn ptr_load(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::LoadAddr(ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::LoadAddr(ptr)));
 }
 
 pub fn ptr_store(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::StoreAddr(ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::StoreAddr(ptr)));
 }
 
 pub fn ptr_store_addr_taken(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::StoreAddrTaken(ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::StoreAddrTaken(ptr)));
 }
 
 pub fn mark_begin_body(mir_loc: MirLocId) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::BeginFuncBody,
-    })
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::BeginFuncBody))
 }
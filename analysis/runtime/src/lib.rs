@@ -1,8 +1,12 @@
+pub mod event_log;
 pub mod events;
 mod handlers;
+mod map_as_pairs;
 pub mod metadata;
 pub mod mir_loc;
 pub mod parse;
+pub mod pdg_facts;
+pub mod pdg_file;
 pub mod runtime;
 
 pub use handlers::*;
@@ -0,0 +1,35 @@
+//! Serialize a [`HashMap`] as a sequence of `(key, value)` pairs, for use with
+//! `#[serde(with = "crate::map_as_pairs")]`.
+//!
+//! JSON only allows strings as map keys, so this is needed for maps keyed by [`FuncId`] and
+//! similar.  Bincode encodes a map the same way as a sequence of pairs, so adding this to an
+//! existing field doesn't change its bincode representation.  The pairs are sorted by key, so
+//! that the output doesn't depend on the [`HashMap`]'s iteration order.
+//!
+//! [`FuncId`]: crate::mir_loc::FuncId
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Serialize + Ord,
+    V: Serialize,
+    S: Serializer,
+{
+    let mut pairs = map.iter().collect::<Vec<_>>();
+    pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    serializer.collect_seq(pairs)
+}
+
+pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let pairs = Vec::<(K, V)>::deserialize(deserializer)?;
+    Ok(pairs.into_iter().collect())
+}
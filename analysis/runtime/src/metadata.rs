@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     fmt::{self, Debug, Formatter},
     io::Cursor,
     iter,
@@ -9,10 +9,15 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::mir_loc::{Func, FuncId, MirLoc, MirLocId};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metadata {
     pub locs: Vec<MirLoc>,
+    #[serde(with = "crate::map_as_pairs")]
     pub functions: HashMap<FuncId, String>,
+    /// The functions whose bodies were instrumented.
+    /// [`functions`](Self::functions) also has the functions they call.
+    /// This is sorted so that saved PDGs are deterministic.
+    pub instrumented: BTreeSet<FuncId>,
 }
 
 impl Metadata {
@@ -34,7 +39,7 @@ where
     let mut cursor = Cursor::new(bytes);
     iter::from_fn(|| {
         // No good alternatives: <https://github.com/rust-lang/rust/issues/86369>.
-        if cursor.position() == len.try_into().unwrap() {
+        if cursor.position() == u64::try_from(len).unwrap() {
             return None;
         }
         Some(bincode::deserialize_from(&mut cursor))
@@ -46,11 +51,17 @@ impl FromIterator<Metadata> for Metadata {
     fn from_iter<I: IntoIterator<Item = Metadata>>(iter: I) -> Self {
         let mut locs = Vec::new();
         let mut functions = HashMap::new();
+        let mut instrumented = BTreeSet::new();
         for metadata in iter {
             locs.extend(metadata.locs);
             functions.extend(metadata.functions);
+            instrumented.extend(metadata.instrumented);
+        }
+        Self {
+            locs,
+            functions,
+            instrumented,
         }
-        Self { locs, functions }
     }
 }
 
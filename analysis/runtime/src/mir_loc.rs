@@ -193,6 +193,9 @@ impl Default for TransferKind {
 pub struct EventMetadata {
     /// Input [`Local`]s for an [`Event`](crate::events::Event).
     pub source: Option<MirPlace>,
+    /// For a hooked call, the source of each argument, since [`source`](Self::source)
+    /// is only the first.  Range [`Event`](crate::events::Event)s may be about any argument.
+    pub arg_sources: Vec<Option<MirPlace>>,
     /// Destination [`Local`] for an [`Event`](crate::events::Event).
     pub destination: Option<MirPlace>,
     /// Destination func [`FuncId`] of [`Event`](crate::events::Event).
@@ -205,11 +208,12 @@ impl EventMetadata {
     fn eq_fields(&self) -> impl Eq + Hash + '_ {
         let Self {
             source,
+            arg_sources,
             destination,
             transfer_kind,
             debug_info: _,
         } = self;
-        (source, destination, transfer_kind)
+        (source, arg_sources, destination, transfer_kind)
     }
 }
 
@@ -225,7 +229,7 @@ impl Hash for EventMetadata {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub struct MirLoc {
     pub func: Func,
     pub basic_block_idx: usize,
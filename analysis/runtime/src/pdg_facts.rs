@@ -0,0 +1,74 @@
+//! Facts about pointers observed at run time, as computed by `c2rust-pdg` from an event log.
+//!
+//! These are saved as part of a [`PdgFile`](crate::pdg_file::PdgFile) by `c2rust-pdg --output`
+//! and read by `c2rust-analyze --pdg`, which compares them against its static permission
+//! assignment.  Facts are keyed by the [`FuncId`] and [`Local`] of each MIR local that held a
+//! pointer, which `c2rust-analyze` can recover from its own `mir_built` bodies, the same MIR that
+//! was instrumented.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::mir_loc::{FuncId, Local};
+
+/// What was observed about the pointers stored in a single MIR local.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PointerFacts {
+    /// Some pointer derived from this one was loaded from.
+    pub load: bool,
+    /// Some pointer derived from this one was stored to.
+    pub store: bool,
+    /// Some pointer derived from this one was offset forward.
+    pub pos_offset: bool,
+    /// Some pointer derived from this one was offset backward.
+    pub neg_offset: bool,
+    /// This pointer could be used as a `&mut`: no conflicting pointer to the same object was used
+    /// while it was live.
+    pub unique: bool,
+    /// Some pointer derived from this one was passed to or returned from a function that wasn't
+    /// instrumented, so it may have been used in ways that weren't observed.
+    pub escaped: bool,
+}
+
+impl Default for PointerFacts {
+    /// The facts for a local that was never assigned, which [`PointerFacts::merge`] leaves
+    /// unchanged.
+    fn default() -> Self {
+        Self {
+            load: false,
+            store: false,
+            pos_offset: false,
+            neg_offset: false,
+            unique: true,
+            escaped: false,
+        }
+    }
+}
+
+impl PointerFacts {
+    /// Combine the facts from another pointer stored in the same local.  A local needs a
+    /// capability if any of its values used it, and is only unique if all of its values were.
+    pub fn merge(&mut self, other: &Self) {
+        self.load |= other.load;
+        self.store |= other.store;
+        self.pos_offset |= other.pos_offset;
+        self.neg_offset |= other.neg_offset;
+        self.unique &= other.unique;
+        self.escaped |= other.escaped;
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PdgFacts {
+    /// Facts for each MIR local that was assigned a pointer at run time.  Locals that were never
+    /// assigned, such as those in functions that never ran, have no entry.
+    #[serde(with = "crate::map_as_pairs")]
+    pub locals: HashMap<(FuncId, Local), PointerFacts>,
+}
+
+impl PdgFacts {
+    pub fn get(&self, func: FuncId, local: Local) -> Option<&PointerFacts> {
+        self.locals.get(&(func, local))
+    }
+}
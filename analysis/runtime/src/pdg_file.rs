@@ -0,0 +1,216 @@
+//! A stable on-disk representation of a PDG, as built by `c2rust-pdg`.
+//!
+//! Building a PDG means replaying the whole event log, which can be gigabytes for a long run.
+//! `c2rust-pdg --output <path>` saves the result, including the computed [`NodeInfo`], the
+//! [`Metadata`] that [`Node`]s refer to, and the per-local [`PdgFacts`], so that later tools can
+//! [`read`](PdgFile::read) it back instead.  The types here mirror those in `c2rust-pdg`, but use
+//! plain integers in place of `rustc` index types so that they don't depend on the compiler.
+//!
+//! Files are written as bincode, or as JSON if the path ends in `.json` and the `json` feature
+//! is enabled.  Either way, they start
+//! with a [`PdgFile::version`], which is checked when reading, since neither format can detect
+//! other changes to the layout.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::events::ThreadId;
+use crate::metadata::Metadata;
+use crate::mir_loc::{DefPathHash, Func, FuncId, Local, MirPlace};
+use crate::pdg_facts::PdgFacts;
+
+/// The current [`PdgFile::version`].  Increment this whenever the serialized types change.
+pub const PDG_FILE_VERSION: u32 = 5;
+
+/// The index of a [`Graph`] within [`PdgFile::graphs`].
+pub type GraphId = u32;
+
+/// The index of a [`Node`] within [`Graph::nodes`].
+pub type NodeId = u32;
+
+/// An entry of [`PdgFile::latest_assignment`]: a [`Local`] of a function on a thread,
+/// and the [`Node`] that last assigned to it.
+pub type LatestAssignment = ((ThreadId, FuncId, Local), (GraphId, NodeId));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NodeKind {
+    Copy,
+    Field(u32),
+    Offset(isize),
+    AddrOfLocal(Local),
+    AddrOfStatic(DefPathHash),
+    Alloc(usize),
+    Free,
+    IntToPtr,
+    PtrToInt,
+    LoadValue,
+    LoadAddr,
+    StoreAddr,
+    LoadRange(usize),
+    StoreRange(usize),
+    StoreValue,
+    Escape,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct FlowInfo {
+    pub load: Option<NodeId>,
+    pub store: Option<NodeId>,
+    pub pos_offset: Option<NodeId>,
+    pub neg_offset: Option<NodeId>,
+    pub escape: Option<NodeId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub flows_to: FlowInfo,
+    pub unique: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Node {
+    pub function: Func,
+    pub block: u32,
+    pub statement_idx: usize,
+    pub dest: Option<MirPlace>,
+    pub kind: NodeKind,
+    pub source: Option<NodeId>,
+    pub debug_info: String,
+    pub info: Option<NodeInfo>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdgFile {
+    /// The format version, which must be [`PDG_FILE_VERSION`].  This must stay the first field.
+    pub version: u32,
+    pub graphs: Vec<Graph>,
+    pub latest_assignment: Vec<LatestAssignment>,
+    pub metadata: Metadata,
+    pub facts: PdgFacts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdgFormat {
+    Bincode,
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl PdgFormat {
+    /// JSON for paths ending in `.json`, and bincode otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            #[cfg(feature = "json")]
+            Some(ext) if ext == "json" => Self::Json,
+            _ => Self::Bincode,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PdgFileError {
+    Bincode(bincode::Error),
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    Version {
+        found: u32,
+    },
+}
+
+impl Display for PdgFileError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Bincode(e) => write!(f, "{e}"),
+            #[cfg(feature = "json")]
+            Self::Json(e) => write!(f, "{e}"),
+            Self::Version { found } => write!(
+                f,
+                "PDG file has version {found}, but only version {PDG_FILE_VERSION} is supported"
+            ),
+        }
+    }
+}
+
+impl Error for PdgFileError {}
+
+impl From<bincode::Error> for PdgFileError {
+    fn from(e: bincode::Error) -> Self {
+        Self::Bincode(e)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for PdgFileError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+/// Just the [`PdgFile::version`], so it can be checked before parsing the rest.
+#[cfg(feature = "json")]
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+impl PdgFile {
+    pub fn new(
+        graphs: Vec<Graph>,
+        latest_assignment: Vec<LatestAssignment>,
+        metadata: Metadata,
+        facts: PdgFacts,
+    ) -> Self {
+        Self {
+            version: PDG_FILE_VERSION,
+            graphs,
+            latest_assignment,
+            metadata,
+            facts,
+        }
+    }
+
+    pub fn read(bytes: &[u8], format: PdgFormat) -> Result<Self, PdgFileError> {
+        let version = match format {
+            PdgFormat::Bincode => bincode::deserialize::<u32>(bytes)?,
+            #[cfg(feature = "json")]
+            PdgFormat::Json => serde_json::from_slice::<Version>(bytes)?.version,
+        };
+        if version != PDG_FILE_VERSION {
+            return Err(PdgFileError::Version { found: version });
+        }
+        Ok(match format {
+            PdgFormat::Bincode => bincode::deserialize(bytes)?,
+            #[cfg(feature = "json")]
+            PdgFormat::Json => serde_json::from_slice(bytes)?,
+        })
+    }
+
+    pub fn write(&self, format: PdgFormat) -> Result<Vec<u8>, PdgFileError> {
+        Ok(match format {
+            PdgFormat::Bincode => bincode::serialize(self)?,
+            #[cfg(feature = "json")]
+            PdgFormat::Json => serde_json::to_vec(self)?,
+        })
+    }
+
+    /// Read a PDG file from `path`, in the [`PdgFormat`] given by its extension.
+    pub fn read_path(path: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let bytes = fs_err::read(path)?;
+        Ok(Self::read(&bytes, PdgFormat::from_path(path))?)
+    }
+
+    /// Write this PDG to `path`, in the [`PdgFormat`] given by its extension.
+    pub fn write_path(&self, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let bytes = self.write(PdgFormat::from_path(path))?;
+        fs_err::write(path, bytes)?;
+        Ok(())
+    }
+}
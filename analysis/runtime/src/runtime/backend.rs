@@ -6,6 +6,8 @@ use std::io::{stderr, BufWriter, Write};
 use bincode;
 
 use super::{AnyError, Detect, FINISHED};
+#[cfg(feature = "compressed-log")]
+use crate::event_log::ChunkWriter;
 use crate::events::{Event, EventKind};
 use crate::metadata::Metadata;
 use crate::parse::{self, AsStr, GetChoices};
@@ -21,6 +23,7 @@ pub(super) trait WriteEvent {
 pub enum BackendKind {
     Debug,
     Log,
+    CompressedLog,
 }
 
 impl AsStr for BackendKind {
@@ -28,13 +31,14 @@ impl AsStr for BackendKind {
         match self {
             Self::Debug => "debug",
             Self::Log => "log",
+            Self::CompressedLog => "compressed-log",
         }
     }
 }
 
 impl GetChoices for BackendKind {
     fn choices() -> &'static [Self] {
-        &[Self::Debug, Self::Log]
+        &[Self::Debug, Self::Log, Self::CompressedLog]
     }
 }
 
@@ -51,7 +55,7 @@ pub struct DebugBackend {
 impl WriteEvent for DebugBackend {
     fn write(&mut self, event: Event) {
         let mir_loc = self.metadata.get(event.mir_loc);
-        eprintln!("{:?}: {:?}", mir_loc, event.kind);
+        eprintln!("[thread {}] {:?}: {:?}", event.thread, mir_loc, event.kind);
     }
 
     fn flush(&mut self) {
@@ -73,10 +77,28 @@ impl WriteEvent for LogBackend {
     }
 }
 
+#[cfg(feature = "compressed-log")]
+pub struct CompressedLogBackend {
+    writer: ChunkWriter<BufWriter<File>>,
+}
+
+#[cfg(feature = "compressed-log")]
+impl WriteEvent for CompressedLogBackend {
+    fn write(&mut self, event: Event) {
+        self.writer.write(&event).unwrap();
+    }
+
+    fn flush(&mut self) {
+        self.writer.flush().unwrap();
+    }
+}
+
 #[enum_dispatch(WriteEvent)]
 pub enum Backend {
     Debug(DebugBackend),
     Log(LogBackend),
+    #[cfg(feature = "compressed-log")]
+    CompressedLog(CompressedLogBackend),
 }
 
 impl Backend {
@@ -111,17 +133,41 @@ impl Detect for DebugBackend {
     }
 }
 
+/// Whether to append to `$INSTRUMENT_OUTPUT`, from `$INSTRUMENT_OUTPUT_APPEND`.
+fn detect_append() -> Result<bool, AnyError> {
+    Ok(*parse::env::one_of("INSTRUMENT_OUTPUT_APPEND")?)
+}
+
+/// Open `$INSTRUMENT_OUTPUT` for a log, appending to it if `append`.
+fn detect_log_output(append: bool) -> Result<BufWriter<File>, AnyError> {
+    let path = parse::env::path("INSTRUMENT_OUTPUT")?;
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(&path)?;
+    Ok(BufWriter::new(file))
+}
+
 impl Detect for LogBackend {
     fn detect() -> Result<Self, AnyError> {
-        let path = parse::env::path("INSTRUMENT_OUTPUT")?;
-        let append: bool = *parse::env::one_of("INSTRUMENT_OUTPUT_APPEND")?;
-        let file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(append)
-            .truncate(!append)
-            .open(&path)?;
-        let writer = BufWriter::new(file);
+        let writer = detect_log_output(detect_append()?)?;
+        Ok(Self { writer })
+    }
+}
+
+#[cfg(feature = "compressed-log")]
+impl Detect for CompressedLogBackend {
+    fn detect() -> Result<Self, AnyError> {
+        // Appending to a plain log, or to one ending in a frame truncated by a crash,
+        // would make the whole log unreadable.
+        if detect_append()? {
+            return Err(
+                "$INSTRUMENT_OUTPUT_APPEND must be false for the compressed-log backend".into(),
+            );
+        }
+        let writer = ChunkWriter::new(detect_log_output(false)?);
         Ok(Self { writer })
     }
 }
@@ -137,6 +183,14 @@ impl Backend {
         let this = match kind {
             BackendKind::Debug => Self::Debug(DebugBackend::detect()?),
             BackendKind::Log => Self::Log(LogBackend::detect()?),
+            #[cfg(feature = "compressed-log")]
+            BackendKind::CompressedLog => Self::CompressedLog(CompressedLogBackend::detect()?),
+            #[cfg(not(feature = "compressed-log"))]
+            BackendKind::CompressedLog => {
+                return Err(
+                    "the compressed-log backend requires the `compressed-log` feature".into(),
+                )
+            }
         };
         Ok(this)
     }
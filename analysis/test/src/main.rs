@@ -13,7 +13,14 @@
 pub mod pointers;
 #[path = "stdlib.rs"]
 pub mod stdlib;
+#[path = "threads.rs"]
+pub mod threads;
 
+/// Runs [`pointers::main`], or [`threads::main`] when passed `threads <count>`.
 fn main() {
-    pointers::main()
+    let args = std::env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("threads") => threads::main(args[2].parse().unwrap()),
+        _ => pointers::main(),
+    }
 }
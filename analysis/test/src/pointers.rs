@@ -19,6 +19,15 @@ extern "C" {
     fn calloc(_: libc::c_ulong, _: libc::c_ulong) -> *mut libc::c_void;
     fn realloc(_: *mut libc::c_void, _: libc::c_ulong) -> *mut libc::c_void;
     fn free(__ptr: *mut libc::c_void);
+    fn memcpy(_: *mut libc::c_void, _: *const libc::c_void, _: libc::c_ulong) -> *mut libc::c_void;
+    fn memset(_: *mut libc::c_void, _: libc::c_int, _: libc::c_ulong) -> *mut libc::c_void;
+    fn memmove(_: *mut libc::c_void, _: *const libc::c_void, _: libc::c_ulong) -> *mut libc::c_void;
+    fn memcmp(_: *const libc::c_void, _: *const libc::c_void, _: libc::c_ulong) -> libc::c_int;
+    fn strcpy(_: *mut libc::c_char, _: *const libc::c_char) -> *mut libc::c_char;
+    fn strncpy(_: *mut libc::c_char, _: *const libc::c_char, _: libc::c_ulong) -> *mut libc::c_char;
+    fn strlen(_: *const libc::c_char) -> libc::c_ulong;
+    fn strcmp(_: *const libc::c_char, _: *const libc::c_char) -> libc::c_int;
+    fn strncmp(_: *const libc::c_char, _: *const libc::c_char, _: libc::c_ulong) -> libc::c_int;
 }
 
 #[cfg(not(feature = "miri"))]
@@ -627,6 +636,61 @@ pub unsafe extern "C" fn test_load_value_store_value() {
     free(*ps);
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn test_memcpy_memset() {
+    let s = malloc(::std::mem::size_of::<S>() as libc::c_ulong) as *mut S;
+    let t = malloc(::std::mem::size_of::<S>() as libc::c_ulong) as *mut S;
+    memset(
+        s as *mut libc::c_void,
+        0 as libc::c_int,
+        ::std::mem::size_of::<S>() as libc::c_ulong,
+    );
+    memcpy(
+        t as *mut libc::c_void,
+        s as *const libc::c_void,
+        ::std::mem::size_of::<S>() as libc::c_ulong,
+    );
+    free(t as *mut libc::c_void);
+    free(s as *mut libc::c_void);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn test_strcpy_strlen() {
+    let s = malloc(4 as libc::c_ulong) as *mut libc::c_char;
+    strcpy(s, b"abc\0" as *const u8 as *const libc::c_char);
+    let n = strlen(s);
+    free(s as *mut libc::c_void);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn test_memmove_memcmp() {
+    let s = calloc(2, ::std::mem::size_of::<S>() as libc::c_ulong) as *mut S;
+    // The two ranges are in the same object, and overlap in the case of `memmove`.
+    memmove(
+        s as *mut libc::c_void,
+        s.offset(1) as *const libc::c_void,
+        ::std::mem::size_of::<S>() as libc::c_ulong,
+    );
+    let cmp = memcmp(
+        s as *const libc::c_void,
+        s.offset(1) as *const libc::c_void,
+        ::std::mem::size_of::<S>() as libc::c_ulong,
+    );
+    free(s as *mut libc::c_void);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn test_strncpy_strcmp_strncmp() {
+    let s = malloc(8 as libc::c_ulong) as *mut libc::c_char;
+    let t = malloc(8 as libc::c_ulong) as *mut libc::c_char;
+    strncpy(s, b"abc\0" as *const u8 as *const libc::c_char, 8 as libc::c_ulong);
+    strncpy(t, s, 8 as libc::c_ulong);
+    let eq = strcmp(s, t);
+    let ne = strncmp(s, t.offset(1), 2 as libc::c_ulong);
+    free(t as *mut libc::c_void);
+    free(s as *mut libc::c_void);
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn insertion_sort(n: libc::c_int, p: *mut libc::c_int) {
     let mut i: libc::c_int = 1 as libc::c_int;
@@ -740,6 +804,10 @@ unsafe fn main_0(mut argc: libc::c_int, mut argv: *mut *mut libc::c_char) -> lib
     test_store_value();
     test_store_value_field();
     test_load_value_store_value();
+    test_memcpy_memset();
+    test_strcpy_strlen();
+    test_memmove_memcmp();
+    test_strncpy_strcmp_strncmp();
     let nums = &mut [2i32, 5i32, 3i32, 1i32, 6i32];
     insertion_sort(nums.len() as libc::c_int, nums as *mut libc::c_int);
     test_ref_field();
@@ -0,0 +1,44 @@
+#![allow(dead_code, non_camel_case_types, unused_variables)]
+
+use std::mem;
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+extern "C" {
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    fn free(__ptr: *mut libc::c_void);
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Counter {
+    pub count: libc::c_int,
+}
+
+/// Run by every thread at once.
+///
+/// The [`Barrier`]s make all threads assign `s` before any of them use it,
+/// so each use must be traced back to the assignment made on its own thread.
+pub unsafe fn thread_worker(barrier: &Barrier) {
+    let s = malloc(mem::size_of::<Counter>() as libc::c_ulong) as *mut Counter;
+    barrier.wait();
+    (*s).count = 1;
+    barrier.wait();
+    let count = (*s).count;
+    barrier.wait();
+    free(s as *mut libc::c_void);
+}
+
+/// Run [`thread_worker`] on `threads` threads at once.
+pub fn main(threads: usize) {
+    let barrier = Arc::new(Barrier::new(threads));
+    let handles = (0..threads)
+        .map(|_| {
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || unsafe { thread_worker(&barrier) })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
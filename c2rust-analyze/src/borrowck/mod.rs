@@ -4,7 +4,9 @@ use crate::dataflow::DataflowConstraints;
 use crate::labeled_ty::{LabeledTy, LabeledTyCtxt};
 use crate::pointer_id::PointerTableMut;
 use crate::util::{describe_rvalue, RvalueDesc};
-use rustc_middle::mir::{Body, BorrowKind, Local, LocalKind, Place, StatementKind, START_BLOCK};
+use rustc_middle::mir::{
+    Body, BorrowKind, Local, LocalKind, Place, Rvalue, StatementKind, START_BLOCK,
+};
 use rustc_middle::ty::{List, TyKind};
 use std::collections::HashMap;
 use std::hash::Hash;
@@ -23,13 +25,15 @@ pub struct Label {
 pub type LTy<'tcx> = LabeledTy<'tcx, Label>;
 pub type LTyCtxt<'tcx> = LabeledTyCtxt<'tcx, Label>;
 
+/// Remove `UNIQUE` from pointers in `hypothesis` until polonius accepts the function, or until
+/// no more progress can be made.  Returns the number of polonius iterations that were run.
 pub fn borrowck_mir<'tcx>(
     acx: &AnalysisCtxt<'_, 'tcx>,
     dataflow: &DataflowConstraints,
     hypothesis: &mut PointerTableMut<PermissionSet>,
     name: &str,
     mir: &Body<'tcx>,
-) {
+) -> usize {
     let mut i = 0;
     loop {
         eprintln!("run polonius");
@@ -73,7 +77,15 @@ pub fn borrowck_mir<'tcx>(
                         Some(RvalueDesc::AddrOfLocal { local, proj: _ }) => {
                             acx.addr_of_local[local]
                         }
-                        None => panic!("loan {:?} was issued by unknown rvalue {:?}?", loan, x.1),
+                        // Pointer-to-pointer casts are treated as reborrows of the operand.
+                        None => match x.1 {
+                            Rvalue::Cast(_, ref op, _) => acx
+                                .ptr_of(op)
+                                .unwrap_or_else(|| panic!("missing pointer ID for {:?}", op)),
+                            ref rv => {
+                                panic!("loan {:?} was issued by unknown rvalue {:?}?", loan, rv)
+                            }
+                        },
                     },
                     _ => panic!("loan {:?} was issued by non-assign stmt {:?}?", loan, stmt),
                 };
@@ -100,6 +112,7 @@ pub fn borrowck_mir<'tcx>(
             break;
         }
     }
+    i
 }
 
 fn run_polonius<'tcx>(
@@ -199,6 +212,7 @@ fn run_polonius<'tcx>(
         &mut maps,
         &mut loans,
         &local_ltys,
+        &acx.gacx.fn_summaries,
         mir,
     );
 
@@ -1,13 +1,14 @@
 use crate::borrowck::atoms::{AllFacts, AtomMaps, Loan, Origin, Path, Point, SubPoint};
 use crate::borrowck::{LTy, LTyCtxt, Label};
 use crate::context::PermissionSet;
+use crate::fn_summary::FnSummaries;
 use crate::util::{self, Callee};
 use rustc_index::vec::IndexVec;
 use rustc_middle::mir::{
     BinOp, Body, BorrowKind, Local, LocalDecl, Location, Operand, Place, ProjectionElem, Rvalue,
     Statement, StatementKind, Terminator, TerminatorKind,
 };
-use rustc_middle::ty::{TyCtxt, TyKind};
+use rustc_middle::ty::{Ty, TyCtxt, TyKind};
 use std::collections::HashMap;
 
 struct TypeChecker<'tcx, 'a> {
@@ -18,6 +19,7 @@ struct TypeChecker<'tcx, 'a> {
     loans: &'a mut HashMap<Local, Vec<(Path, Loan, BorrowKind)>>,
     local_ltys: &'a [LTy<'tcx>],
     local_decls: &'a IndexVec<Local, LocalDecl<'tcx>>,
+    fn_summaries: &'a FnSummaries,
 
     current_location: Location,
 }
@@ -80,33 +82,45 @@ impl<'tcx> TypeChecker<'tcx, '_> {
         origin
     }
 
+    /// Reborrow `pl_deref` (the target of a raw pointer) to produce a pointer of type `ty` whose
+    /// pointee has type `pointee_lty`.  The kind of borrow depends on the permissions of
+    /// `expect_ty`.
+    fn reborrow(
+        &mut self,
+        pl_deref: Place<'tcx>,
+        ty: Ty<'tcx>,
+        pointee_lty: LTy<'tcx>,
+        expect_ty: LTy<'tcx>,
+    ) -> LTy<'tcx> {
+        let perm = expect_ty.label.perm;
+        let borrow_kind = if perm.contains(PermissionSet::UNIQUE) {
+            BorrowKind::Mut {
+                allow_two_phase_borrow: false,
+            }
+        } else {
+            BorrowKind::Shared
+        };
+
+        let origin = self.issue_loan(pl_deref, borrow_kind);
+
+        // Return a type with the new loan on the outermost `ref`.
+        let label = Label {
+            origin: Some(origin),
+            perm,
+        };
+        self.ltcx.mk(ty, self.ltcx.mk_slice(&[pointee_lty]), label)
+    }
+
     pub fn visit_rvalue(&mut self, rv: &Rvalue<'tcx>, expect_ty: LTy<'tcx>) -> LTy<'tcx> {
         match *rv {
             Rvalue::Use(Operand::Move(pl)) | Rvalue::Use(Operand::Copy(pl))
                 if matches!(expect_ty.ty.kind(), TyKind::RawPtr(_)) =>
             {
                 // Copy of a raw pointer.  We treat this as a reborrow.
-                let perm = expect_ty.label.perm;
-                let borrow_kind = if perm.contains(PermissionSet::UNIQUE) {
-                    BorrowKind::Mut {
-                        allow_two_phase_borrow: false,
-                    }
-                } else {
-                    BorrowKind::Shared
-                };
-
                 let pl_deref = self.tcx.mk_place_deref(pl);
-                let origin = self.issue_loan(pl_deref, borrow_kind);
-
-                // Return a type with the new loan on the outermost `ref`.
                 let ty = rv.ty(self.local_decls, *self.ltcx);
                 let pl_lty = self.visit_place(pl_deref);
-                let label = Label {
-                    origin: Some(origin),
-                    perm,
-                };
-                let lty = self.ltcx.mk(ty, self.ltcx.mk_slice(&[pl_lty]), label);
-                lty
+                self.reborrow(pl_deref, ty, pl_lty, expect_ty)
             }
 
             Rvalue::Use(ref op) => self.visit_operand(op),
@@ -152,17 +166,47 @@ impl<'tcx> TypeChecker<'tcx, '_> {
             Rvalue::BinaryOp(BinOp::Offset, _) | Rvalue::CheckedBinaryOp(BinOp::Offset, _) => {
                 todo!("visit_rvalue BinOp::Offset")
             }
-            Rvalue::BinaryOp(_, ref _ab) | Rvalue::CheckedBinaryOp(_, ref _ab) => {
+            Rvalue::BinaryOp(..) | Rvalue::CheckedBinaryOp(..) | Rvalue::UnaryOp(..) => {
                 let ty = rv.ty(self.local_decls, *self.ltcx);
                 self.ltcx.label(ty, &mut |ty| {
                     assert!(
                         !matches!(ty.kind(), TyKind::RawPtr(..) | TyKind::Ref(..)),
-                        "pointer BinaryOp NYI"
+                        "pointer {:?} NYI",
+                        rv
                     );
                     Label::default()
                 })
             }
 
+            Rvalue::Cast(_, ref op @ (Operand::Move(pl) | Operand::Copy(pl)), ty)
+                if matches!(ty.kind(), TyKind::RawPtr(_)) =>
+            {
+                // Pointer-to-pointer cast, such as `malloc(n) as *mut T`.  The pointee type may
+                // change, in which case it contains no pointers (see the `TypeOf` impl for
+                // `Rvalue`).
+                let pl_deref = self.tcx.mk_place_deref(pl);
+                let pl_lty = self.visit_place(pl_deref);
+                let pointee_ty = match *ty.kind() {
+                    TyKind::RawPtr(tm) => tm.ty,
+                    _ => unreachable!(),
+                };
+                let pointee_lty = if pl_lty.ty == pointee_ty {
+                    pl_lty
+                } else {
+                    self.ltcx.label(pointee_ty, &mut |_| Label::default())
+                };
+                match *op {
+                    // Like a plain copy, a cast of a copied pointer is a reborrow.
+                    Operand::Copy(_) => self.reborrow(pl_deref, ty, pointee_lty, expect_ty),
+                    // The source of a move is dead afterward, so the result simply takes over
+                    // its origin.
+                    _ => {
+                        let label = self.visit_place(pl).label;
+                        self.ltcx.mk(ty, self.ltcx.mk_slice(&[pointee_lty]), label)
+                    }
+                }
+            }
+
             Rvalue::Cast(_, _, ty) => self.ltcx.label(ty, &mut |ty| {
                 assert!(
                     !matches!(ty.kind(), TyKind::RawPtr(..) | TyKind::Ref(..)),
@@ -223,6 +267,22 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                         let rv_lty = self.visit_operand(&args[0]);
                         self.do_assign(pl_lty, rv_lty);
                     }
+                    Some(Callee::Other { def_id, .. })
+                        if !def_id.is_local() || self.tcx.is_foreign_item(def_id) =>
+                    {
+                        // A function with a summary whose result is derived from one of its
+                        // arguments is also handled like a pointer assignment.
+                        let name = self.tcx.item_name(def_id);
+                        let ret_alias = self
+                            .fn_summaries
+                            .get(name.as_str())
+                            .and_then(|summary| summary.ret_alias);
+                        if let Some(i) = ret_alias {
+                            let pl_lty = self.visit_place(destination);
+                            let rv_lty = self.visit_operand(&args[i]);
+                            self.do_assign(pl_lty, rv_lty);
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -232,6 +292,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn visit<'tcx>(
     tcx: TyCtxt<'tcx>,
     ltcx: LTyCtxt<'tcx>,
@@ -239,6 +300,7 @@ pub fn visit<'tcx>(
     maps: &mut AtomMaps<'tcx>,
     loans: &mut HashMap<Local, Vec<(Path, Loan, BorrowKind)>>,
     local_ltys: &[LTy<'tcx>],
+    fn_summaries: &FnSummaries,
     mir: &Body<'tcx>,
 ) {
     let mut tc = TypeChecker {
@@ -249,6 +311,7 @@ pub fn visit<'tcx>(
         loans,
         local_ltys,
         local_decls: &mir.local_decls,
+        fn_summaries,
         current_location: Location::START,
     };
 
@@ -1,9 +1,10 @@
+use crate::fn_summary::FnSummaries;
 use crate::labeled_ty::{LabeledTy, LabeledTyCtxt};
 use crate::pointer_id::{
     GlobalPointerTable, LocalPointerTable, NextGlobalPointerId, NextLocalPointerId, PointerTable,
     PointerTableMut,
 };
-use crate::util::{describe_rvalue, RvalueDesc};
+use crate::util::{self, describe_rvalue, RvalueDesc};
 use bitflags::bitflags;
 use rustc_hir::def_id::DefId;
 use rustc_index::vec::IndexVec;
@@ -11,6 +12,7 @@ use rustc_middle::mir::{
     Body, HasLocalDecls, Local, LocalDecls, Operand, Place, PlaceElem, PlaceRef, ProjectionElem,
     Rvalue,
 };
+use rustc_middle::ty::subst::GenericArgKind;
 use rustc_middle::ty::{Ty, TyCtxt, TyKind};
 use std::collections::HashMap;
 use std::ops::Index;
@@ -37,6 +39,9 @@ bitflags! {
         const OFFSET_ADD = 0x0010;
         /// This pointer can be offset in the negative direction.
         const OFFSET_SUB = 0x0020;
+        /// This pointer owns the heap object it points to, which may be freed through it.  Such
+        /// pointers are converted to `Box<T>` if they are also `UNIQUE` and `Rc<T>` otherwise.
+        const FREE = 0x0040;
     }
 }
 
@@ -53,6 +58,24 @@ bitflags! {
     }
 }
 
+impl PermissionSet {
+    /// The name of each flag, as written in the JSON output and function summaries.
+    pub const NAMES: &'static [(&'static str, PermissionSet)] = &[
+        ("READ", PermissionSet::READ),
+        ("WRITE", PermissionSet::WRITE),
+        ("UNIQUE", PermissionSet::UNIQUE),
+        ("LINEAR", PermissionSet::LINEAR),
+        ("OFFSET_ADD", PermissionSet::OFFSET_ADD),
+        ("OFFSET_SUB", PermissionSet::OFFSET_SUB),
+        ("FREE", PermissionSet::FREE),
+    ];
+}
+
+impl FlagSet {
+    /// The name of each flag, as written in the JSON output.
+    pub const NAMES: &'static [(&'static str, FlagSet)] = &[("CELL", FlagSet::CELL)];
+}
+
 pub use crate::pointer_id::PointerId;
 
 pub type LTy<'tcx> = LabeledTy<'tcx, PointerId>;
@@ -69,6 +92,8 @@ pub struct GlobalAnalysisCtxt<'tcx> {
     pub lcx: LTyCtxt<'tcx>,
 
     pub fn_sigs: HashMap<DefId, LFnSig<'tcx>>,
+    /// Summaries for functions without MIR bodies, used in place of `fn_sigs`.
+    pub fn_summaries: FnSummaries,
 
     next_ptr_id: NextGlobalPointerId,
 }
@@ -95,6 +120,7 @@ impl<'tcx> GlobalAnalysisCtxt<'tcx> {
             tcx,
             lcx: LabeledTyCtxt::new(tcx),
             fn_sigs: HashMap::new(),
+            fn_summaries: FnSummaries::builtin(),
             next_ptr_id: NextGlobalPointerId::new(),
         }
     }
@@ -131,6 +157,7 @@ impl<'tcx> GlobalAnalysisCtxt<'tcx> {
             tcx: _,
             lcx,
             ref mut fn_sigs,
+            fn_summaries: _,
             ref mut next_ptr_id,
         } = *self;
 
@@ -411,6 +438,20 @@ impl<'tcx> TypeOf<'tcx> for Rvalue<'tcx> {
                     // same structure.
                     return acx.lcx().mk(ty, op_lty.args, op_lty.label);
                 }
+                if let (Some(op_pointee), Some(ty_pointee)) = (op_pointee, ty_pointee) {
+                    let tcx = acx.tcx();
+                    if (util::is_c_void(tcx, op_pointee.ty) || util::is_c_void(tcx, ty_pointee))
+                        && !has_pointers(op_pointee.ty)
+                        && !has_pointers(ty_pointee)
+                    {
+                        // A cast to or from `*mut c_void`, as in `malloc(n) as *mut T` or
+                        // `free(p as *mut c_void)`.  The target keeps the `PointerId` of the
+                        // source.  We only support this when neither pointee contains pointers,
+                        // since those would have nothing to be unified with.
+                        let args = acx.lcx().mk_slice(&[label_no_pointers(acx, ty_pointee)]);
+                        return acx.lcx().mk(ty, args, op_lty.label);
+                    }
+                }
 
                 label_no_pointers(acx, ty)
             }
@@ -429,6 +470,14 @@ impl<'tcx> TypeOf<'tcx> for Rvalue<'tcx> {
     }
 }
 
+/// Returns `true` if `ty` contains any reference or raw pointer types.
+fn has_pointers(ty: Ty) -> bool {
+    ty.walk().any(|arg| match arg.unpack() {
+        GenericArgKind::Type(ty) => matches!(ty.kind(), TyKind::Ref(..) | TyKind::RawPtr(..)),
+        _ => false,
+    })
+}
+
 /// Label a type that contains no pointer types by applying `PointerId::NONE` everywhere.  Panics
 /// if the type does contain pointers.
 fn label_no_pointers<'tcx>(acx: &AnalysisCtxt<'_, 'tcx>, ty: Ty<'tcx>) -> LTy<'tcx> {
@@ -30,8 +30,7 @@ impl DataflowConstraints {
         self.constraints.push(Constraint::AllPerms(ptr, perms));
     }
 
-    #[allow(dead_code)]
-    fn _add_no_perms(&mut self, ptr: PointerId, perms: PermissionSet) {
+    fn add_no_perms(&mut self, ptr: PointerId, perms: PermissionSet) {
         self.constraints.push(Constraint::NoPerms(ptr, perms));
     }
 
@@ -72,7 +71,8 @@ impl DataflowConstraints {
                 let PROPAGATE_UP = PermissionSet::READ
                     | PermissionSet::WRITE
                     | PermissionSet::OFFSET_ADD
-                    | PermissionSet::OFFSET_SUB;
+                    | PermissionSet::OFFSET_SUB
+                    | PermissionSet::FREE;
 
                 (
                     old_a & !(!old_b & PROPAGATE_DOWN),
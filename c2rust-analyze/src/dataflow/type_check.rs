@@ -101,9 +101,18 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                 Rvalue::BinaryOp(..) => PointerId::NONE,
                 Rvalue::CheckedBinaryOp(BinOp::Offset, _) => todo!("visit_rvalue BinOp::Offset"),
                 Rvalue::CheckedBinaryOp(..) => PointerId::NONE,
-                Rvalue::Cast(_, _, ty) => {
-                    assert!(!matches!(ty.kind(), TyKind::RawPtr(..) | TyKind::Ref(..)));
-                    PointerId::NONE
+                // `Neg` and `Not` only apply to numbers and `bool`s.
+                Rvalue::UnaryOp(..) => PointerId::NONE,
+                Rvalue::Cast(_, ref op, ty) => {
+                    // Supported pointer-to-pointer casts keep the `PointerId` of the operand; see
+                    // the `TypeOf` impl for `Rvalue`.
+                    let ptr = self.acx.type_of(rv).label;
+                    if ptr == PointerId::NONE {
+                        assert!(!matches!(ty.kind(), TyKind::RawPtr(..) | TyKind::Ref(..)));
+                    } else {
+                        self.visit_operand(op);
+                    }
+                    ptr
                 }
                 _ => panic!("TODO: handle assignment of {:?}", rv),
             },
@@ -196,9 +205,22 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                         let rv_lty = self.visit_operand(&args[0]);
                         self.do_assign(pl_lty.label, rv_lty.label);
                         self.do_unify_pointees(pl_lty, rv_lty);
-                        let perms = PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB;
+                        let perms = util::offset_perms(tcx, self.mir, &args[1]);
                         self.constraints.add_all_perms(rv_lty.label, perms);
                     }
+                    Some(Callee::Malloc | Callee::Calloc) => {
+                        // The result points to a new heap object, so it's not derived from any
+                        // other pointer.
+                        self.visit_place(destination, Mutability::Mut);
+                    }
+                    Some(Callee::Free) => {
+                        // Freeing through a pointer requires it to own the object.  `FREE` is
+                        // then propagated back to the allocation.
+                        assert!(args.len() == 1);
+                        let rv_lty = self.visit_operand(&args[0]);
+                        self.constraints
+                            .add_all_perms(rv_lty.label, PermissionSet::FREE);
+                    }
                     Some(Callee::Other { def_id, substs }) => {
                         self.visit_call_other(def_id, substs, args, destination);
                     }
@@ -219,7 +241,10 @@ impl<'tcx> TypeChecker<'tcx, '_> {
     ) {
         let sig = match self.acx.gacx.fn_sigs.get(&def_id) {
             Some(&x) => x,
-            None => todo!("call to unknown function {def_id:?}"),
+            None => {
+                self.visit_call_summary(def_id, args, dest);
+                return;
+            }
         };
         if substs.non_erasable_generics().next().is_some() {
             todo!("call to generic function {def_id:?} {substs:?}");
@@ -240,6 +265,68 @@ impl<'tcx> TypeChecker<'tcx, '_> {
     }
 }
 
+impl<'tcx> TypeChecker<'tcx, '_> {
+    /// Handle a call to a function with no MIR body, using its summary from
+    /// [`FnSummaries`][crate::fn_summary::FnSummaries] if it has one.
+    fn visit_call_summary(&mut self, def_id: DefId, args: &[Operand<'tcx>], dest: Place<'tcx>) {
+        let tcx = self.acx.tcx();
+        let name = tcx.item_name(def_id);
+        let summary = match self.acx.gacx.fn_summaries.get(name.as_str()) {
+            Some(x) => x,
+            None => {
+                // We don't know what the callee does with its arguments, so assume the worst:
+                // they may be used in any way, and aliases may be retained.
+                eprintln!(
+                    "no summary for {:?}; treating its pointer arguments as opaque",
+                    def_id
+                );
+                let perms = PermissionSet::READ
+                    | PermissionSet::WRITE
+                    | PermissionSet::OFFSET_ADD
+                    | PermissionSet::OFFSET_SUB;
+                for arg_op in args {
+                    let arg_lty = self.visit_operand(arg_op);
+                    for lty in arg_lty.iter() {
+                        if lty.label != PointerId::NONE {
+                            self.constraints.add_all_perms(lty.label, perms);
+                            self.constraints
+                                .add_no_perms(lty.label, PermissionSet::UNIQUE);
+                        }
+                    }
+                }
+                self.visit_place(dest, Mutability::Mut);
+                return;
+            }
+        };
+
+        let mut arg_ltys = Vec::with_capacity(args.len());
+        for (i, arg_op) in args.iter().enumerate() {
+            let arg_lty = self.visit_operand(arg_op);
+            if arg_lty.label != PointerId::NONE {
+                self.constraints
+                    .add_all_perms(arg_lty.label, summary.arg_perms(i));
+            }
+            arg_ltys.push(arg_lty);
+        }
+
+        let dest_lty = self.visit_place(dest, Mutability::Mut);
+        if dest_lty.label == PointerId::NONE {
+            return;
+        }
+        self.constraints.add_all_perms(dest_lty.label, summary.ret);
+        if let Some(i) = summary.ret_alias {
+            // The result is derived from argument `i`, like the result of `offset`.
+            let arg_lty = arg_ltys[i];
+            if arg_lty.label != PointerId::NONE {
+                self.do_assign(dest_lty.label, arg_lty.label);
+                if dest_lty.args[0].ty == arg_lty.args[0].ty {
+                    self.do_unify_pointees(dest_lty, arg_lty);
+                }
+            }
+        }
+    }
+}
+
 pub fn visit<'tcx>(
     acx: &AnalysisCtxt<'_, 'tcx>,
     mir: &Body<'tcx>,
@@ -3,10 +3,12 @@ use crate::pointer_id::PointerTable;
 use crate::type_desc::{self, Ownership, Quantity};
 use crate::util::{self, Callee};
 use rustc_middle::mir::{
-    BasicBlock, Body, Location, Operand, Place, Rvalue, Statement, StatementKind, Terminator,
-    TerminatorKind,
+    BasicBlock, Body, Local, LocalKind, Location, Operand, Place, Rvalue, Statement, StatementKind,
+    Terminator, TerminatorKind,
 };
 use rustc_span::{Span, DUMMY_SP};
+use serde::Serialize;
+use std::collections::HashSet;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct ExprLoc {
@@ -15,7 +17,7 @@ pub struct ExprLoc {
     pub sub: Vec<SubLoc>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
 pub enum SubLoc {
     /// The LHS of an assignment or call.  `StatementKind::Assign/TerminatorKind::Call -> Place`
     Dest,
@@ -31,7 +33,7 @@ pub enum SubLoc {
     PlacePointer(usize),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
 pub enum RewriteKind {
     /// Replace `ptr.offset(i)` with something like `&ptr[i..]`.
     OffsetSlice { mutbl: bool },
@@ -39,6 +41,25 @@ pub enum RewriteKind {
     SliceFirst { mutbl: bool },
     /// Replace `ptr` with `&*ptr`, converting `&mut T` to `&T`.
     MutToImm,
+    /// Replace `ptr.offset(i)`, where `ptr` is an `OffsetPtr`, with something like
+    /// `(ptr.0, (ptr.1 as isize + i) as usize)`.
+    OffsetOffsetPtr { mutbl: bool },
+    /// Replace `slice` with `(slice, 0)`, converting a slice to an `OffsetPtr`.
+    SliceToOffsetPtr,
+    /// Replace `ptr` with `&ptr.0[ptr.1..]`, converting an `OffsetPtr` to a slice.
+    OffsetPtrToSlice { mutbl: bool },
+    /// Replace `ptr` with `&ptr.0[ptr.1]`, converting an `OffsetPtr` to a single reference.
+    OffsetPtrCurrent { mutbl: bool },
+    /// Replace `ptr` with `&*ptr` or `&mut *ptr`, borrowing from an owning `Box` or `Rc`.
+    Borrow { mutbl: bool },
+    /// Replace `malloc(n) as *mut T` with a new `Box<T>` or `Rc<T>`, initialized with zeroes.  If
+    /// the result is a reference, the `Box` is leaked, since the C code never frees it.  `cell`
+    /// wraps the value in `Cell`.
+    Alloc { own: Ownership, cell: bool },
+    /// Replace `free(ptr as *mut c_void)` with `drop(ptr)`, where `ptr` is a `Box` or `Rc`.
+    Free,
+    /// Replace `ptr` with `Rc::clone(&ptr)`, when an `Rc` is copied into another variable.
+    CloneRc,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -54,6 +75,8 @@ struct ExprRewriteVisitor<'a, 'tcx> {
     rewrites: &'a mut Vec<ExprRewrite>,
     mir: &'a Body<'tcx>,
     loc: ExprLoc,
+    /// Locals holding the result of a `malloc` or `calloc` call.
+    alloc_results: HashSet<Local>,
 }
 
 impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
@@ -65,6 +88,22 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
     ) -> ExprRewriteVisitor<'a, 'tcx> {
         let perms = asn.perms();
         let flags = asn.flags();
+        let tcx = acx.tcx();
+        let alloc_results = mir
+            .basic_blocks()
+            .iter()
+            .filter_map(|bb| match bb.terminator().kind {
+                TerminatorKind::Call {
+                    ref func,
+                    destination,
+                    ..
+                } => match util::ty_callee(tcx, func.ty(mir, tcx)) {
+                    Some(Callee::Malloc | Callee::Calloc) => destination.as_local(),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
         ExprRewriteVisitor {
             acx,
             perms,
@@ -79,6 +118,7 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
                 span: DUMMY_SP,
                 sub: Vec::new(),
             },
+            alloc_results,
         }
     }
 
@@ -128,6 +168,9 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
                 let (pl, ref rv) = **x;
                 let pl_ty = self.acx.type_of(pl);
                 self.enter_assign_rvalue(|v| v.visit_rvalue(rv, pl_ty));
+                if let Rvalue::Use(Operand::Copy(rv_pl)) = *rv {
+                    self.enter_assign_rvalue(|v| v.visit_rc_copy(rv_pl, pl));
+                }
                 // TODO: visit place
             }
             StatementKind::FakeRead(..) => {}
@@ -177,6 +220,10 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
                             self.visit_ptr_offset(&args[0], pl_ty);
                             return;
                         }
+                        Callee::Free => {
+                            self.visit_free(&args[0]);
+                            return;
+                        }
                         _ => {}
                     }
                 }
@@ -229,8 +276,15 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
             Rvalue::Len(_pl) => {
                 // TODO
             }
-            Rvalue::Cast(_kind, ref _op, _ty) => {
-                // TODO
+            Rvalue::Cast(_kind, ref op, _ty) => {
+                let is_alloc = op
+                    .place()
+                    .and_then(|pl| pl.as_local())
+                    .map_or(false, |l| self.alloc_results.contains(&l));
+                if is_alloc {
+                    self.visit_alloc(expect_ty);
+                }
+                // TODO: other casts
             }
             Rvalue::BinaryOp(_bop, ref _ops) => {
                 // TODO
@@ -298,26 +352,97 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
         let (result_own, result_qty) =
             type_desc::perms_to_desc(self.perms[result_ptr], self.flags[result_ptr]);
 
+        // The argument has at least the permissions of the result, so if the result moves
+        // backwards, so does the argument.  The argument may still be an `OffsetPtr` when the
+        // result is not, in which case the offset is done on the `OffsetPtr` and the result is
+        // converted afterward.
+        let arg_is_offset_ptr =
+            op.place()
+                .and_then(|pl| self.acx.ptr_of(pl))
+                .map_or(false, |ptr| {
+                    let (_, qty) = type_desc::perms_to_desc(self.perms[ptr], self.flags[ptr]);
+                    qty == Quantity::OffsetPtr
+                });
+
         let arg_expect_own = result_own;
-        // TODO: infer `arg_expect_qty` based on the type of offset this is (positive / unknown)
-        let arg_expect_qty = match result_qty {
-            Quantity::Single => Quantity::Slice,
-            Quantity::Slice => Quantity::Slice,
-            Quantity::OffsetPtr => todo!("OffsetPtr"),
+        let arg_expect_qty = if arg_is_offset_ptr {
+            Quantity::OffsetPtr
+        } else {
+            Quantity::Slice
         };
 
         self.enter_call_arg(0, |v| {
             v.visit_operand_desc(op, arg_expect_own, arg_expect_qty)
         });
 
-        // Emit `OffsetSlice` for the offset itself.
         let mutbl = matches!(result_own, Ownership::Mut);
 
+        if arg_is_offset_ptr {
+            // Emit `OffsetOffsetPtr` for the offset itself, then convert the result if needed.
+            self.emit(RewriteKind::OffsetOffsetPtr { mutbl });
+            match result_qty {
+                Quantity::Single => self.emit(RewriteKind::OffsetPtrCurrent { mutbl }),
+                Quantity::Slice => self.emit(RewriteKind::OffsetPtrToSlice { mutbl }),
+                Quantity::OffsetPtr => {}
+            }
+            return;
+        }
+
+        // Emit `OffsetSlice` for the offset itself.
         self.emit(RewriteKind::OffsetSlice { mutbl });
 
-        // If the result is `Single`, also insert an upcast.
-        if result_qty == Quantity::Single {
-            self.emit(RewriteKind::SliceFirst { mutbl });
+        // If the result is `Single` or `OffsetPtr`, also insert an upcast.
+        match result_qty {
+            Quantity::Single => self.emit(RewriteKind::SliceFirst { mutbl }),
+            Quantity::Slice => {}
+            Quantity::OffsetPtr => self.emit(RewriteKind::SliceToOffsetPtr),
+        }
+    }
+
+    /// Replace the `malloc(n) as *mut T` cast that produces `result_ty` with an owned allocation.
+    fn visit_alloc(&mut self, result_ty: LTy<'tcx>) {
+        let ptr = result_ty.label;
+        if ptr == PointerId::NONE {
+            return;
+        }
+        let (own, qty) = type_desc::perms_to_desc(self.perms[ptr], self.flags[ptr]);
+        if qty != Quantity::Single || matches!(own, Ownership::Raw | Ownership::RawMut) {
+            return;
+        }
+        let cell = own == Ownership::Cell
+            || (own == Ownership::Rc && self.flags[ptr].contains(FlagSet::CELL));
+        self.emit(RewriteKind::Alloc { own, cell });
+    }
+
+    fn visit_free(&mut self, op: &Operand<'tcx>) {
+        let ptr = match op.place().and_then(|pl| self.acx.ptr_of(pl)) {
+            Some(ptr) => ptr,
+            None => return,
+        };
+        let (own, qty) = type_desc::perms_to_desc(self.perms[ptr], self.flags[ptr]);
+        if qty == Quantity::Single && matches!(own, Ownership::Box | Ownership::Rc) {
+            self.emit(RewriteKind::Free);
+        }
+    }
+
+    /// Copying an `Rc` into a user variable leaves both variables owning the object, so the copy
+    /// needs an explicit clone.  Copies into temporaries are usually moved out right away, as in
+    /// call arguments, and don't get one.
+    fn visit_rc_copy(&mut self, src: Place<'tcx>, dest: Place<'tcx>) {
+        let is_var = dest
+            .as_local()
+            .map_or(false, |l| self.mir.local_kind(l) == LocalKind::Var);
+        if !is_var {
+            return;
+        }
+        let (src_ptr, dest_ptr) = match (self.acx.ptr_of(src), self.acx.ptr_of(dest)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return,
+        };
+        let src_own = type_desc::perms_to_desc(self.perms[src_ptr], self.flags[src_ptr]).0;
+        let dest_own = type_desc::perms_to_desc(self.perms[dest_ptr], self.flags[dest_ptr]).0;
+        if src_own == Ownership::Rc && dest_own == Ownership::Rc {
+            self.emit(RewriteKind::CloneRc);
         }
     }
 
@@ -358,6 +483,40 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
             return;
         }
 
+        if qty1 == qty2
+            && qty1 != Quantity::OffsetPtr
+            && matches!(own1, Ownership::Box | Ownership::Rc)
+            && matches!(own2, Ownership::Imm | Ownership::Cell | Ownership::Mut)
+            && (own1, own2) != (Ownership::Rc, Ownership::Mut)
+        {
+            let mutbl = own2 == Ownership::Mut;
+            self.emit(RewriteKind::Borrow { mutbl });
+            return;
+        }
+
+        if own1 == own2 || (own1, own2) == (Ownership::Mut, Ownership::Imm) {
+            let mutbl = own2 == Ownership::Mut;
+            let kind = match (qty1, qty2) {
+                (Quantity::Slice, Quantity::Single) => Some(RewriteKind::SliceFirst { mutbl }),
+                (Quantity::Slice, Quantity::OffsetPtr) => Some(RewriteKind::SliceToOffsetPtr),
+                (Quantity::OffsetPtr, Quantity::Single) => {
+                    Some(RewriteKind::OffsetPtrCurrent { mutbl })
+                }
+                (Quantity::OffsetPtr, Quantity::Slice) => {
+                    Some(RewriteKind::OffsetPtrToSlice { mutbl })
+                }
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                // Converting the quantity of a `&mut` and producing a `&` reborrows immutably.
+                if own1 != own2 && kind == RewriteKind::SliceToOffsetPtr {
+                    self.emit(RewriteKind::MutToImm);
+                }
+                self.emit(kind);
+                return;
+            }
+        }
+
         eprintln!(
             "unsupported cast kind: {:?} {:?} -> {:?}",
             self.perms[ptr],
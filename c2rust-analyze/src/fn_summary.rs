@@ -0,0 +1,91 @@
+//! Summaries of the pointer behavior of functions whose bodies we can't analyze, such as libc
+//! functions and other FFI declarations.  A table of common libc functions is built in, and more
+//! summaries can be loaded from a JSON file with `--fn-summaries <path>`.  The file maps function
+//! names to summaries:
+//!
+//! ```json
+//! {
+//!     "memcpy": {
+//!         "args": [["WRITE", "OFFSET_ADD"], ["READ", "OFFSET_ADD"], []],
+//!         "ret_alias": 0
+//!     }
+//! }
+//! ```
+//!
+//! Summaries loaded from a file replace any built-in summary for the same function.
+use crate::context::PermissionSet;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FnSummary {
+    /// The permissions the function requires of each pointer argument.  Arguments past the end of
+    /// this list get `varargs`.
+    #[serde(default)]
+    pub args: Vec<PermissionSet>,
+    /// The permissions the function requires of each variadic argument, as with the strings
+    /// passed to `printf`.
+    #[serde(default)]
+    pub varargs: PermissionSet,
+    /// The permissions that callers must grant to the returned pointer.
+    #[serde(default)]
+    pub ret: PermissionSet,
+    /// The index of the argument that the returned pointer is derived from, as in `memcpy` or
+    /// `strchr`.  If this is unset, the returned pointer is unrelated to the arguments.
+    #[serde(default)]
+    pub ret_alias: Option<usize>,
+}
+
+impl FnSummary {
+    pub fn arg_perms(&self, i: usize) -> PermissionSet {
+        self.args.get(i).copied().unwrap_or(self.varargs)
+    }
+}
+
+impl<'de> Deserialize<'de> for PermissionSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        let mut perms = PermissionSet::empty();
+        for name in names {
+            perms |= match PermissionSet::NAMES.iter().find(|&&(n, _)| n == name) {
+                Some(&(_, perm)) => perm,
+                None => return Err(de::Error::custom(format!("unknown permission {:?}", name))),
+            };
+        }
+        Ok(perms)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FnSummaries {
+    summaries: HashMap<String, FnSummary>,
+}
+
+impl FnSummaries {
+    /// The built-in summaries for common libc functions.  `malloc`, `calloc`, and `free` are
+    /// handled separately, as [`Callee::Malloc`][crate::util::Callee::Malloc] and so on.
+    pub fn builtin() -> FnSummaries {
+        let src = include_str!("fn_summaries.json");
+        FnSummaries {
+            summaries: serde_json::from_str(src)
+                .unwrap_or_else(|e| panic!("failed to parse built-in function summaries: {}", e)),
+        }
+    }
+
+    /// Load the summaries in `path`, replacing any existing summaries for the same functions.
+    pub fn load(&mut self, path: &Path) {
+        let src =
+            fs::read_to_string(path).unwrap_or_else(|e| panic!("failed to read {:?}: {}", path, e));
+        let summaries: HashMap<String, FnSummary> = serde_json::from_str(&src)
+            .unwrap_or_else(|e| panic!("failed to parse function summaries in {:?}: {}", path, e));
+        self.summaries.extend(summaries);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FnSummary> {
+        self.summaries.get(name)
+    }
+}
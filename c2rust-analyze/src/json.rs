@@ -0,0 +1,175 @@
+//! Machine-readable summary of the analysis results, written when `--output-json <path>` is
+//! passed.  The output is deterministic for a given input, so runs on different revisions can be
+//! diffed directly.
+use crate::context::{AnalysisCtxt, Assignment, FlagSet, LTy, PermissionSet, PointerId};
+use crate::expr_rewrite::{ExprRewrite, RewriteKind, SubLoc};
+use crate::type_desc::{self, Ownership, Quantity};
+use rustc_hir::def_id::LocalDefId;
+use rustc_middle::mir::{Body, LocalDecl};
+use rustc_middle::ty::TyCtxt;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::BitAnd;
+use std::path::Path;
+
+#[derive(Serialize, Default)]
+pub struct AnalysisOutput {
+    /// The number of iterations of the global fixpoint loop.
+    pub fixpoint_iterations: usize,
+    /// Map from each global `PointerId` assigned during the initial pass to the `PointerId` of
+    /// its equivalence class.
+    pub global_equiv_map: BTreeMap<PointerId, PointerId>,
+    pub functions: Vec<FunctionOutput>,
+}
+
+#[derive(Serialize)]
+pub struct FunctionOutput {
+    pub name: String,
+    pub def_path: String,
+    /// The stable hash of the function's `DefPath`, as a hex string.
+    pub def_path_hash: String,
+    pub span: String,
+    /// Like [`AnalysisOutput::global_equiv_map`], but for this function's local `PointerId`s.
+    pub local_equiv_map: BTreeMap<PointerId, PointerId>,
+    /// The total number of polonius iterations run on this function, across all iterations of
+    /// the global fixpoint loop.
+    pub polonius_iterations: usize,
+    pub locals: Vec<LocalOutput>,
+    pub rewrites: Vec<RewriteOutput>,
+}
+
+#[derive(Serialize)]
+pub struct LocalOutput {
+    /// The MIR name of the local, such as `_1`.
+    pub local: String,
+    pub span: String,
+    pub ty: String,
+    /// The type after rewriting pointers according to the final assignment.
+    pub rewritten_ty: String,
+    /// The pointer representing the address of the local itself.
+    pub addr_of: PointerOutput,
+    /// The pointers appearing in the type of the local, in preorder.
+    pub pointers: Vec<PointerOutput>,
+}
+
+#[derive(Serialize)]
+pub struct PointerOutput {
+    pub id: PointerId,
+    pub perms: Vec<String>,
+    pub flags: Vec<String>,
+    /// The `Ownership` and `Quantity` derived from `perms` and `flags`.  These are omitted for
+    /// `addr_of` pointers, which aren't rewritten.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<Ownership>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<Quantity>,
+}
+
+#[derive(Serialize)]
+pub struct RewriteOutput {
+    /// The MIR statement or terminator, such as `bb1[4]`.
+    pub stmt: String,
+    pub span: String,
+    pub sub: Vec<SubLoc>,
+    pub kinds: Vec<RewriteKind>,
+}
+
+impl Serialize for PointerId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Get the names of the flags set in `bits`, in the order they're listed in `names`.
+fn flag_names<T>(bits: T, names: &[(&'static str, T)]) -> Vec<String>
+where
+    T: Copy + BitAnd<Output = T> + PartialEq,
+{
+    names
+        .iter()
+        .filter(|&&(_, flag)| bits & flag == flag)
+        .map(|&(name, _)| name.to_owned())
+        .collect()
+}
+
+fn describe_pointer(asn: &Assignment, ptr: PointerId, with_desc: bool) -> PointerOutput {
+    let perms = asn.perms()[ptr];
+    let flags = asn.flags()[ptr];
+    let (ownership, quantity) = if with_desc {
+        let (own, qty) = type_desc::perms_to_desc(perms, flags);
+        (Some(own), Some(qty))
+    } else {
+        (None, None)
+    };
+    PointerOutput {
+        id: ptr,
+        perms: flag_names(perms, PermissionSet::NAMES),
+        flags: flag_names(flags, FlagSet::NAMES),
+        ownership,
+        quantity,
+    }
+}
+
+fn describe_local<'tcx>(
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    asn: &Assignment,
+    local: rustc_middle::mir::Local,
+    decl: &LocalDecl<'tcx>,
+) -> LocalOutput {
+    let tcx = acx.tcx();
+    let lty: LTy = acx.local_tys[local];
+    LocalOutput {
+        local: format!("{:?}", local),
+        span: crate::describe_local(tcx, decl),
+        ty: format!("{:?}", decl.ty),
+        rewritten_ty: format!("{:?}", type_desc::convert_type(acx, lty, asn)),
+        addr_of: describe_pointer(asn, acx.addr_of_local[local], false),
+        pointers: lty
+            .iter()
+            .filter(|lty| lty.label != PointerId::NONE)
+            .map(|lty| describe_pointer(asn, lty.label, true))
+            .collect(),
+    }
+}
+
+pub fn describe_function<'tcx>(
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    asn: &Assignment,
+    ldid: LocalDefId,
+    mir: &Body<'tcx>,
+    local_equiv_map: BTreeMap<PointerId, PointerId>,
+    polonius_iterations: usize,
+    rewrites: &[ExprRewrite],
+) -> FunctionOutput {
+    let tcx: TyCtxt = acx.tcx();
+    let source_map = tcx.sess.source_map();
+    let def_id = ldid.to_def_id();
+    FunctionOutput {
+        name: tcx.item_name(def_id).to_string(),
+        def_path: tcx.def_path_str(def_id),
+        def_path_hash: tcx.def_path_hash(def_id).0.to_hex(),
+        span: source_map.span_to_embeddable_string(mir.span),
+        local_equiv_map,
+        polonius_iterations,
+        locals: mir
+            .local_decls
+            .iter_enumerated()
+            .map(|(local, decl)| describe_local(acx, asn, local, decl))
+            .collect(),
+        rewrites: rewrites
+            .iter()
+            .map(|rw| RewriteOutput {
+                stmt: format!("{:?}", rw.loc.stmt),
+                span: source_map.span_to_embeddable_string(rw.loc.span),
+                sub: rw.loc.sub.clone(),
+                kinds: rw.kinds.clone(),
+            })
+            .collect(),
+    }
+}
+
+pub fn write_output(path: &Path, output: &AnalysisOutput) {
+    let json = serde_json::to_string_pretty(output).unwrap();
+    fs::write(path, json).unwrap_or_else(|e| panic!("failed to write {:?}: {}", path, e));
+}
@@ -26,18 +26,25 @@ use rustc_index::vec::IndexVec;
 use rustc_middle::mir::visit::Visitor;
 use rustc_middle::mir::{BindingForm, Body, LocalDecl, LocalInfo, LocalKind, Location, Operand};
 use rustc_middle::ty::{Ty, TyCtxt, TyKind, WithOptConstParam};
+use rustc_session::config::ErrorOutputType;
 use rustc_span::Span;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
+use std::mem;
 use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 
 mod borrowck;
 mod context;
 mod dataflow;
 mod equiv;
 mod expr_rewrite;
+mod fn_summary;
+mod json;
 mod labeled_ty;
+mod pdg_facts;
 mod pointer_id;
+mod rewrite;
 mod type_desc;
 mod util;
 
@@ -95,8 +102,19 @@ impl<T> DerefMut for MaybeUnset<T> {
     }
 }
 
-fn run(tcx: TyCtxt) {
+fn run(
+    tcx: TyCtxt,
+    output_json: Option<&Path>,
+    fn_summaries: Option<&Path>,
+    pdg: Option<&Path>,
+    pdg_refine: bool,
+    rewrite_mode: Option<rewrite::RewriteMode>,
+) {
     let mut gacx = GlobalAnalysisCtxt::new(tcx);
+    if let Some(path) = fn_summaries {
+        gacx.fn_summaries.load(path);
+    }
+    let pdg_facts = pdg.map(pdg_facts::load);
     let mut func_info = HashMap::new();
 
     /// Local information, specific to a single function.  Many of the data structures we use for
@@ -119,6 +137,11 @@ fn run(tcx: TyCtxt) {
         /// get a complete [`Assignment`] for this function, which maps every [`PointerId`] in this
         /// function to a [`PermissionSet`] and [`FlagSet`].
         lasn: MaybeUnset<LocalAssignment>,
+        /// Map from the local `PointerId`s assigned in the initial pass to the renumbered ones.
+        /// Only used for `--output-json`.
+        local_equiv_map: BTreeMap<PointerId, PointerId>,
+        /// Total number of polonius iterations run on this function.
+        polonius_iterations: usize,
     }
 
     // Assign global `PointerId`s for all pointers that appear in function signatures.
@@ -195,6 +218,10 @@ fn run(tcx: TyCtxt) {
         let info = func_info.get_mut(&ldid).unwrap();
         let (local_counter, local_equiv_map) = info.local_equiv.renumber(&global_equiv_map);
         eprintln!("local_equiv_map = {local_equiv_map:?}");
+        info.local_equiv_map = local_equiv_map
+            .iter()
+            .map(|(old, &new)| (old, new))
+            .collect();
         info.acx_data.remap_pointers(
             gacx.lcx,
             global_equiv_map.and(&local_equiv_map),
@@ -244,7 +271,7 @@ fn run(tcx: TyCtxt) {
             // fixpoint, so there's no need to do multiple iterations here.
             info.dataflow.propagate(&mut asn.perms_mut());
 
-            borrowck::borrowck_mir(
+            info.polonius_iterations += borrowck::borrowck_mir(
                 &acx,
                 &info.dataflow,
                 &mut asn.perms_mut(),
@@ -261,7 +288,34 @@ fn run(tcx: TyCtxt) {
     }
     eprintln!("reached fixpoint in {} iterations", loop_count);
 
-    // Print results for each function.
+    // Compare against the facts observed at run time, if any.  This happens before printing and
+    // rewriting so that `--pdg-refine` applies to all functions consistently.
+    if let Some(ref pdg_facts) = pdg_facts {
+        let mut disagreements = 0;
+        for ldid in tcx.hir().body_owners() {
+            let info = func_info.get_mut(&ldid).unwrap();
+            let ldid_const = WithOptConstParam::unknown(ldid);
+            let mir = tcx.mir_built(ldid_const);
+            let mir = mir.borrow();
+            let acx = gacx.function_context_with_data(&mir, info.acx_data.take());
+            let mut asn = gasn.and(&mut info.lasn);
+            disagreements += pdg_facts::check_function(&acx, &mut asn, &mir, pdg_facts, pdg_refine);
+            info.acx_data.set(acx.into_data());
+        }
+        eprintln!("pdg facts disagree for {} locals", disagreements);
+    }
+
+    let mut json_output = json::AnalysisOutput {
+        fixpoint_iterations: loop_count,
+        global_equiv_map: global_equiv_map
+            .iter()
+            .map(|(old, &new)| (old, new))
+            .collect(),
+        functions: Vec::new(),
+    };
+
+    // Print results for each function, and collect source rewrites if requested.
+    let mut rewriter = rewrite::Rewriter::new(tcx);
     for ldid in tcx.hir().body_owners() {
         let info = func_info.get_mut(&ldid).unwrap();
         let ldid_const = WithOptConstParam::unknown(ldid);
@@ -341,6 +395,31 @@ fn run(tcx: TyCtxt) {
                 eprintln!("  {:?}", kind);
             }
         }
+
+        if output_json.is_some() {
+            json_output.functions.push(json::describe_function(
+                &acx,
+                &asn,
+                ldid,
+                &mir,
+                mem::take(&mut info.local_equiv_map),
+                info.polonius_iterations,
+                &rewrites,
+            ));
+        }
+
+        if rewrite_mode.is_some() {
+            rewriter.rewrite_types(&acx, &asn, ldid, &mir);
+            rewriter.rewrite_exprs(ldid, &rewrites);
+        }
+    }
+
+    if let Some(mode) = rewrite_mode {
+        rewriter.apply(mode);
+    }
+
+    if let Some(path) = output_json {
+        json::write_output(path, &json_output);
     }
 }
 
@@ -464,6 +543,10 @@ fn for_each_callee(tcx: TyCtxt, ldid: LocalDefId, f: impl FnMut(LocalDefId)) {
         fn visit_operand(&mut self, operand: &Operand<'tcx>, _location: Location) {
             let ty = operand.ty(self.mir, self.tcx);
             if let Some(Callee::Other { def_id, .. }) = util::ty_callee(self.tcx, ty) {
+                // Foreign items have local `DefId`s, but no bodies.
+                if self.tcx.is_foreign_item(def_id) {
+                    return;
+                }
                 if let Some(ldid) = def_id.as_local() {
                     (self.f)(ldid);
                 }
@@ -474,7 +557,18 @@ fn for_each_callee(tcx: TyCtxt, ldid: LocalDefId, f: impl FnMut(LocalDefId)) {
     CalleeVisitor { tcx, mir, f }.visit_body(mir);
 }
 
-struct AnalysisCallbacks;
+struct AnalysisCallbacks {
+    /// Where to write the JSON summary of the results, if requested.
+    output_json: Option<PathBuf>,
+    /// A JSON file of additional function summaries, which override the built-in ones.
+    fn_summaries: Option<PathBuf>,
+    /// A PDG saved by `c2rust-pdg --output`, whose pointer facts are checked against the results.
+    pdg: Option<PathBuf>,
+    /// Whether to remove permissions that the `pdg` shows were never used.
+    pdg_refine: bool,
+    /// How to apply the rewrites to the source, or `None` to only print them.
+    rewrite_mode: Option<rewrite::RewriteMode>,
+}
 
 impl rustc_driver::Callbacks for AnalysisCallbacks {
     fn after_expansion<'tcx>(
@@ -483,13 +577,67 @@ impl rustc_driver::Callbacks for AnalysisCallbacks {
         queries: &'tcx rustc_interface::Queries<'tcx>,
     ) -> rustc_driver::Compilation {
         queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
-            run(tcx);
+            run(
+                tcx,
+                self.output_json.as_deref(),
+                self.fn_summaries.as_deref(),
+                self.pdg.as_deref(),
+                self.pdg_refine,
+                self.rewrite_mode,
+            );
         });
         rustc_driver::Compilation::Continue
     }
 }
 
+/// Remove the option `name` (as either `name value` or `name=value`) from `args`, which are
+/// otherwise passed through to rustc, and return its value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let i = args
+        .iter()
+        .position(|arg| arg == name || arg.starts_with(&prefix))?;
+    let arg = args.remove(i);
+    if arg == name {
+        assert!(i < args.len(), "missing value for {}", name);
+        Some(args.remove(i))
+    } else {
+        Some(arg[prefix.len()..].to_owned())
+    }
+}
+
+/// Remove the flag `name` from `args`, and return whether it was present.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
 fn main() -> rustc_interface::interface::Result<()> {
-    let args = env::args().collect::<Vec<_>>();
-    rustc_driver::RunCompiler::new(&args, &mut AnalysisCallbacks).run()
+    let mut args = env::args().collect::<Vec<_>>();
+    let output_json = take_option(&mut args, "--output-json").map(PathBuf::from);
+    let fn_summaries = take_option(&mut args, "--fn-summaries").map(PathBuf::from);
+    let pdg = take_option(&mut args, "--pdg").map(PathBuf::from);
+    let pdg_refine = take_flag(&mut args, "--pdg-refine");
+    assert!(pdg.is_some() || !pdg_refine, "--pdg-refine requires --pdg");
+    let rewrite_mode = take_option(&mut args, "--rewrite-mode").map(|mode| {
+        mode.parse().unwrap_or_else(|e: String| {
+            rustc_session::early_error(
+                ErrorOutputType::default(),
+                &format!("--rewrite-mode: {}", e),
+            )
+        })
+    });
+    let mut callbacks = AnalysisCallbacks {
+        output_json,
+        fn_summaries,
+        pdg,
+        pdg_refine,
+        rewrite_mode,
+    };
+    rustc_driver::RunCompiler::new(&args, &mut callbacks).run()
 }
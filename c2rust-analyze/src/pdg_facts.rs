@@ -0,0 +1,117 @@
+//! Comparison of the static permission assignment against facts observed at run time.
+//!
+//! A PDG saved with `c2rust-pdg --output` records, for each MIR local that held a pointer during
+//! a run of the instrumented program, whether that pointer was read, written, offset, or aliased
+//! (see [`c2rust_analysis_rt::pdg_facts`]).  Passing the PDG to `--pdg` reports every local where
+//! the two analyses disagree.  With `--pdg-refine`, permissions that the static analysis
+//! inferred but that were never used at run time are also removed from the assignment.  This is
+//! only as good as the test run's coverage, since a path that never ran contributes no facts.
+//! Likewise, a pointer that escaped into code that wasn't instrumented may have been used there,
+//! so it is never reported or refined as unused.
+use crate::context::{AnalysisCtxt, Assignment, PermissionSet, PointerId};
+use c2rust_analysis_rt::mir_loc::FuncId;
+use c2rust_analysis_rt::pdg_facts::{PdgFacts, PointerFacts};
+use c2rust_analysis_rt::pdg_file::PdgFile;
+use rustc_middle::mir::Body;
+use std::path::Path;
+
+/// Permissions that the PDG can observe directly.  `UNIQUE` is checked separately, since the
+/// static analysis starts from `UNIQUE` and removes it, rather than adding it.
+const OBSERVABLE: PermissionSet = PermissionSet::READ
+    .union(PermissionSet::WRITE)
+    .union(PermissionSet::OFFSET_ADD)
+    .union(PermissionSet::OFFSET_SUB);
+
+/// Permissions that `--pdg-refine` removes if they were never used.  Removing these can only turn
+/// a pointer into a more restrictive reference; `READ` is kept so the result stays usable.
+const REFINABLE: PermissionSet = PermissionSet::WRITE
+    .union(PermissionSet::OFFSET_ADD)
+    .union(PermissionSet::OFFSET_SUB);
+
+/// Load the facts from the PDG saved at `path`.
+pub fn load(path: &Path) -> PdgFacts {
+    PdgFile::read_path(path)
+        .unwrap_or_else(|e| panic!("failed to load PDG from {:?}: {}", path, e))
+        .facts
+}
+
+fn observed_perms(facts: &PointerFacts) -> PermissionSet {
+    let mut perms = PermissionSet::empty();
+    perms.set(PermissionSet::READ, facts.load);
+    perms.set(PermissionSet::WRITE, facts.store);
+    perms.set(PermissionSet::OFFSET_ADD, facts.pos_offset);
+    perms.set(PermissionSet::OFFSET_SUB, facts.neg_offset);
+    perms.set(PermissionSet::UNIQUE, facts.unique);
+    perms
+}
+
+/// Check the permissions of the outermost pointer of each local in `mir` against `facts`, and
+/// print any disagreements.  If `refine` is set, also remove any [`REFINABLE`] permissions that
+/// were never used.  Returns the number of locals where the two disagree.
+pub fn check_function<'tcx>(
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    asn: &mut Assignment,
+    mir: &Body<'tcx>,
+    facts: &PdgFacts,
+    refine: bool,
+) -> usize {
+    let tcx = acx.tcx();
+    let def_id = mir.source.def_id();
+    let func = FuncId(tcx.def_path_hash(def_id).0.as_value().into());
+
+    let mut count = 0;
+    for (local, decl) in mir.local_decls.iter_enumerated() {
+        let ptr = acx.local_tys[local].label;
+        if ptr == PointerId::NONE {
+            continue;
+        }
+        let local_facts = match facts.get(func, local.as_u32().into()) {
+            Some(x) => x,
+            None => continue,
+        };
+
+        let static_perms = asn.perms()[ptr];
+        let observed = observed_perms(local_facts);
+        // Used at run time, but not inferred statically.  This indicates a bug in the static
+        // analysis (or a mismatch between the analyzed and instrumented code).
+        let missing = (observed - static_perms) & OBSERVABLE;
+        // Inferred statically, but never used at run time.
+        let unused = if local_facts.escaped {
+            PermissionSet::empty()
+        } else {
+            (static_perms - observed) & REFINABLE
+        };
+        let aliased = static_perms.contains(PermissionSet::UNIQUE)
+            && !observed.contains(PermissionSet::UNIQUE);
+        if missing.is_empty() && unused.is_empty() && !aliased {
+            continue;
+        }
+
+        if count == 0 {
+            eprintln!("pdg facts for {:?}:", tcx.item_name(def_id));
+        }
+        count += 1;
+        eprintln!(
+            "{:?} ({}): static = {:?}, observed = {:?}",
+            local,
+            crate::describe_local(tcx, decl),
+            static_perms,
+            observed,
+        );
+        if !missing.is_empty() {
+            eprintln!("  used but not inferred: {:?}", missing);
+        }
+        if aliased {
+            eprintln!("  inferred UNIQUE, but aliased at run time");
+        }
+        if !unused.is_empty() {
+            if refine {
+                asn.perms_mut()[ptr].remove(unused);
+                eprintln!("  never used, removed: {:?}", unused);
+            } else {
+                eprintln!("  never used: {:?}", unused);
+            }
+        }
+    }
+    count
+}
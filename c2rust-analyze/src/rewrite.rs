@@ -0,0 +1,573 @@
+//! Rewriting of the analyzed source code.  This takes the type assignment computed by the
+//! analysis (via [`type_desc::perms_to_desc`]) and the [`ExprRewrite`]s produced by
+//! [`expr_rewrite::gen_expr_rewrites`], maps each of them back to a span in the original source,
+//! and emits the edited files.
+//!
+//! Rewriting is enabled by passing `--rewrite-mode` with one of `inplace`, `alongside` (write
+//! `foo.new` next to `foo.rs`), or `diff` (print a unified diff to stdout).  Without it, the
+//! analysis only prints its results.
+//!
+//! [`expr_rewrite::gen_expr_rewrites`]: crate::expr_rewrite::gen_expr_rewrites
+use crate::context::{AnalysisCtxt, Assignment, FlagSet, LTy, PermissionSet, PointerId};
+use crate::expr_rewrite::{ExprRewrite, RewriteKind, SubLoc};
+use crate::pointer_id::PointerTable;
+use crate::type_desc::{self, Ownership, Quantity};
+use rustc_hir as hir;
+use rustc_hir::def_id::LocalDefId;
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_middle::mir::{BindingForm, Body, LocalInfo, LocalKind, Mutability};
+use rustc_middle::ty::BindingMode;
+use rustc_middle::ty::{TyCtxt, TyKind};
+use rustc_span::{FileName, Span};
+use similar::TextDiff;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RewriteMode {
+    /// Overwrite the original source files.
+    InPlace,
+    /// Write the rewritten source to a new file alongside the original, like `foo.new` for
+    /// `foo.rs`.
+    Alongside,
+    /// Print a unified diff of the changes to stdout.
+    Diff,
+}
+
+impl FromStr for RewriteMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RewriteMode, String> {
+        match s {
+            "inplace" => Ok(RewriteMode::InPlace),
+            "alongside" => Ok(RewriteMode::Alongside),
+            "diff" => Ok(RewriteMode::Diff),
+            _ => Err(format!(
+                "unknown rewrite mode {:?} (expected `inplace`, `alongside`, or `diff`)",
+                s
+            )),
+        }
+    }
+}
+
+/// Collects source edits for the whole crate.  Edits never overlap: adding an edit that encloses
+/// existing ones replaces them, and the enclosing edit's text is built from [`Self::snippet`],
+/// which already includes the inner edits.  This means edits must be added innermost first.
+pub struct Rewriter<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    edits: Vec<(Span, String)>,
+}
+
+impl<'tcx> Rewriter<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>) -> Rewriter<'tcx> {
+        Rewriter {
+            tcx,
+            edits: Vec::new(),
+        }
+    }
+
+    /// Get the source text of `span`, with any edits made inside `span` applied.
+    fn snippet(&self, span: Span) -> String {
+        let source_map = self.tcx.sess.source_map();
+        let mut inner = self
+            .edits
+            .iter()
+            .filter(|&&(sp, _)| span.contains(sp))
+            .collect::<Vec<_>>();
+        inner.sort_by_key(|&&(sp, _)| sp.lo());
+
+        let mut s = String::new();
+        let mut pos = span.lo();
+        for &(sp, ref text) in inner {
+            s.push_str(
+                &source_map
+                    .span_to_snippet(span.with_lo(pos).with_hi(sp.lo()))
+                    .unwrap(),
+            );
+            s.push_str(text);
+            pos = sp.hi();
+        }
+        s.push_str(&source_map.span_to_snippet(span.with_lo(pos)).unwrap());
+        s
+    }
+
+    fn add_edit(&mut self, span: Span, text: String) {
+        if span.from_expansion() {
+            eprintln!(
+                "rewrite: skipping edit inside macro expansion at {:?}",
+                span
+            );
+            return;
+        }
+        if let Some(&(sp, _)) = self
+            .edits
+            .iter()
+            .find(|&&(sp, _)| sp.overlaps(span) && !span.contains(sp))
+        {
+            eprintln!(
+                "rewrite: skipping edit at {:?}, which conflicts with the edit at {:?}",
+                span, sp
+            );
+            return;
+        }
+        self.edits.retain(|&(sp, _)| !span.contains(sp));
+        self.edits.push((span, text));
+    }
+
+    /// Rewrite the pointer types in the signature of `ldid` and in the type annotations of its
+    /// `let` bindings to match the final assignment.
+    pub fn rewrite_types(
+        &mut self,
+        acx: &AnalysisCtxt<'_, 'tcx>,
+        asn: &Assignment,
+        ldid: LocalDefId,
+        mir: &Body<'tcx>,
+    ) {
+        let hir_map = self.tcx.hir();
+        let perms = asn.perms();
+        let flags = asn.flags();
+
+        let hir_id = hir_map.local_def_id_to_hir_id(ldid);
+        if let Some(decl) = hir_map.fn_decl_by_hir_id(hir_id) {
+            for (i, hir_ty) in decl.inputs.iter().enumerate() {
+                let lty = acx.local_tys[rustc_middle::mir::Local::from_usize(i + 1)];
+                self.rewrite_ty(hir_ty, lty, &perms, &flags);
+            }
+            if let hir::FnRetTy::Return(hir_ty) = decl.output {
+                let lty = acx.local_tys[rustc_middle::mir::RETURN_PLACE];
+                self.rewrite_ty(hir_ty, lty, &perms, &flags);
+            }
+        }
+
+        let body = hir_map.body(hir_map.body_owned_by(ldid));
+        let mut collector = HirCollector::default();
+        collector.visit_body(body);
+
+        for (local, decl) in mir.local_decls.iter_enumerated() {
+            if mir.local_kind(local) != LocalKind::Var {
+                continue;
+            }
+            let var = match decl.local_info.as_deref() {
+                Some(LocalInfo::User(binding_form)) => match binding_form.as_ref() {
+                    rustc_middle::mir::ClearCrossCrate::Set(BindingForm::Var(v)) => v,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let pat_span = var.pat_span;
+
+            // Writing through a `Box` requires the variable holding it to be `mut`, unlike
+            // writing through a raw pointer.
+            let ptr = acx.local_tys[local].label;
+            if ptr != PointerId::NONE
+                && var.binding_mode == BindingMode::BindByValue(Mutability::Not)
+                && perms[ptr].contains(PermissionSet::WRITE)
+                && type_desc::perms_to_desc(perms[ptr], flags[ptr]).0 == Ownership::Box
+            {
+                self.add_edit(pat_span, format!("mut {}", self.snippet(pat_span)));
+            }
+
+            if let Some(hir_ty) = collector.local_tys.get(&pat_span) {
+                self.rewrite_ty(hir_ty, acx.local_tys[local], &perms, &flags);
+            }
+        }
+    }
+
+    fn rewrite_ty(
+        &mut self,
+        hir_ty: &hir::Ty,
+        lty: LTy<'tcx>,
+        perms: &PointerTable<PermissionSet>,
+        flags: &PointerTable<FlagSet>,
+    ) {
+        if let Some(text) = self.render_ty(hir_ty, lty, perms, flags) {
+            self.add_edit(hir_ty.span, text);
+        }
+    }
+
+    /// Render the rewritten form of `hir_ty`, or return `None` if it doesn't need to change.
+    fn render_ty(
+        &self,
+        hir_ty: &hir::Ty,
+        lty: LTy<'tcx>,
+        perms: &PointerTable<PermissionSet>,
+        flags: &PointerTable<FlagSet>,
+    ) -> Option<String> {
+        let mt = match hir_ty.kind {
+            hir::TyKind::Ptr(ref mt) => mt,
+            // TODO: descend into other type constructors, such as arrays and tuples
+            _ => return None,
+        };
+        if lty.label == PointerId::NONE || !matches!(lty.kind(), TyKind::RawPtr(..)) {
+            return None;
+        }
+
+        let pointee = self
+            .render_ty(mt.ty, lty.args[0], perms, flags)
+            .unwrap_or_else(|| self.snippet(mt.ty.span));
+
+        let (own, qty) = type_desc::perms_to_desc(perms[lty.label], flags[lty.label]);
+        let is_cell = own == Ownership::Cell
+            || (own == Ownership::Rc && flags[lty.label].contains(FlagSet::CELL));
+        let ty = if is_cell {
+            format!("core::cell::Cell<{}>", pointee)
+        } else {
+            pointee
+        };
+        let ty = match qty {
+            Quantity::Single => ty,
+            Quantity::Slice | Quantity::OffsetPtr => format!("[{}]", ty),
+        };
+        let ty = match own {
+            Ownership::Raw => format!("*const {}", ty),
+            Ownership::RawMut => format!("*mut {}", ty),
+            Ownership::Imm | Ownership::Cell => format!("&{}", ty),
+            Ownership::Mut => format!("&mut {}", ty),
+            Ownership::Rc => format!("std::rc::Rc<{}>", ty),
+            Ownership::Box => format!("Box<{}>", ty),
+        };
+        if qty == Quantity::OffsetPtr {
+            return Some(format!("({}, usize)", ty));
+        }
+        Some(ty)
+    }
+
+    /// Apply the expression rewrites computed for the body of `ldid`.
+    pub fn rewrite_exprs(&mut self, ldid: LocalDefId, rewrites: &[ExprRewrite]) {
+        let hir_map = self.tcx.hir();
+        let body = hir_map.body(hir_map.body_owned_by(ldid));
+        let mut collector = HirCollector::default();
+        collector.visit_body(body);
+
+        // Visit inner expressions before the ones that enclose them, so the text for each outer
+        // rewrite includes the inner ones.
+        let mut rewrites = rewrites.iter().collect::<Vec<_>>();
+        rewrites.sort_by_key(|rw| rw.loc.span.hi() - rw.loc.span.lo());
+
+        for rw in rewrites {
+            let expr = match collector.exprs.get(&rw.loc.span) {
+                Some(&expr) => find_subexpr(expr, &rw.loc.sub),
+                None => None,
+            };
+            let expr = match expr {
+                Some(expr) => expr,
+                None => {
+                    eprintln!(
+                        "rewrite: no expression found for {:?} at {:?}",
+                        rw.kinds, rw.loc
+                    );
+                    continue;
+                }
+            };
+            if let Some(text) = self.render_expr(expr, &rw.kinds) {
+                self.add_edit(expr.span, text);
+            }
+        }
+    }
+
+    fn render_expr(&self, expr: &hir::Expr, kinds: &[RewriteKind]) -> Option<String> {
+        let mut text = self.operand_snippet(expr);
+        // Whether `text` is a place expression, which can be evaluated more than once
+        let mut is_place = self.is_unedited_place(expr);
+
+        for (i, kind) in kinds.iter().enumerate() {
+            // Rewrites produce prefix or tuple expressions, so the result of the previous one
+            // needs parentheses when used as an operand.
+            let operand = if i == 0 {
+                text.clone()
+            } else {
+                format!("({})", text)
+            };
+            text = match *kind {
+                RewriteKind::OffsetSlice { mutbl } => {
+                    let (receiver, arg) = offset_call_args(expr, kind)?;
+                    format!(
+                        "&{}{}[{} as usize..]",
+                        mut_prefix(mutbl),
+                        self.operand_snippet(receiver),
+                        self.offset_arg_snippet(arg)
+                    )
+                }
+                RewriteKind::SliceFirst { mutbl } => {
+                    format!("&{}{}[0]", mut_prefix(mutbl), operand)
+                }
+                RewriteKind::MutToImm => format!("&*{}", operand),
+                RewriteKind::Borrow { mutbl } => format!("&{}*{}", mut_prefix(mutbl), operand),
+                RewriteKind::OffsetOffsetPtr { mutbl } => {
+                    let (receiver, arg) = offset_call_args(expr, kind)?;
+                    let arg_text = self.offset_arg_snippet(arg);
+                    with_offset_ptr(
+                        &self.operand_snippet(receiver),
+                        self.is_unedited_place(receiver),
+                        |slice, idx| {
+                            let slice = if mutbl {
+                                format!("&mut *{}", slice)
+                            } else {
+                                slice.to_owned()
+                            };
+                            format!("({}, ({} as isize + {}) as usize)", slice, idx, arg_text)
+                        },
+                    )
+                }
+                RewriteKind::SliceToOffsetPtr => format!("({}, 0)", operand),
+                RewriteKind::OffsetPtrToSlice { mutbl } => {
+                    with_offset_ptr(&text, is_place, |slice, idx| {
+                        format!("&{}{}[{}..]", mut_prefix(mutbl), slice, idx)
+                    })
+                }
+                RewriteKind::OffsetPtrCurrent { mutbl } => {
+                    with_offset_ptr(&text, is_place, |slice, idx| {
+                        format!("&{}{}[{}]", mut_prefix(mutbl), slice, idx)
+                    })
+                }
+                RewriteKind::Alloc { own, cell } => {
+                    let pointee = match expr.kind {
+                        hir::ExprKind::Cast(_, cast_ty) => match cast_ty.kind {
+                            hir::TyKind::Ptr(ref mt) => self.snippet(mt.ty.span),
+                            _ => return None,
+                        },
+                        _ => {
+                            eprintln!("rewrite: {:?} on unsupported expr {:?}", kind, expr.span);
+                            return None;
+                        }
+                    };
+                    let mut value = format!("core::mem::zeroed::<{}>()", pointee);
+                    if cell {
+                        value = format!("core::cell::Cell::new({})", value);
+                    }
+                    match own {
+                        Ownership::Box => format!("Box::new({})", value),
+                        Ownership::Rc => format!("std::rc::Rc::new({})", value),
+                        _ => format!("Box::leak(Box::new({}))", value),
+                    }
+                }
+                RewriteKind::Free => {
+                    let arg = match expr.kind {
+                        hir::ExprKind::Call(_, [ref arg]) => arg,
+                        _ => {
+                            eprintln!("rewrite: {:?} on unsupported expr {:?}", kind, expr.span);
+                            return None;
+                        }
+                    };
+                    // Drop the cast to `*mut c_void` along with the call.
+                    let arg = match arg.kind {
+                        hir::ExprKind::Cast(inner, _) => inner,
+                        _ => arg,
+                    };
+                    format!("drop({})", self.snippet(arg.span))
+                }
+                RewriteKind::CloneRc => format!("std::rc::Rc::clone(&{})", operand),
+            };
+            is_place = false;
+        }
+
+        Some(text)
+    }
+
+    /// Get the source text of the `isize` argument of an `offset` call, for use as the operand
+    /// of `as`.
+    fn offset_arg_snippet(&self, arg: &hir::Expr) -> String {
+        if matches!(arg.kind, hir::ExprKind::Cast(..)) {
+            self.snippet(arg.span)
+        } else {
+            self.operand_snippet(arg)
+        }
+    }
+
+    fn is_unedited_place(&self, expr: &hir::Expr) -> bool {
+        matches!(
+            expr.kind,
+            hir::ExprKind::Path(..) | hir::ExprKind::Field(..)
+        ) && !self.edits.iter().any(|&(sp, _)| sp == expr.span)
+    }
+
+    /// Get the source text of `expr` (see [`Self::snippet`]), adding parentheses if it can't be
+    /// used as the operand of a prefix or postfix operator as is.
+    fn operand_snippet(&self, expr: &hir::Expr) -> String {
+        let text = self.snippet(expr.span);
+        let edited = self.edits.iter().any(|&(sp, _)| sp == expr.span);
+        if is_atom(expr) && !edited {
+            text
+        } else {
+            format!("({})", text)
+        }
+    }
+
+    /// Write out the edited files according to `mode`.
+    pub fn apply(self, mode: RewriteMode) {
+        let source_map = self.tcx.sess.source_map();
+
+        let mut edits_by_file = HashMap::new();
+        for (span, text) in self.edits {
+            let lo = source_map.lookup_byte_offset(span.lo());
+            let hi = source_map.lookup_byte_offset(span.hi());
+            edits_by_file
+                .entry(lo.sf.start_pos)
+                .or_insert_with(|| (lo.sf.clone(), Vec::new()))
+                .1
+                .push((lo.pos.0 as usize, hi.pos.0 as usize, text));
+        }
+
+        let mut files = edits_by_file.into_values().collect::<Vec<_>>();
+        files.sort_by_key(|(sf, _)| sf.start_pos);
+        for (sf, mut edits) in files {
+            let path = match sf.name {
+                FileName::Real(ref name) => match name.local_path() {
+                    Some(path) => path.to_owned(),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let src = sf.src.as_ref().expect("missing source for local file");
+
+            edits.sort_by_key(|&(lo, _, _)| lo);
+            let mut new_src = String::with_capacity(src.len());
+            let mut pos = 0;
+            for (lo, hi, text) in edits {
+                new_src.push_str(&src[pos..lo]);
+                new_src.push_str(&text);
+                pos = hi;
+            }
+            new_src.push_str(&src[pos..]);
+
+            match mode {
+                RewriteMode::InPlace => write_file(path, &new_src),
+                RewriteMode::Alongside => write_file(path.with_extension("new"), &new_src),
+                RewriteMode::Diff => {
+                    let name = path.display().to_string();
+                    print!(
+                        "{}",
+                        TextDiff::from_lines(src.as_str(), &new_src)
+                            .unified_diff()
+                            .header(&name, &name)
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn write_file(path: PathBuf, src: &str) {
+    fs::write(&path, src).unwrap_or_else(|e| panic!("failed to write {:?}: {}", path, e));
+}
+
+/// Get the receiver and argument of a `ptr.offset(i)` call.
+fn offset_call_args<'a, 'hir>(
+    expr: &'a hir::Expr<'hir>,
+    kind: &RewriteKind,
+) -> Option<(&'a hir::Expr<'hir>, &'a hir::Expr<'hir>)> {
+    match expr.kind {
+        hir::ExprKind::MethodCall(_, [ref receiver, ref arg], _) => Some((receiver, arg)),
+        _ => {
+            eprintln!("rewrite: {:?} on unsupported expr {:?}", kind, expr.span);
+            None
+        }
+    }
+}
+
+/// Find the HIR expression for the MIR subexpression `sub` of `expr`, the expression whose span
+/// is the statement's span.  Steps that don't have a HIR counterpart of their own, like the
+/// operand of a plain `Rvalue::Use`, stay on the same expression.
+fn find_subexpr<'a, 'hir>(
+    mut expr: &'a hir::Expr<'hir>,
+    sub: &[SubLoc],
+) -> Option<&'a hir::Expr<'hir>> {
+    for sl in sub {
+        expr = match (sl, &expr.kind) {
+            (&SubLoc::Dest, &hir::ExprKind::Assign(lhs, _, _)) => lhs,
+            (&SubLoc::AssignRvalue, &hir::ExprKind::Assign(_, rhs, _)) => rhs,
+            (&SubLoc::CallArg(i), &hir::ExprKind::Call(_, args)) => args.get(i)?,
+            // The receiver is `args[0]`, matching the MIR call's argument order.
+            (&SubLoc::CallArg(i), &hir::ExprKind::MethodCall(_, args, _)) => args.get(i)?,
+            (&SubLoc::RvalueOperand(i), &hir::ExprKind::Binary(_, lhs, rhs)) => match i {
+                0 => lhs,
+                1 => rhs,
+                _ => return None,
+            },
+            (&SubLoc::RvalueOperand(0), &hir::ExprKind::Unary(op, operand))
+                if op != hir::UnOp::Deref =>
+            {
+                operand
+            }
+            (&SubLoc::RvalueOperand(0), &hir::ExprKind::Cast(operand, _)) => operand,
+            (&SubLoc::PlacePointer(i), _) => {
+                // Collect the pointer operand of each deref, outermost first.
+                let mut ptrs = Vec::new();
+                let mut e = expr;
+                loop {
+                    match e.kind {
+                        hir::ExprKind::Unary(hir::UnOp::Deref, ptr) => {
+                            ptrs.push(ptr);
+                            e = ptr;
+                        }
+                        hir::ExprKind::Field(base, _) | hir::ExprKind::Index(base, _) => e = base,
+                        _ => break,
+                    }
+                }
+                *ptrs.iter().rev().nth(i)?
+            }
+            (&SubLoc::AssignRvalue | &SubLoc::RvalueOperand(_) | &SubLoc::OperandPlace, _) => expr,
+            _ => return None,
+        };
+    }
+    Some(expr)
+}
+
+/// Build an expression from the slice and index of the `OffsetPtr` expression `ptr`.  Place
+/// expressions are used directly, as `ptr.0` and `ptr.1`; anything else is bound with a `match`
+/// so it's only evaluated once.
+fn with_offset_ptr(ptr: &str, is_place: bool, f: impl FnOnce(&str, &str) -> String) -> String {
+    if is_place {
+        f(&format!("{}.0", ptr), &format!("{}.1", ptr))
+    } else {
+        format!("match {} {{ (s, i) => {} }}", ptr, f("s", "i"))
+    }
+}
+
+fn mut_prefix(mutbl: bool) -> &'static str {
+    if mutbl {
+        "mut "
+    } else {
+        ""
+    }
+}
+
+/// Returns `true` if the source text of `expr` can be used as the operand of an operator without
+/// adding parentheses.
+fn is_atom(expr: &hir::Expr) -> bool {
+    matches!(
+        expr.kind,
+        hir::ExprKind::Path(..)
+            | hir::ExprKind::Lit(..)
+            | hir::ExprKind::Call(..)
+            | hir::ExprKind::MethodCall(..)
+            | hir::ExprKind::Field(..)
+            | hir::ExprKind::Index(..)
+    )
+}
+
+/// Indexes the expressions and `let` type annotations of a HIR body by span.
+#[derive(Default)]
+struct HirCollector<'hir> {
+    exprs: HashMap<Span, &'hir hir::Expr<'hir>>,
+    /// Type annotations of `let` statements, keyed on the span of the pattern.
+    local_tys: HashMap<Span, &'hir hir::Ty<'hir>>,
+}
+
+impl<'hir> Visitor<'hir> for HirCollector<'hir> {
+    fn visit_expr(&mut self, ex: &'hir hir::Expr<'hir>) {
+        // When several expressions share a span (e.g. an expression and an adjustment of it),
+        // keep the outermost one.
+        self.exprs.entry(ex.span).or_insert(ex);
+        intravisit::walk_expr(self, ex);
+    }
+
+    fn visit_local(&mut self, local: &'hir hir::Local<'hir>) {
+        if let Some(ty) = local.ty {
+            self.local_tys.insert(local.pat.span, ty);
+        }
+        intravisit::walk_local(self, local);
+    }
+}
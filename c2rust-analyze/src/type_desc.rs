@@ -1,10 +1,12 @@
 use crate::context::{AnalysisCtxt, Assignment, FlagSet, LTy, PermissionSet, PointerId};
 use rustc_hir::def::{DefKind, Res};
+use rustc_hir::def_id::DefId;
 use rustc_middle::ty::subst::GenericArg;
 use rustc_middle::ty::{ReErased, Ty, TyCtxt};
+use serde::Serialize;
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Serialize)]
 pub enum Ownership {
     /// E.g. `*const T`
     Raw,
@@ -23,18 +25,34 @@ pub enum Ownership {
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Serialize)]
 pub enum Quantity {
     /// E.g. `&T`
     Single,
     /// E.g. `&[T]`
     Slice,
-    /// E.g. `OffsetPtr<T>`
+    /// A pointer that may move backwards, represented as a slice of the whole underlying object
+    /// paired with the index of the current element, e.g. `(&[T], usize)`.
     OffsetPtr,
 }
 
 pub fn perms_to_desc(perms: PermissionSet, flags: FlagSet) -> (Ownership, Quantity) {
-    let own = if perms.contains(PermissionSet::UNIQUE | PermissionSet::WRITE) {
+    let offset = PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB;
+    let own = if perms.contains(PermissionSet::FREE) && perms.intersects(offset) {
+        // Heap arrays stay raw pointers, since there's no rewrite yet for the allocations that
+        // would produce a `Box<[T]>` or `Rc<[T]>`.
+        if perms.contains(PermissionSet::WRITE) {
+            Ownership::RawMut
+        } else {
+            Ownership::Raw
+        }
+    } else if perms.contains(PermissionSet::FREE) {
+        if perms.contains(PermissionSet::UNIQUE) {
+            Ownership::Box
+        } else {
+            Ownership::Rc
+        }
+    } else if perms.contains(PermissionSet::UNIQUE | PermissionSet::WRITE) {
         Ownership::Mut
     } else if flags.contains(FlagSet::CELL) {
         Ownership::Cell
@@ -44,11 +62,8 @@ pub fn perms_to_desc(perms: PermissionSet, flags: FlagSet) -> (Ownership, Quanti
         Ownership::Imm
     };
 
-    // TODO(spernsteiner): will not remain identical branches
-    #[allow(clippy::if_same_then_else)]
     let qty = if perms.contains(PermissionSet::OFFSET_SUB) {
-        // TODO(spernsteiner): should be [`Quantity::OffsetPtr`], but that's not implemented yet
-        Quantity::Slice
+        Quantity::OffsetPtr
     } else if perms.contains(PermissionSet::OFFSET_ADD) {
         Quantity::Slice
     } else {
@@ -58,37 +73,51 @@ pub fn perms_to_desc(perms: PermissionSet, flags: FlagSet) -> (Ownership, Quanti
     (own, qty)
 }
 
-fn mk_cell<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
-    let core_crate = tcx
+/// Find the struct `krate::module::name`.
+fn find_struct(tcx: TyCtxt, krate: &str, module: &str, name: &str) -> DefId {
+    let crate_num = tcx
         .crates(())
         .iter()
         .cloned()
-        .find(|&krate| tcx.crate_name(krate).as_str() == "core")
-        .expect("failed to find crate `core`");
+        .find(|&crate_num| tcx.crate_name(crate_num).as_str() == krate)
+        .unwrap_or_else(|| panic!("failed to find crate `{}`", krate));
 
-    let cell_mod_child = tcx
-        .module_children(core_crate.as_def_id())
+    let mod_child = tcx
+        .module_children(crate_num.as_def_id())
         .iter()
-        .find(|child| child.ident.as_str() == "cell")
-        .expect("failed to find module `core::cell`");
-    let cell_mod = match cell_mod_child.res {
+        .find(|child| child.ident.as_str() == module)
+        .unwrap_or_else(|| panic!("failed to find module `{}::{}`", krate, module));
+    let mod_did = match mod_child.res {
         Res::Def(DefKind::Mod, did) => did,
-        ref r => panic!("unexpected resolution {:?} for `core::cell`", r),
+        ref r => panic!("unexpected resolution {:?} for `{}::{}`", r, krate, module),
     };
 
-    let cell_struct_child = tcx
-        .module_children(cell_mod)
+    let struct_child = tcx
+        .module_children(mod_did)
         .iter()
-        .find(|child| child.ident.as_str() == "Cell")
-        .expect("failed to find struct `core::cell::Cell`");
-    let cell_struct = match cell_struct_child.res {
+        .find(|child| child.ident.as_str() == name)
+        .unwrap_or_else(|| panic!("failed to find struct `{}::{}::{}`", krate, module, name));
+    match struct_child.res {
         Res::Def(DefKind::Struct, did) => did,
-        ref r => panic!("unexpected resolution {:?} for `core::cell::Cell`", r),
-    };
+        ref r => panic!(
+            "unexpected resolution {:?} for `{}::{}::{}`",
+            r, krate, module, name
+        ),
+    }
+}
 
-    let cell_adt = tcx.adt_def(cell_struct);
+fn mk_adt<'tcx>(tcx: TyCtxt<'tcx>, did: DefId, ty: Ty<'tcx>) -> Ty<'tcx> {
+    let adt = tcx.adt_def(did);
     let substs = tcx.mk_substs([GenericArg::from(ty)].into_iter());
-    tcx.mk_adt(cell_adt, substs)
+    tcx.mk_adt(adt, substs)
+}
+
+fn mk_cell<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
+    mk_adt(tcx, find_struct(tcx, "core", "cell", "Cell"), ty)
+}
+
+fn mk_rc<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Ty<'tcx> {
+    mk_adt(tcx, find_struct(tcx, "alloc", "rc", "Rc"), ty)
 }
 
 pub fn convert_type<'tcx>(
@@ -110,14 +139,15 @@ pub fn convert_type<'tcx>(
         assert_eq!(args.len(), 1);
         let mut ty = args[0];
 
-        if own == Ownership::Cell {
+        // An `Rc` that's written through needs `Cell` as well, since `Rc` only provides shared
+        // access.
+        if own == Ownership::Cell || (own == Ownership::Rc && flags[ptr].contains(FlagSet::CELL)) {
             ty = mk_cell(tcx, ty);
         }
 
         ty = match qty {
             Quantity::Single => ty,
-            Quantity::Slice => tcx.mk_slice(ty),
-            Quantity::OffsetPtr => todo!(),
+            Quantity::Slice | Quantity::OffsetPtr => tcx.mk_slice(ty),
         };
 
         ty = match own {
@@ -126,10 +156,14 @@ pub fn convert_type<'tcx>(
             Ownership::Imm => tcx.mk_imm_ref(tcx.mk_region(ReErased), ty),
             Ownership::Cell => tcx.mk_imm_ref(tcx.mk_region(ReErased), ty),
             Ownership::Mut => tcx.mk_mut_ref(tcx.mk_region(ReErased), ty),
-            Ownership::Rc => todo!(),
-            Ownership::Box => todo!(),
+            Ownership::Rc => mk_rc(tcx, ty),
+            Ownership::Box => tcx.mk_box(ty),
         };
 
+        if qty == Quantity::OffsetPtr {
+            ty = tcx.intern_tup(&[ty, tcx.types.usize]);
+        }
+
         ty
     })
 }
@@ -1,7 +1,12 @@
+use crate::context::PermissionSet;
 use rustc_hir::def::DefKind;
 use rustc_hir::def_id::DefId;
-use rustc_middle::mir::{Local, Mutability, Operand, PlaceElem, PlaceRef, Rvalue};
-use rustc_middle::ty::{DefIdTree, SubstsRef, Ty, TyCtxt, TyKind};
+use rustc_middle::mir::{
+    Body, Local, Mutability, Operand, PlaceElem, PlaceRef, Rvalue, StatementKind, TerminatorKind,
+    UnOp,
+};
+use rustc_middle::ty::{DefIdTree, ParamEnv, SubstsRef, Ty, TyCtxt, TyKind};
+use std::cmp::Ordering;
 
 #[derive(Debug)]
 pub enum RvalueDesc<'tcx> {
@@ -71,6 +76,12 @@ pub enum Callee<'tcx> {
         pointee_ty: Ty<'tcx>,
         mutbl: Mutability,
     },
+    /// libc `malloc`, which returns a pointer to a new heap object.
+    Malloc,
+    /// libc `calloc`, which returns a pointer to a new zero-initialized heap array.
+    Calloc,
+    /// libc `free`, which frees the heap object that its argument points to.
+    Free,
     Other {
         def_id: DefId,
         substs: SubstsRef<'tcx>,
@@ -101,9 +112,120 @@ pub fn ty_callee<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Option<Callee<'tcx>>
             };
             Some(Callee::PtrOffset { pointee_ty, mutbl })
         }
+        "malloc" if tcx.is_foreign_item(did) => Some(Callee::Malloc),
+        "calloc" if tcx.is_foreign_item(did) => Some(Callee::Calloc),
+        "free" if tcx.is_foreign_item(did) => Some(Callee::Free),
         _ => Some(Callee::Other {
             def_id: did,
             substs,
         }),
     }
 }
+
+/// Check whether `ty` is `core::ffi::c_void` (which `std` and current versions of `libc`
+/// re-export), or the copy that older versions of `libc` define in a platform module.
+pub fn is_c_void(tcx: TyCtxt, ty: Ty) -> bool {
+    let did = match *ty.kind() {
+        TyKind::Adt(adt_def, _) => adt_def.did(),
+        _ => return false,
+    };
+    let path = tcx.def_path(did);
+    let names = path
+        .data
+        .iter()
+        .map(|d| d.data.to_string())
+        .collect::<Vec<_>>();
+    match tcx.crate_name(did.krate).as_str() {
+        "core" => names == ["ffi", "c_void"],
+        "libc" => names.last().map_or(false, |name| name == "c_void"),
+        _ => false,
+    }
+}
+
+/// Compute the permissions needed to offset a pointer by `op`, the `isize` argument of an
+/// `offset` call in `mir`.  Offsets that are known to be non-negative only need `OFFSET_ADD` and
+/// offsets that are known to be non-positive only need `OFFSET_SUB`.  Currently this recognizes
+/// constants, casts from unsigned types (as in `p.offset(i as isize)` with `i: usize`), and
+/// negations of either (as in `p.offset(-(n as isize))`).
+pub fn offset_perms<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    mir: &Body<'tcx>,
+    op: &Operand<'tcx>,
+) -> PermissionSet {
+    let unknown = PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB;
+    match *op {
+        Operand::Constant(ref c) => {
+            let bits = match c.literal.try_eval_bits(tcx, ParamEnv::reveal_all(), c.ty()) {
+                Some(x) => x,
+                None => return unknown,
+            };
+            let x = tcx.data_layout.pointer_size.sign_extend(bits) as i128;
+            match x.cmp(&0) {
+                Ordering::Greater => PermissionSet::OFFSET_ADD,
+                Ordering::Less => PermissionSet::OFFSET_SUB,
+                Ordering::Equal => PermissionSet::empty(),
+            }
+        }
+        Operand::Copy(pl) | Operand::Move(pl) => {
+            let local = match pl.as_local() {
+                Some(l) if mir.local_kind(l) == rustc_middle::mir::LocalKind::Temp => l,
+                _ => return unknown,
+            };
+            // A temporary may be assigned in several places, as in
+            // `p.offset(if c { n as isize } else { -(n as isize) })`, so the offset needs the
+            // permissions of every value that may be assigned to it.
+            let mut perms = PermissionSet::empty();
+            let mut assigned = false;
+            for bb in mir.basic_blocks() {
+                for stmt in &bb.statements {
+                    match stmt.kind {
+                        StatementKind::Assign(ref x) if x.0.as_local() == Some(local) => {
+                            perms |= rvalue_offset_perms(tcx, mir, &x.1);
+                            assigned = true;
+                        }
+                        _ => {}
+                    }
+                }
+                if let TerminatorKind::Call { destination, .. } = bb.terminator().kind {
+                    if destination.as_local() == Some(local) {
+                        return unknown;
+                    }
+                }
+            }
+            if assigned {
+                perms
+            } else {
+                unknown
+            }
+        }
+    }
+}
+
+/// Compute the permissions needed to offset a pointer by the result of `rv`.  See
+/// [`offset_perms`].
+fn rvalue_offset_perms<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    mir: &Body<'tcx>,
+    rv: &Rvalue<'tcx>,
+) -> PermissionSet {
+    let unknown = PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB;
+    match *rv {
+        Rvalue::Use(ref op) => offset_perms(tcx, mir, op),
+        Rvalue::Cast(_, ref op, _) if matches!(op.ty(mir, tcx).kind(), TyKind::Uint(_)) => {
+            PermissionSet::OFFSET_ADD
+        }
+        Rvalue::UnaryOp(UnOp::Neg, ref op) => {
+            let perms = offset_perms(tcx, mir, op);
+            if perms == unknown {
+                unknown
+            } else if perms.contains(PermissionSet::OFFSET_ADD) {
+                PermissionSet::OFFSET_SUB
+            } else if perms.contains(PermissionSet::OFFSET_SUB) {
+                PermissionSet::OFFSET_ADD
+            } else {
+                PermissionSet::empty()
+            }
+        }
+        _ => unknown,
+    }
+}
@@ -0,0 +1,57 @@
+use std::ffi::c_void;
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+}
+
+// CHECK-LABEL: final labeling for "alloc1_box"
+pub unsafe fn alloc1_box() {
+    // CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = READ | WRITE | UNIQUE | FREE#
+    let p = malloc(4) as *mut i32;
+    *p = 1;
+    free(p as *mut c_void);
+}
+// CHECK-LABEL: type assignment for "alloc1_box"
+// CHECK: ({{.*}}: p): std::boxed::Box<i32>
+
+// CHECK-LABEL: final labeling for "alloc1_borrow"
+pub unsafe fn alloc1_borrow() {
+    // CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = READ | WRITE | UNIQUE | FREE#
+    let p = malloc(4) as *mut i32;
+    // CHECK-DAG: ([[#@LINE+1]]: q): {{.*}}type = READ | WRITE | UNIQUE#
+    let q = p;
+    *q = 1;
+    free(p as *mut c_void);
+}
+// CHECK-LABEL: type assignment for "alloc1_borrow"
+// CHECK: ({{.*}}: p): std::boxed::Box<i32>
+// CHECK: ({{.*}}: q): &mut i32
+
+// The caller shares ownership of the result, but it's still unique within this function.
+// CHECK-LABEL: final labeling for "alloc1_new"
+// CHECK-DAG: ([[#@LINE+1]]: *mut i32): {{.*}}type = READ | WRITE | UNIQUE | FREE#
+unsafe fn alloc1_new() -> *mut i32 {
+    malloc(4) as *mut i32
+}
+
+// CHECK-LABEL: final labeling for "alloc1_delete"
+// CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = FREE#
+unsafe fn alloc1_delete(p: *mut i32) {
+    free(p as *mut c_void);
+}
+
+// CHECK-LABEL: final labeling for "alloc1_shared"
+pub unsafe fn alloc1_shared() {
+    // CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = READ | WRITE | FREE#
+    let p = alloc1_new();
+    // CHECK-DAG: ([[#@LINE+1]]: q): {{.*}}type = READ | WRITE | FREE#
+    let q = p;
+    *q = 1;
+    *p = 2;
+    *q = 3;
+    alloc1_delete(q);
+}
+// CHECK-LABEL: type assignment for "alloc1_shared"
+// CHECK: ({{.*}}: p): std::rc::Rc<std::cell::Cell<i32>>
+// CHECK: ({{.*}}: q): std::rc::Rc<std::cell::Cell<i32>>
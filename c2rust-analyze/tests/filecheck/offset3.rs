@@ -0,0 +1,48 @@
+// Pointers that are only offset forwards become slices, and pointers that may move backwards
+// become `OffsetPtr`s, represented as a slice and an index.
+
+// CHECK-LABEL: final labeling for "offset3_add"
+// CHECK-DAG: ([[#@LINE+1]]: x): {{.*}}type = READ | UNIQUE | OFFSET_ADD#
+pub unsafe fn offset3_add(x: *mut i32, n: usize) -> i32 {
+    *x.offset(n as isize)
+}
+// CHECK-LABEL: type assignment for "offset3_add"
+// CHECK: _1 ({{.*}}: x): &[i32]
+
+// CHECK-LABEL: final labeling for "offset3_sub"
+// CHECK-DAG: ([[#@LINE+1]]: x): {{.*}}type = READ | UNIQUE | OFFSET_SUB#
+pub unsafe fn offset3_sub(x: *mut i32) -> i32 {
+    *x.offset(-1)
+}
+// CHECK-LABEL: type assignment for "offset3_sub"
+// CHECK: _1 ({{.*}}: x): (&[i32], usize)
+// CHECK: OffsetOffsetPtr { mutbl: false }
+// CHECK-NEXT: OffsetPtrCurrent { mutbl: false }
+
+// CHECK-LABEL: final labeling for "offset3_sub_unsigned"
+// CHECK-DAG: ([[#@LINE+1]]: x): {{.*}}type = READ | UNIQUE | OFFSET_SUB#
+pub unsafe fn offset3_sub_unsigned(x: *mut i32, n: usize) -> i32 {
+    *x.offset(-(n as isize))
+}
+
+// CHECK-LABEL: final labeling for "offset3_walk_back"
+// CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = READ | WRITE | UNIQUE | OFFSET_ADD | OFFSET_SUB#
+pub unsafe fn offset3_walk_back(p: *mut u8, n: usize) {
+    // CHECK-DAG: ([[#@LINE+1]]: end): {{.*}}type = READ | WRITE | UNIQUE | OFFSET_SUB#
+    let end = p.offset(n as isize);
+    // CHECK-DAG: ([[#@LINE+1]]: last): {{.*}}type = READ | WRITE | UNIQUE#
+    let last = end.offset(-1);
+    *last = 0;
+}
+// CHECK-LABEL: type assignment for "offset3_walk_back"
+// CHECK: _1 ({{.*}}: p): (&mut [u8], usize)
+// CHECK: ({{.*}}: end): (&mut [u8], usize)
+// CHECK: ({{.*}}: last): &mut u8
+
+// CHECK-LABEL: final labeling for "offset3_either"
+// CHECK-DAG: ([[#@LINE+1]]: x): {{.*}}type = READ | UNIQUE | OFFSET_ADD | OFFSET_SUB#
+pub unsafe fn offset3_either(x: *mut i32, n: usize, back: bool) -> i32 {
+    *x.offset(if back { -(n as isize) } else { n as isize })
+}
+// CHECK-LABEL: type assignment for "offset3_either"
+// CHECK: _1 ({{.*}}: x): (&[i32], usize)
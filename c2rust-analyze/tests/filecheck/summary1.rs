@@ -0,0 +1,29 @@
+use std::ffi::c_void;
+
+extern "C" {
+    fn strlen(s: *const u8) -> usize;
+    fn memcpy(dest: *mut c_void, src: *const c_void, n: usize) -> *mut c_void;
+    fn opaque(p: *mut i32);
+}
+
+// CHECK-LABEL: final labeling for "summary1_strlen"
+// CHECK-DAG: ([[#@LINE+1]]: s): {{.*}}type = READ | UNIQUE | OFFSET_ADD#
+pub unsafe fn summary1_strlen(s: *mut u8) -> usize {
+    strlen(s)
+}
+
+// CHECK-LABEL: final labeling for "summary1_memcpy"
+// CHECK-DAG: ([[#@LINE+2]]: dest): {{.*}}type = READ | WRITE | UNIQUE | OFFSET_ADD#
+// CHECK-DAG: ([[#@LINE+1]]: src): {{.*}}type = READ | UNIQUE | OFFSET_ADD#
+pub unsafe fn summary1_memcpy(dest: *mut u8, src: *mut u8, n: usize) -> u8 {
+    // CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = READ | UNIQUE#
+    let p = memcpy(dest as *mut c_void, src as *const c_void, n) as *mut u8;
+    *p
+}
+
+// Functions without a summary may do anything with their arguments.
+// CHECK-LABEL: final labeling for "summary1_opaque"
+pub unsafe fn summary1_opaque(p: *mut i32) {
+    // CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = READ | WRITE | OFFSET_ADD | OFFSET_SUB#
+    opaque(p);
+}
@@ -0,0 +1,138 @@
+use serde_json::{json, Value};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Run `c2rust-analyze --output-json` on `src`, writing the output and the compiled crate to a
+/// scratch directory named `name`, and return the path of the output.
+fn analyze(src: &str, name: &str) -> PathBuf {
+    analyze_with_args(src, name, &[])
+}
+
+/// Like [`analyze`], but also passes `args` to `c2rust-analyze`.
+fn analyze_with_args(src: &str, name: &str, args: &[&str]) -> PathBuf {
+    let lib_dir = env::var("C2RUST_TARGET_LIB_DIR").unwrap();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("json")
+        .join(name);
+    fs::create_dir_all(&dir).unwrap();
+    let json_path = dir.join("output.json");
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--manifest-path")
+        .arg(format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR")))
+        .arg("--")
+        .arg(src)
+        .arg("--output-json")
+        .arg(&json_path)
+        .args(args)
+        .arg("-L")
+        .arg(lib_dir)
+        .arg("--crate-type")
+        .arg("rlib")
+        .arg("--out-dir")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "c2rust-analyze failed with status {:?}:\n{}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr),
+    );
+    json_path
+}
+
+#[test]
+fn json_output() {
+    let path = analyze("tests/filecheck/offset1.rs", "offset1");
+    let json = fs::read_to_string(&path).unwrap();
+    let output: Value = serde_json::from_str(&json).unwrap();
+
+    let func = &output["functions"][0];
+    assert_eq!(func["name"], "offset1_const");
+
+    // `x`, the first argument
+    let local = &func["locals"][1];
+    assert_eq!(local["ty"], "*mut i32");
+    assert_eq!(local["rewritten_ty"], "&[i32]");
+    assert_eq!(local["addr_of"]["perms"], json!(["UNIQUE"]));
+    assert_eq!(local["addr_of"]["flags"], json!([]));
+    assert_eq!(local["addr_of"].get("ownership"), None);
+    let ptr = &local["pointers"][0];
+    assert_eq!(ptr["perms"], json!(["READ", "UNIQUE", "OFFSET_ADD"]));
+    assert_eq!(ptr["flags"], json!([]));
+    assert_eq!(ptr["ownership"], "Imm");
+    assert_eq!(ptr["quantity"], "Slice");
+
+    assert_eq!(
+        func["rewrites"][0]["kinds"],
+        json!([
+            { "OffsetSlice": { "mutbl": false } },
+            { "SliceFirst": { "mutbl": false } },
+        ])
+    );
+}
+
+#[test]
+fn json_output_deterministic() {
+    let a = fs::read_to_string(analyze("tests/filecheck/alias1.rs", "alias1-a")).unwrap();
+    let b = fs::read_to_string(analyze("tests/filecheck/alias1.rs", "alias1-b")).unwrap();
+    assert_eq!(a, b);
+}
+
+/// Return the permissions of the first argument of each function in the output at `path`.
+fn arg_perms(path: &Path) -> Vec<(String, Value)> {
+    let output: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    output["functions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|func| {
+            let name = func["name"].as_str().unwrap().to_owned();
+            (name, func["locals"][1]["pointers"][0]["perms"].clone())
+        })
+        .collect()
+}
+
+#[test]
+fn fn_summaries_file() {
+    let builtin = arg_perms(&analyze("tests/filecheck/summary1.rs", "summary1-builtin"));
+    let custom = arg_perms(&analyze_with_args(
+        "tests/filecheck/summary1.rs",
+        "summary1-custom",
+        &["--fn-summaries", "tests/fn_summaries/summary1.json"],
+    ));
+
+    assert_eq!(
+        builtin,
+        [
+            (
+                "summary1_strlen".into(),
+                json!(["READ", "UNIQUE", "OFFSET_ADD"])
+            ),
+            (
+                "summary1_memcpy".into(),
+                json!(["READ", "WRITE", "UNIQUE", "OFFSET_ADD"])
+            ),
+            (
+                "summary1_opaque".into(),
+                json!(["READ", "WRITE", "UNIQUE", "OFFSET_ADD", "OFFSET_SUB"])
+            ),
+        ]
+    );
+    // The file replaces the built-in summary for `strlen` and adds one for `opaque`, but leaves
+    // `memcpy` alone.
+    assert_eq!(
+        custom,
+        [
+            ("summary1_strlen".into(), json!(["READ", "UNIQUE"])),
+            (
+                "summary1_memcpy".into(),
+                json!(["READ", "WRITE", "UNIQUE", "OFFSET_ADD"])
+            ),
+            ("summary1_opaque".into(), json!(["READ", "WRITE", "UNIQUE"])),
+        ]
+    );
+}
@@ -0,0 +1,107 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Copy `tests/rewrite/{name}.rs` into a fresh directory, so the rewrites don't touch the
+/// checked-in input, and return the path of the copy.
+fn copy_input(name: &str, mode: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("rewrite-{}", mode));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}.rs", name));
+    fs::copy(format!("tests/rewrite/{}.rs", name), &path).unwrap();
+    path
+}
+
+/// Run `c2rust-analyze --rewrite-mode {mode}` on `path` and return its stdout.
+fn analyze(path: &Path, mode: &str) -> String {
+    let lib_dir = env::var("C2RUST_TARGET_LIB_DIR").unwrap();
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--manifest-path")
+        .arg(format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR")))
+        .arg("--")
+        .arg(path)
+        .arg("--rewrite-mode")
+        .arg(mode)
+        .arg("-L")
+        .arg(lib_dir)
+        .arg("--crate-type")
+        .arg("rlib")
+        .arg("--out-dir")
+        .arg(path.parent().unwrap())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "c2rust-analyze failed with status {:?}:\n{}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr),
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn expected(name: &str) -> String {
+    fs::read_to_string(format!("tests/rewrite/{}.expected", name)).unwrap()
+}
+
+/// Check that the rewritten code at `path` compiles.
+fn assert_compiles(path: &Path) {
+    let status = Command::new("rustc")
+        .arg(path)
+        .arg("--edition")
+        .arg("2021")
+        .arg("--crate-type")
+        .arg("rlib")
+        .arg("--out-dir")
+        .arg(path.parent().unwrap())
+        .status()
+        .unwrap();
+    assert!(status.success(), "rewritten code failed to compile");
+}
+
+#[test]
+fn rewrite_inplace() {
+    let path = copy_input("rewrite1", "inplace");
+    analyze(&path, "inplace");
+    assert_eq!(fs::read_to_string(&path).unwrap(), expected("rewrite1"));
+    assert_compiles(&path);
+}
+
+#[test]
+fn rewrite_alongside() {
+    let path = copy_input("rewrite1", "alongside");
+    analyze(&path, "alongside");
+    let orig = fs::read_to_string("tests/rewrite/rewrite1.rs").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), orig);
+    assert_eq!(
+        fs::read_to_string(path.with_extension("new")).unwrap(),
+        expected("rewrite1")
+    );
+}
+
+#[test]
+fn rewrite_diff() {
+    let path = copy_input("rewrite1", "diff");
+    let diff = analyze(&path, "diff");
+    let orig = fs::read_to_string("tests/rewrite/rewrite1.rs").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), orig);
+
+    let name = path.display();
+    assert!(diff.starts_with(&format!("--- {}\n+++ {}\n", name, name)));
+    assert!(diff.contains("\n-    *p.offset(1)\n"));
+    assert!(diff.contains("\n+    *&(&(&*p)[1 as usize..])[0]\n"));
+    assert!(diff.contains("\n-    q = p;\n"));
+    assert!(diff.contains("\n+    q = &*p;\n"));
+}
+
+/// The rewritten `malloc` and `free` calls must match the `Box` and `Rc` types inferred for the
+/// pointers, so the output should compile.
+#[test]
+fn rewrite_alloc() {
+    let path = copy_input("alloc", "alloc");
+    analyze(&path, "inplace");
+    assert_eq!(fs::read_to_string(&path).unwrap(), expected("alloc"));
+    assert_compiles(&path);
+}
@@ -0,0 +1,37 @@
+use std::ffi::c_void;
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+}
+
+pub unsafe fn alloc_box() -> i32 {
+    let p = malloc(4) as *mut i32;
+    *p = 1;
+    let x = *p;
+    free(p as *mut c_void);
+    x
+}
+
+pub unsafe fn alloc_borrow() -> i32 {
+    let p = malloc(4) as *mut i32;
+    let q = p;
+    *q = 1;
+    let x = *q;
+    free(p as *mut c_void);
+    x
+}
+
+pub unsafe fn alloc_leak() -> i32 {
+    let p = malloc(4) as *mut i32;
+    *p = 1;
+    *p
+}
+
+pub unsafe fn alloc_rc() -> i32 {
+    let p = malloc(4) as *mut i32;
+    let q = p;
+    let x = *p + *q;
+    free(q as *mut c_void);
+    x
+}
@@ -0,0 +1,14 @@
+// Rewriting `p.offset(1)` requires a cast of the receiver, which is a subexpression of the
+// call's span.
+pub unsafe fn offset_read(p: *mut i32) -> i32 {
+    *p.offset(2) = 1;
+    *p.offset(1)
+}
+
+// The cast of `p` here applies to the RHS of the assignment, not the whole expression.
+pub unsafe fn assign(p: *mut i32) -> i32 {
+    let q: *const i32;
+    *p = 1;
+    q = p;
+    *q
+}
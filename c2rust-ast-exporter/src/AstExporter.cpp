@@ -3,6 +3,7 @@
 #include <fstream>
 #include <iostream>
 #include <iterator>
+#include <mutex>
 #include <set>
 #include <unordered_map>
 #include <unordered_set>
@@ -561,6 +562,9 @@ class TranslateASTVisitor final
     std::unordered_set<unsigned> macroCallSites;
     SmallVector<MacroInfo*, 1> curMacroExpansionStack;
     StringRef curMacroExpansionSource;
+    // The arguments of the function-like macro the current expression is an
+    // expansion of, with the index of the parameter each one is for.
+    std::vector<std::pair<unsigned, Expr *>> curMacroArgs;
 
     // Returns true when a new entry is added to exportedTags
     bool markForExport(void *ptr, ASTEntryTag tag) {
@@ -655,7 +659,21 @@ class TranslateASTVisitor final
             cbor_encode_null(&local);
         }
 
-        // 12.. - Extra entries
+        // 12 - Macro arguments, as pairs of parameter index and expression
+        cbor_encoder_create_array(&local, &childEnc,
+                                  encodeMacroExpansions ? curMacroArgs.size() : 0);
+        if (encodeMacroExpansions) {
+            for (auto &Arg : curMacroArgs) {
+                CborEncoder pairEnc;
+                cbor_encoder_create_array(&childEnc, &pairEnc, 2);
+                cbor_encode_uint(&pairEnc, Arg.first);
+                cbor_encode_uint(&pairEnc, uintptr_t(Arg.second));
+                cbor_encoder_close_container(&childEnc, &pairEnc);
+            }
+        }
+        cbor_encoder_close_container(&local, &childEnc);
+
+        // 13.. - Extra entries
         extra(&local);
 
         cbor_encoder_close_container(encoder, &local);
@@ -773,6 +791,59 @@ class TranslateASTVisitor final
         return true;
     }
 
+    // The index of the parameter of `mac` whose argument `loc` is in, if `loc`
+    // is in an argument of the expansion of `mac` at `ExpansionLoc`, or -1.
+    int getMacroParam(MacroInfo *mac, SourceLocation ExpansionLoc,
+                      SourceLocation loc) const {
+        auto &Mgr = Context->getSourceManager();
+        if (!loc.isMacroID() || !Mgr.isMacroArgExpansion(loc))
+            return -1;
+        // The expansion location of a macro argument is where its parameter
+        // is used in the replacement list.
+#if CLANG_VERSION_MAJOR < 7
+        auto ParamLoc = Mgr.getImmediateExpansionRange(loc).first;
+        if (!ParamLoc.isMacroID() ||
+            Mgr.getImmediateExpansionRange(ParamLoc).first != ExpansionLoc)
+            return -1;
+#else // CLANG_VERSION_MAJOR >= 7
+        auto ParamLoc = Mgr.getImmediateExpansionRange(loc).getBegin();
+        if (!ParamLoc.isMacroID() ||
+            Mgr.getImmediateExpansionRange(ParamLoc).getBegin() != ExpansionLoc)
+            return -1;
+#endif
+        Token Tok;
+        if (Lexer::getRawToken(Mgr.getSpellingLoc(ParamLoc), Tok, Mgr,
+                               Context->getLangOpts(), false) ||
+            !Tok.is(tok::raw_identifier))
+            return -1;
+        auto Name = Tok.getRawIdentifier();
+        int i = 0;
+        for (auto *Param : mac->params()) {
+            if (Param->getName() == Name)
+                return i;
+            i++;
+        }
+        return -1;
+    }
+
+    // Find the largest subexpressions of S that are arguments of the
+    // expansion of `mac` at `ExpansionLoc`.
+    void findMacroArgs(MacroInfo *mac, SourceLocation ExpansionLoc, Stmt *S) {
+        for (auto *Child : S->children()) {
+            if (!Child)
+                continue;
+            if (auto *E = dyn_cast<Expr>(Child)) {
+                auto Param = getMacroParam(mac, ExpansionLoc, E->getBeginLoc());
+                if (Param >= 0 &&
+                    Param == getMacroParam(mac, ExpansionLoc, E->getEndLoc())) {
+                    curMacroArgs.emplace_back(Param, E);
+                    continue;
+                }
+            }
+            findMacroArgs(mac, ExpansionLoc, Child);
+        }
+    }
+
     static bool isScalarAsmType(QualType ty) {
         ty = ty.getCanonicalType();
         switch (ty->getTypeClass()) {
@@ -841,8 +912,18 @@ class TranslateASTVisitor final
             std::vector<void *> childIds;
             auto range = SourceRange(Mac->getDefinitionLoc(), Mac->getDefinitionEndLoc());
             encode_entry_raw(Mac, tag, range, QualType(), false,
-                             false, false, childIds, [Name](CborEncoder *local) {
+                             false, false, childIds, [Name, Mac](CborEncoder *local) {
                                  cbor_encode_string(local, Name.str());
+                                 if (Mac->isFunctionLike()) {
+                                     CborEncoder params;
+                                     cbor_encoder_create_array(local, &params,
+                                                               Mac->getNumParams());
+                                     for (auto *Param : Mac->params()) {
+                                         cbor_encode_string(&params, Param->getName().str());
+                                     }
+                                     cbor_encoder_close_container(local, &params);
+                                     cbor_encode_boolean(local, Mac->isVariadic());
+                                 }
                              });
 
         }
@@ -1191,6 +1272,7 @@ class TranslateASTVisitor final
     bool VisitExpr(Expr *E) {
         curMacroExpansionStack.clear();
         curMacroExpansionSource = StringRef();
+        curMacroArgs.clear();
 
         // We only translate constant macro objects to Rust consts, so this
         // expression must be constant.
@@ -1229,6 +1311,7 @@ class TranslateASTVisitor final
         // The macro stack unwound by getImmediateMacroCallerLoc and friends
         // starts with literal replacement and works it's way to the macro call
         // that was replaced.
+        SourceLocation FirstExpansionLoc;
         while (Begin.isMacroID()) {
 #if CLANG_VERSION_MAJOR < 7
             auto ExpansionRange = Mgr.getImmediateExpansionRange(Begin);
@@ -1257,9 +1340,18 @@ class TranslateASTVisitor final
             End = ExpansionEnd;
 
             if (VisitMacro(name, Begin, mac, E)) {
+                if (curMacroExpansionStack.empty())
+                    FirstExpansionLoc = Begin;
                 curMacroExpansionStack.push_back(mac);
             }
         }
+
+        // Only the arguments of a single function-like macro are recorded, not
+        // those of nested expansions.
+        if (curMacroExpansionStack.size() == 1 &&
+            curMacroExpansionStack[0]->isFunctionLike()) {
+            findMacroArgs(curMacroExpansionStack[0], FirstExpansionLoc, E);
+        }
         return true;
     }
 
@@ -2753,6 +2845,13 @@ Outputs process(int argc, const char *argv[], int *result) {
     auto argv_ = augment_argv(argc, argv);
     int argc_ = argv_.size() - 1; // ignore the extra nullptr
 
+    // CommonOptionsParser writes to the global llvm::cl options, so only one
+    // thread may parse options at a time. Running the tool itself only uses
+    // state owned by this invocation, so translation units can still be
+    // exported in parallel.
+    static std::mutex options_mutex;
+    std::unique_lock<std::mutex> options_lock(options_mutex);
+
 #if CLANG_VERSION_MAJOR < 13
     CommonOptionsParser OptionsParser(argc_, argv_.data(), MyToolCategory);
 #else
@@ -2778,6 +2877,7 @@ Outputs process(int argc, const char *argv[], int *result) {
     // CommonOptionsParser is stateful so the vector returned by
     // getSourcePathList() includes paths from past invocations.
     std::string sourcePath = OptionsParser.getSourcePathList().back();
+    options_lock.unlock();
     // Make a new list with just the file we're currently translating
     std::vector<std::string> sourcePathList(1, sourcePath);
     ClangTool Tool(OptionsParser.getCompilations(), sourcePathList);
@@ -82,6 +82,10 @@ pub struct AstNode {
     // macro definitions.
     pub macro_expansions: Vec<u64>,
     pub macro_expansion_text: Option<String>,
+    // Arguments of the function-like macro this node is an expansion of, as
+    // pairs of the parameter index and the argument expression. A parameter
+    // that is used more than once has an argument for each use.
+    pub macro_args: Vec<(usize, u64)>,
     pub extras: Vec<Value>,
 }
 
@@ -237,6 +241,9 @@ pub fn process(items: Value) -> error::Result<AstContext> {
                 .unwrap()
                 .map(|s| s.to_string());
 
+            // entry[12]
+            let macro_args = from_value::<Vec<(usize, u64)>>(entry.pop_front().unwrap()).unwrap();
+
             let node = AstNode {
                 tag: import_ast_tag(tag),
                 children,
@@ -251,6 +258,7 @@ pub fn process(items: Value) -> error::Result<AstContext> {
                 rvalue,
                 macro_expansions,
                 macro_expansion_text,
+                macro_args,
                 extras: entry.into_iter().collect(),
             };
 
@@ -7,10 +7,14 @@ use std::slice;
 
 pub mod clang_ast;
 
-pub fn get_clang_major_version() -> Option<u32> {
+/// The version of clang that the AST exporter was built with, such as `14.0.6`.
+pub fn get_clang_version() -> &'static str {
     let s = unsafe { CStr::from_ptr(clang_version()) };
-    s.to_str()
-        .unwrap()
+    s.to_str().unwrap()
+}
+
+pub fn get_clang_major_version() -> Option<u32> {
+    get_clang_version()
         .split('.')
         .next()
         .unwrap()
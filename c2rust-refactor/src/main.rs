@@ -0,0 +1,155 @@
+use clap::{load_yaml, App, ArgMatches};
+use std::fs;
+use std::process;
+use std::str::FromStr;
+
+use c2rust_refactor::file_io::OutputMode;
+use c2rust_refactor::{CargoTarget, Command, Cursor, Mark, Options, RustcArgSource};
+
+fn main() {
+    let yaml = load_yaml!("refactor.yaml");
+    let args = App::from_yaml(yaml).get_matches();
+
+    let opts = match parse_opts(&args) {
+        Some(opts) => opts,
+        None => process::exit(1),
+    };
+
+    let ret = match c2rust_refactor::lib_main(opts) {
+        Ok(()) => 0,
+        Err(_) => 1,
+    };
+    process::exit(ret);
+}
+
+fn parse_opts(args: &ArgMatches) -> Option<Options> {
+    // Parse rewrite mode
+    let rewrite_modes = match args.values_of("rewrite-mode") {
+        Some(values) => values
+            .map(|s| match s {
+                "inplace" => OutputMode::InPlace,
+                "alongside" => OutputMode::Alongside,
+                "print" => OutputMode::Print,
+                "diff" => OutputMode::PrintDiff,
+                "json" => OutputMode::Json,
+                "marks" => OutputMode::Marks,
+                _ => unreachable!(),
+            })
+            .collect(),
+        None => vec![OutputMode::Print],
+    };
+
+    // Parse cursors
+    let cursor_strs = args.values_of_lossy("cursor").unwrap_or_default();
+    let mut cursors = Vec::with_capacity(cursor_strs.len());
+    for s in &cursor_strs {
+        let mut parts = s.split(':');
+
+        let file = parts.next()?.to_owned();
+        let line = parts.next().and_then(|s| u32::from_str(s).ok());
+        let col = parts.next().and_then(|s| u32::from_str(s).ok());
+        let (line, col) = match (line, col) {
+            (Some(line), Some(col)) => (line, col),
+            _ => {
+                eprintln!("Bad cursor string: {:?}", s);
+                return None;
+            }
+        };
+        let label = parts.next().filter(|s| !s.is_empty()).map(str::to_owned);
+        let kind = parts.next().map(str::to_owned);
+
+        if parts.next().is_some() {
+            eprintln!("Bad cursor string: {:?}", s);
+            return None;
+        }
+
+        cursors.push(Cursor::new(file, line, col, label, kind));
+    }
+
+    // Parse marks
+    let mark_strs = args.values_of_lossy("mark").unwrap_or_default();
+    let mut marks = Vec::with_capacity(mark_strs.len());
+    for s in &mark_strs {
+        let mut parts = s.split(':');
+
+        let id = match parts.next().and_then(|s| usize::from_str(s).ok()) {
+            Some(id) => id,
+            None => {
+                eprintln!("Bad mark string: {:?}", s);
+                return None;
+            }
+        };
+        let label = parts.next().map(str::to_owned);
+
+        if parts.next().is_some() {
+            eprintln!("Bad mark string: {:?}", s);
+            return None;
+        }
+
+        marks.push(Mark::new(id, label));
+    }
+
+    // Get plugin options
+    let plugins = args.values_of_lossy("plugin-name").unwrap_or_default();
+    let plugin_dirs = args.values_of_lossy("plugin-dir").unwrap_or_default();
+
+    // Handle --cargo and rustc-args
+    let rustc_args = match args.values_of_lossy("rustc-args") {
+        Some(args) => RustcArgSource::CmdLine(args),
+        None => {
+            let target = if let Some(bin) = args.value_of("bin") {
+                CargoTarget::Bin(bin.to_owned())
+            } else if args.is_present("bins") {
+                CargoTarget::AllBins
+            } else if args.is_present("lib") {
+                CargoTarget::Lib
+            } else {
+                CargoTarget::All
+            };
+            RustcArgSource::Cargo(target)
+        }
+    };
+
+    // Parse command names + args
+    let transforms: Vec<String> = match args.value_of("transforms-file") {
+        Some(file_name) => match fs::read_to_string(file_name) {
+            Ok(contents) => contents.split_whitespace().map(str::to_owned).collect(),
+            Err(e) => {
+                eprintln!("Could not read transforms file {:?}: {}", file_name, e);
+                return None;
+            }
+        },
+        None => args.values_of_lossy("transforms").unwrap_or_default(),
+    };
+    let mut commands = Vec::new();
+    let mut cur_command: Option<Command> = None;
+    for arg in transforms {
+        if arg == ";" {
+            match cur_command.take() {
+                Some(cmd) => commands.push(cmd),
+                None => {
+                    eprintln!("Expected command before ';'");
+                    return None;
+                }
+            }
+        } else if let Some(cmd) = cur_command.as_mut() {
+            cmd.args.push(arg);
+        } else {
+            cur_command = Some(Command {
+                name: arg,
+                args: Vec::new(),
+            });
+        }
+    }
+    commands.extend(cur_command);
+
+    Some(Options {
+        rewrite_modes,
+        commands,
+        rustc_args,
+        cursors,
+        marks,
+        plugins,
+        plugin_dirs,
+    })
+}
@@ -5,6 +5,7 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use handlebars::Handlebars;
+use indexmap::IndexSet;
 use pathdiff::diff_paths;
 use serde_derive::Serialize;
 use serde_json::json;
@@ -59,8 +60,12 @@ pub fn get_build_dir(tcfg: &TranspilerConfig, cc_db: &Path) -> PathBuf {
 pub struct CrateConfig<'lcmd> {
     pub crate_name: String,
     pub modules: Vec<PathBuf>,
+    /// Modules emitted as binaries instead of being included in `lib.rs`
+    pub binaries: Vec<PathBuf>,
     pub pragmas: PragmaSet,
     pub crates: CrateSet,
+    /// Features selecting between the configurations of files compiled with different `-D` flags
+    pub features: IndexSet<String>,
     pub link_cmd: &'lcmd LinkCmd,
 }
 
@@ -73,14 +78,7 @@ pub fn emit_build_files<'lcmd>(
     crate_cfg: Option<CrateConfig<'lcmd>>,
     workspace_members: Option<Vec<String>>,
 ) -> Option<PathBuf> {
-    let mut reg = Handlebars::new();
-
-    reg.register_template_string("Cargo.toml", include_str!("Cargo.toml.hbs"))
-        .unwrap();
-    reg.register_template_string("lib.rs", include_str!("lib.rs.hbs"))
-        .unwrap();
-    reg.register_template_string("build.rs", include_str!("build.rs.hbs"))
-        .unwrap();
+    let reg = templates();
 
     if !build_dir.exists() {
         fs::create_dir_all(&build_dir)
@@ -98,12 +96,25 @@ pub fn emit_build_files<'lcmd>(
             &reg,
             build_dir,
             ccfg.modules,
+            &ccfg.binaries,
             ccfg.pragmas,
             &ccfg.crates,
         )
     })
 }
 
+fn templates() -> Handlebars<'static> {
+    let mut reg = Handlebars::new();
+
+    reg.register_template_string("Cargo.toml", include_str!("Cargo.toml.hbs"))
+        .unwrap();
+    reg.register_template_string("lib.rs", include_str!("lib.rs.hbs"))
+        .unwrap();
+    reg.register_template_string("build.rs", include_str!("build.rs.hbs"))
+        .unwrap();
+    reg
+}
+
 #[derive(Serialize)]
 struct Module {
     path: Option<String>,
@@ -158,13 +169,13 @@ enum ModuleSubset {
 }
 
 fn convert_module_list(
-    tcfg: &TranspilerConfig,
     build_dir: &Path,
     mut modules: Vec<PathBuf>,
+    binaries: &[PathBuf],
     module_subset: ModuleSubset,
 ) -> Vec<Module> {
     modules.retain(|m| {
-        let is_binary = tcfg.is_binary(m);
+        let is_binary = binaries.contains(m);
         let is_binary_subset = module_subset == ModuleSubset::Binaries;
         // Don't add binary modules to lib.rs, these are emitted to
         // standalone, separate binary modules.
@@ -175,7 +186,7 @@ fn convert_module_list(
     let mut module_tree = ModuleTree(BTreeMap::new());
     for m in &modules {
         match m.strip_prefix(build_dir) {
-            Ok(relpath) if !tcfg.is_binary(m) => {
+            Ok(relpath) if !binaries.contains(m) => {
                 // The module is inside the build directory, use nested modules
                 let mut cur = &mut module_tree;
                 for sm in relpath.iter() {
@@ -222,6 +233,7 @@ fn emit_build_rs(
 ) -> Option<PathBuf> {
     let json = json!({
         "libraries": link_cmd.libs,
+        "library_dirs": link_cmd.lib_dirs,
     });
     let output = reg.render("build.rs", &json).unwrap();
     let output_path = build_dir.join("build.rs");
@@ -235,10 +247,11 @@ fn emit_lib_rs(
     reg: &Handlebars,
     build_dir: &Path,
     modules: Vec<PathBuf>,
+    binaries: &[PathBuf],
     pragmas: PragmaSet,
     crates: &CrateSet,
 ) -> Option<PathBuf> {
-    let modules = convert_module_list(tcfg, build_dir, modules, ModuleSubset::Libraries);
+    let modules = convert_module_list(build_dir, modules, binaries, ModuleSubset::Libraries);
     let crates = convert_dependencies_list(crates.clone());
     let file_name = get_lib_rs_file_name(tcfg);
     let json = json!({
@@ -271,6 +284,19 @@ fn emit_cargo_toml<'lcmd>(
     crate_cfg: &Option<CrateConfig<'lcmd>>,
     workspace_members: Option<Vec<String>>,
 ) {
+    let lib_rs_file = get_lib_rs_file_name(tcfg);
+    let output = render_cargo_toml(reg, build_dir, lib_rs_file, crate_cfg, workspace_members);
+    let output_path = build_dir.join("Cargo.toml");
+    maybe_write_to_file(&output_path, output, tcfg.overwrite_existing);
+}
+
+fn render_cargo_toml<'lcmd>(
+    reg: &Handlebars,
+    build_dir: &Path,
+    lib_rs_file: &str,
+    crate_cfg: &Option<CrateConfig<'lcmd>>,
+    workspace_members: Option<Vec<String>>,
+) -> String {
     // rust_checks_path is gone because we don't want to refer to the source
     // path but instead want the cross-check libs to be installed via cargo.
     let mut json = json!({
@@ -279,21 +305,30 @@ fn emit_cargo_toml<'lcmd>(
         "workspace_members": workspace_members.unwrap_or_default(),
     });
     if let Some(ccfg) = crate_cfg {
-        let binaries = convert_module_list(
-            tcfg,
+        let mut binaries = convert_module_list(
             build_dir,
             ccfg.modules.to_owned(),
+            &ccfg.binaries,
             ModuleSubset::Binaries,
         );
+        let is_library = ccfg.link_cmd.r#type.is_library();
+        if let [binary] = binaries.as_mut_slice() {
+            // Name the binary of an executable after the program it was linked into
+            if !is_library {
+                binary.name = ccfg.crate_name.clone();
+            }
+        }
         let dependencies = convert_dependencies_list(ccfg.crates.clone());
         let crate_json = json!({
             "crate_name": ccfg.crate_name,
             "crate_rust_name": ccfg.crate_name.replace('-', "_"),
             "crate_types": ccfg.link_cmd.r#type.as_cargo_types(),
-            "is_library": ccfg.link_cmd.r#type.is_library(),
-            "lib_rs_file": get_lib_rs_file_name(tcfg),
+            // Executables whose `main` was found are a library used by their binary
+            "is_library": is_library || !binaries.is_empty(),
+            "lib_rs_file": lib_rs_file,
             "binaries": binaries,
             "dependencies": dependencies,
+            "features": ccfg.features,
         });
         json.as_object_mut().unwrap().extend(
             crate_json
@@ -304,10 +339,7 @@ fn emit_cargo_toml<'lcmd>(
         );
     }
 
-    let file_name = "Cargo.toml";
-    let output_path = build_dir.join(file_name);
-    let output = reg.render(file_name, &json).unwrap();
-    maybe_write_to_file(&output_path, output, tcfg.overwrite_existing);
+    reg.render("Cargo.toml", &json).unwrap()
 }
 
 fn maybe_write_to_file(output_path: &Path, output: String, overwrite: bool) -> Option<PathBuf> {
@@ -327,3 +359,77 @@ fn maybe_write_to_file(output_path: &Path, output: String, overwrite: bool) -> O
 
     Some(PathBuf::from(output_path))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile_cmds::LinkType;
+    use crate::ExternCrate;
+
+    fn render(r#type: LinkType, modules: &[&str], binaries: &[&str], features: &[&str]) -> String {
+        render_with_crates(r#type, modules, binaries, features, &[ExternCrate::Libc])
+    }
+
+    fn render_with_crates(
+        r#type: LinkType,
+        modules: &[&str],
+        binaries: &[&str],
+        features: &[&str],
+        crates: &[ExternCrate],
+    ) -> String {
+        let build_dir = Path::new("/build");
+        let link_cmd = LinkCmd {
+            inputs: vec![],
+            output: None,
+            libs: vec![],
+            lib_dirs: vec![],
+            r#type,
+            cmd_inputs: vec![],
+            top_level: false,
+        };
+        let ccfg = CrateConfig {
+            crate_name: "my-prog".to_owned(),
+            modules: modules.iter().map(|m| build_dir.join(m)).collect(),
+            binaries: binaries.iter().map(|m| build_dir.join(m)).collect(),
+            pragmas: PragmaSet::new(),
+            crates: crates.iter().copied().collect(),
+            features: features.iter().map(|&f| f.to_owned()).collect(),
+            link_cmd: &link_cmd,
+        };
+        render_cargo_toml(&templates(), build_dir, "lib.rs", &Some(ccfg), None)
+    }
+
+    #[test]
+    fn test_cargo_toml_exe() {
+        insta::assert_snapshot!(render(
+            LinkType::Exe,
+            &["src/main.rs", "src/util.rs"],
+            &["src/main.rs"],
+            &[],
+        ));
+    }
+
+    #[test]
+    fn test_cargo_toml_library_binaries() {
+        insta::assert_snapshot!(render(
+            LinkType::Static,
+            &["src/tool.rs", "src/other.rs", "src/util.rs"],
+            &["src/tool.rs", "src/other.rs"],
+            &["use_avx2", "no_a"],
+        ));
+    }
+
+    #[test]
+    fn test_cargo_toml_x87() {
+        let toml = render_with_crates(
+            LinkType::Exe,
+            &["src/main.rs"],
+            &["src/main.rs"],
+            &[],
+            &[ExternCrate::Libc, ExternCrate::C2RustX87],
+        );
+        // The path depends on where the transpiler was built.
+        assert!(toml.contains(crate::C2RUST_X87_PATH), "{}", toml);
+        insta::assert_snapshot!(toml.replace(crate::C2RUST_X87_PATH, "/c2rust/c2rust-x87"));
+    }
+}
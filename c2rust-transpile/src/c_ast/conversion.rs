@@ -3,6 +3,7 @@ use crate::diagnostics::diag;
 use c2rust_ast_exporter::clang_ast::*;
 use failure::err_msg;
 use serde_bytes::ByteBuf;
+use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::vec::Vec;
@@ -10,7 +11,7 @@ use std::vec::Vec;
 use super::Located;
 use crate::diagnostics::{Diagnostic, TranslationError, TranslationErrorKind};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ClangAstParseErrorKind {
     MissingChild,
     MissingType,
@@ -154,6 +155,7 @@ fn parse_cast_kind(kind: &str) -> CastKind {
         "FloatingComplexToReal" => CastKind::FloatingComplexToReal,
         "FloatingComplexCast" => CastKind::FloatingComplexCast,
         "FloatingComplexToIntegralComplex" => CastKind::FloatingComplexToIntegralComplex,
+        "FloatingComplexToBoolean" => CastKind::FloatingComplexToBoolean,
         "IntegralRealToComplex" => CastKind::IntegralRealToComplex,
         "IntegralComplexToReal" => CastKind::IntegralComplexToReal,
         "IntegralComplexToBoolean" => CastKind::IntegralComplexToBoolean,
@@ -844,6 +846,15 @@ impl ConversionContext {
                     .insert(CExprId(new_id), text.clone());
             }
 
+            for &(param, arg) in &node.macro_args {
+                let arg = self.visit_expr(arg);
+                self.typed_context
+                    .macro_args
+                    .entry(CExprId(new_id))
+                    .or_default()
+                    .push((param, arg));
+            }
+
             match node.tag {
                 // Statements
                 ASTEntryTag::TagBreakStmt if expected_ty & OTHER_STMT != 0 => {
@@ -2067,7 +2078,17 @@ impl ConversionContext {
 
                     let mac_object = match node.tag {
                         ASTEntryTag::TagMacroObjectDef => CDeclKind::MacroObject { name },
-                        ASTEntryTag::TagMacroFunctionDef => CDeclKind::MacroFunction { name },
+                        ASTEntryTag::TagMacroFunctionDef => {
+                            let params = from_value::<Vec<String>>(node.extras[1].clone())
+                                .expect("Function-like macros must have parameters");
+                            let is_variadic = from_value::<bool>(node.extras[2].clone())
+                                .expect("Expected macro variadic flag");
+                            CDeclKind::MacroFunction {
+                                name,
+                                params,
+                                is_variadic,
+                            }
+                        }
                         _ => unreachable!("Unexpected tag for macro"),
                     };
 
@@ -2084,7 +2105,15 @@ impl ConversionContext {
                     let name = from_value::<String>(node.extras[0].clone())
                         .expect("Macros must have a name");
 
-                    let mac_object = CDeclKind::MacroFunction { name };
+                    let params = from_value::<Vec<String>>(node.extras[1].clone())
+                        .expect("Function-like macros must have parameters");
+                    let is_variadic = from_value::<bool>(node.extras[2].clone())
+                        .expect("Expected macro variadic flag");
+                    let mac_object = CDeclKind::MacroFunction {
+                        name,
+                        params,
+                        is_variadic,
+                    };
                     self.add_decl(new_id, located(node, mac_object));
                     self.processed_nodes.insert(new_id, MACRO_DECL);
 
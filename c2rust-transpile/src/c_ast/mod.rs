@@ -80,6 +80,10 @@ pub struct TypedAstContext {
     // if any
     pub macro_expansion_text: HashMap<CExprId, String>,
 
+    // map expansions of function-like macros to their arguments, as pairs of
+    // the parameter index and the argument expression
+    pub macro_args: HashMap<CExprId, Vec<(usize, CExprId)>>,
+
     pub comments: Vec<Located<String>>,
 
     // The key is the typedef decl being squashed away,
@@ -181,6 +185,7 @@ impl TypedAstContext {
             macro_invocations: HashMap::new(),
             macro_expansions: HashMap::new(),
             macro_expansion_text: HashMap::new(),
+            macro_args: HashMap::new(),
             label_names: Default::default(),
 
             comments: Vec::new(),
@@ -721,6 +726,16 @@ impl TypedAstContext {
         }
         false
     }
+
+    /// Whether this translation unit defines `main`, rather than just declaring it.
+    pub fn has_main_definition(&self) -> bool {
+        self.c_main.map_or(false, |main_id| {
+            matches!(
+                self.index(main_id).kind,
+                CDeclKind::Function { body: Some(_), .. }
+            )
+        })
+    }
 }
 
 impl CommentContext {
@@ -970,6 +985,8 @@ pub enum CDeclKind {
 
     MacroFunction {
         name: String,
+        params: Vec<String>,
+        is_variadic: bool,
         // replacements: Vec<CExprId>,
     },
 
@@ -1206,6 +1223,7 @@ pub enum CastKind {
     FloatingComplexToReal,
     FloatingComplexCast,
     FloatingComplexToIntegralComplex,
+    FloatingComplexToBoolean,
     IntegralRealToComplex,
     IntegralComplexToReal,
     IntegralComplexToBoolean,
@@ -1778,6 +1796,10 @@ impl CTypeKind {
         matches!(self, Float | Double | LongDouble)
     }
 
+    pub fn is_complex(&self) -> bool {
+        matches!(self, Self::Complex { .. })
+    }
+
     pub fn as_underlying_decl(&self) -> Option<CDeclId> {
         use CTypeKind::*;
         match *self {
@@ -652,8 +652,17 @@ impl<W: Write> Printer<W> {
                 self.writer.write_fmt(format_args!("#define {} ", name))?;
             }
 
-            MacroFunction { name, .. } => {
-                self.writer.write_fmt(format_args!("#define {}() ", name))?;
+            MacroFunction {
+                name,
+                params,
+                is_variadic,
+            } => {
+                let mut params = params.join(", ");
+                if *is_variadic {
+                    params.push_str(if params.is_empty() { "..." } else { ", ..." });
+                }
+                self.writer
+                    .write_fmt(format_args!("#define {}({}) ", name, params))?;
             }
 
             &NonCanonicalDecl { canonical_decl } => {
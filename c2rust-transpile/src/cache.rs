@@ -0,0 +1,444 @@
+//! An incremental cache of translated files, enabled with `--cache`.
+//!
+//! Each output file gets an entry in `.c2rust-cache/` in the build directory, recording the key
+//! it was translated with, a hash of the `.rs` file that was written, and the pragmas and crates
+//! the translation needed.  The key hashes the preprocessed input (with comments and macro
+//! definitions, since both are translated), the settings the AST exporter runs with (its clang
+//! version, the compile commands, and the extra clang arguments), and the [`TranspilerConfig`]
+//! options that affect translation.  If the key matches and the `.rs` file is
+//! unchanged, the translation unit isn't parsed or translated again, but its pragmas and crates
+//! are still available for the build files.  The cache isn't used with `--reorganize-definitions`, which
+//! rewrites the `.rs` files after they are cached.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use failure::{format_err, Error};
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::ast_exporter;
+use crate::compile_cmds::CompileCmd;
+use crate::report::DeclReport;
+use crate::{CrateSet, ExternCrate, PragmaVec, TranspilerConfig};
+
+const CACHE_DIR: &str = ".c2rust-cache";
+
+/// Increment this whenever the key or the entry format changes.
+const CACHE_VERSION: u32 = 4;
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    output_hash: String,
+    pragmas: Vec<(String, Vec<String>)>,
+    crates: Vec<ExternCrate>,
+    is_binary: bool,
+    decls: Vec<DeclReport>,
+}
+
+/// What is remembered about a translation besides the output file itself.
+pub(crate) struct CachedTranslation {
+    pub pragmas: PragmaVec,
+    pub crates: CrateSet,
+    pub is_binary: bool,
+    /// The outcome of translating each declaration, for the `--report`
+    pub decls: Vec<DeclReport>,
+}
+
+pub(crate) enum Lookup {
+    /// The output file is up to date.
+    Hit(CachedTranslation),
+    /// The output file was written by an earlier run, but its input or configuration has changed
+    /// since.  The file can be overwritten, since it hasn't been edited.
+    Stale,
+    /// There is no entry for the output file, or the file was changed after it was written.
+    Miss,
+}
+
+pub(crate) struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(build_dir: &Path) -> Self {
+        Self {
+            dir: build_dir.join(CACHE_DIR),
+        }
+    }
+
+    fn entry_path(&self, output_path: &Path) -> PathBuf {
+        let name = hash_hex(output_path.to_string_lossy().as_bytes());
+        self.dir.join(name).with_extension("json")
+    }
+
+    fn read_entry(&self, output_path: &Path) -> Option<CacheEntry> {
+        let json = fs::read(self.entry_path(output_path)).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    pub fn lookup(&self, output_path: &Path, key: &str) -> Lookup {
+        let entry = match self.read_entry(output_path) {
+            Some(entry) => entry,
+            None => return Lookup::Miss,
+        };
+        let output = match fs::read(output_path) {
+            Ok(output) => output,
+            Err(_) => return Lookup::Miss,
+        };
+        if hash_hex(&output) != entry.output_hash {
+            return Lookup::Miss;
+        }
+        if entry.key != key {
+            return Lookup::Stale;
+        }
+        let pragmas = entry
+            .pragmas
+            .into_iter()
+            .map(|(key, vals)| (intern(key), vals.into_iter().map(intern).collect()))
+            .collect();
+        let crates = entry.crates.into_iter().collect();
+        Lookup::Hit(CachedTranslation {
+            pragmas,
+            crates,
+            is_binary: entry.is_binary,
+            decls: entry.decls,
+        })
+    }
+
+    pub fn store(
+        &self,
+        output_path: &Path,
+        key: String,
+        output: &str,
+        translation: &CachedTranslation,
+    ) -> Result<(), Error> {
+        let entry = CacheEntry {
+            key,
+            output_hash: hash_hex(output.as_bytes()),
+            pragmas: translation
+                .pragmas
+                .iter()
+                .map(|(key, vals)| {
+                    let vals = vals.iter().map(|val| val.to_string()).collect();
+                    (key.to_string(), vals)
+                })
+                .collect(),
+            crates: translation.crates.iter().copied().collect(),
+            is_binary: translation.is_binary,
+            decls: translation.decls.clone(),
+        };
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.entry_path(output_path), serde_json::to_vec(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// Compute the cache key for translating `cmds`, the configurations of one file, to `output_path`.
+/// `exe_crate` is the crate of the executable the file is linked into, if any.
+pub(crate) fn key(
+    tcfg: &TranspilerConfig,
+    cmds: &[CompileCmd],
+    output_path: &Path,
+    extra_clang_args: &[&str],
+    exe_crate: Option<&str>,
+) -> Result<String, Error> {
+    let input_path = cmds[0].abs_file();
+    let mut hasher = Sha256::new();
+    let mut header = String::new();
+    writeln!(header, "version: {}", CACHE_VERSION)?;
+    writeln!(header, "c2rust: {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(header, "input: {}", input_path.display())?;
+    writeln!(header, "output: {}", output_path.display())?;
+    writeln!(header, "binary: {}", tcfg.is_binary(&input_path))?;
+    writeln!(header, "executable: {:?}", exe_crate)?;
+    writeln!(header, "config: {}", config_fingerprint(tcfg))?;
+    writeln!(
+        header,
+        "exporter: {}",
+        exporter_fingerprint(cmds, extra_clang_args)
+    )?;
+    hasher.update(header.as_bytes());
+    for cmd in cmds {
+        hasher.update(preprocess(cmd)?);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The options that can change the translation of a file, in a stable format.
+fn config_fingerprint(tcfg: &TranspilerConfig) -> String {
+    // Destructure exhaustively, so that new options have to be considered here.
+    let TranspilerConfig {
+        dump_untyped_context: _,
+        dump_typed_context: _,
+        pretty_typed_context: _,
+        dump_function_cfgs: _,
+        json_function_cfgs: _,
+        dump_cfg_liveness: _,
+        dump_structures: _,
+        verbose: _,
+        debug_ast_exporter: _,
+        incremental_relooper,
+        fail_on_multiple,
+        filter: _,
+        debug_relooper_labels,
+        prefix_function_names,
+        translate_asm,
+        use_c_loop_info,
+        use_c_multiple_info,
+        simplify_structures,
+        panic_on_translator_failure,
+        emit_modules,
+        fail_on_error,
+        replace_unsupported_decls,
+        translate_valist,
+        long_double,
+        overwrite_existing: _,
+        cache: _,
+        reduce_type_annotations,
+        reorganize_definitions,
+        enabled_warnings: _,
+        emit_no_std,
+        output_dir: _,
+        report: _,
+        translate_const_macros,
+        translate_fn_macros,
+        disable_refactoring: _,
+        preserve_unused_functions,
+        log_level: _,
+        jobs: _,
+        emit_build_files,
+        binaries: _,
+        overrides,
+    } = tcfg;
+    format!(
+        "{:?}",
+        (
+            (
+                incremental_relooper,
+                fail_on_multiple,
+                debug_relooper_labels,
+                prefix_function_names,
+                translate_asm,
+                use_c_loop_info,
+                use_c_multiple_info,
+                simplify_structures,
+            ),
+            (
+                panic_on_translator_failure,
+                emit_modules,
+                fail_on_error,
+                replace_unsupported_decls,
+                translate_valist,
+                reduce_type_annotations,
+                reorganize_definitions,
+                emit_no_std,
+            ),
+            (
+                translate_const_macros,
+                translate_fn_macros,
+                preserve_unused_functions,
+                emit_build_files,
+                long_double,
+            ),
+            overrides,
+        )
+    )
+}
+
+/// The settings the AST exporter parses `cmds` with.  The preprocessed input doesn't capture all
+/// of these, since the target and language options also change how clang types the AST.
+fn exporter_fingerprint(cmds: &[CompileCmd], extra_clang_args: &[&str]) -> String {
+    let cmds = cmds
+        .iter()
+        .map(|cmd| (cmd.directory(), cmd.args()))
+        .collect::<Vec<_>>();
+    format!(
+        "{:?}",
+        (ast_exporter::get_clang_version(), cmds, extra_clang_args)
+    )
+}
+
+/// Run the compiler from `cmd` with `-E` to get its preprocessed input.  Comments (`-C`) and macro
+/// definitions (`-dD`) are kept, since they can be translated as well.
+fn preprocess(cmd: &CompileCmd) -> Result<Vec<u8>, Error> {
+    let args = cmd
+        .args()
+        .ok_or_else(|| format_err!("no compile command for {}", cmd.file.display()))?;
+    let (compiler, args) = args
+        .split_first()
+        .ok_or_else(|| format_err!("empty compile command for {}", cmd.file.display()))?;
+
+    // Drop the options that select an output or write dependency files.
+    let mut pp_args = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "-M" | "-MM" | "-MD" | "-MMD" | "-MP" => {}
+            "-o" | "-MF" | "-MT" | "-MQ" => {
+                args.next();
+            }
+            _ => pp_args.push(arg.as_str()),
+        }
+    }
+
+    let output = Command::new(compiler)
+        .args(pp_args)
+        .args(["-E", "-C", "-dD", "-o", "-"])
+        .current_dir(cmd.directory())
+        .output()?;
+    if !output.status.success() {
+        return Err(format_err!(
+            "`{} -E` failed for {}: {}",
+            compiler,
+            cmd.file.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+fn hash_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Pragmas are `&'static str`s, since they normally come from string literals in the translator.
+/// The ones read back from the cache are leaked, but only once each.
+fn intern(s: String) -> &'static str {
+    static INTERNED: Mutex<Option<HashSet<&'static str>>> = Mutex::new(None);
+    let mut interned = INTERNED.lock().unwrap();
+    let interned = interned.get_or_insert_with(HashSet::new);
+    match interned.get(s.as_str()) {
+        Some(s) => s,
+        None => {
+            let s = Box::leak(s.into_boxed_str());
+            interned.insert(s);
+            s
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation() -> CachedTranslation {
+        CachedTranslation {
+            pragmas: vec![("feature", vec!["register_tool"])],
+            crates: [ExternCrate::Libc].into_iter().collect(),
+            is_binary: false,
+            decls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path());
+        let output_path = dir.path().join("foo.rs");
+        assert!(matches!(cache.lookup(&output_path, "key"), Lookup::Miss));
+
+        fs::write(&output_path, "fn foo() {}").unwrap();
+        cache
+            .store(&output_path, "key".into(), "fn foo() {}", &translation())
+            .unwrap();
+        match cache.lookup(&output_path, "key") {
+            Lookup::Hit(cached) => {
+                assert_eq!(cached.pragmas, translation().pragmas);
+                assert_eq!(cached.crates, translation().crates);
+                assert!(!cached.is_binary);
+            }
+            _ => panic!("expected a cache hit"),
+        }
+        assert!(matches!(
+            cache.lookup(&output_path, "other key"),
+            Lookup::Stale
+        ));
+
+        // An edited output file mustn't be overwritten.
+        fs::write(&output_path, "fn foo() { edited(); }").unwrap();
+        assert!(matches!(cache.lookup(&output_path, "key"), Lookup::Miss));
+    }
+
+    #[test]
+    fn preprocess_keeps_macros() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("foo.c"), "#define FOO 1\nint x = FOO;\n").unwrap();
+        let args = ["cc", "-c", "foo.c", "-o", "foo.o", "-DBAR=2"];
+        let cmd = CompileCmd::new(
+            dir.path().to_owned(),
+            "foo.c".into(),
+            args.iter().map(|&arg| arg.to_owned()).collect(),
+        );
+        let out = String::from_utf8(preprocess(&cmd).unwrap()).unwrap();
+        assert!(out.contains("#define FOO 1"));
+        assert!(out.contains("#define BAR 2"));
+        assert!(out.contains("int x = 1;"));
+        assert!(!dir.path().join("foo.o").exists());
+    }
+
+    #[test]
+    fn key() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("foo.c"), "int x = 1;\n").unwrap();
+        let cmd = CompileCmd::new(
+            dir.path().to_owned(),
+            dir.path().join("foo.c"),
+            vec!["cc".to_owned(), "-c".to_owned(), "foo.c".to_owned()],
+        );
+        let output_path = dir.path().join("foo.rs");
+        let key = |tcfg: &TranspilerConfig| {
+            super::key(tcfg, &[cmd.clone()], &output_path, &[], None).unwrap()
+        };
+
+        let tcfg = TranspilerConfig::default();
+        let base = key(&tcfg);
+        assert_eq!(base, key(&tcfg));
+
+        // Options that don't change the translation don't change the key.
+        let tcfg = TranspilerConfig {
+            jobs: 4,
+            overwrite_existing: true,
+            ..Default::default()
+        };
+        assert_eq!(base, key(&tcfg));
+
+        let tcfg = TranspilerConfig {
+            translate_const_macros: true,
+            ..Default::default()
+        };
+        assert_ne!(base, key(&tcfg));
+
+        fs::write(dir.path().join("foo.c"), "int x = 2;\n").unwrap();
+        assert_ne!(base, key(&TranspilerConfig::default()));
+    }
+
+    #[test]
+    fn exporter_settings_change_key() {
+        let cmd = |args: &[&str]| {
+            CompileCmd::new(
+                "/src".into(),
+                "foo.c".into(),
+                args.iter().map(|&arg| arg.to_owned()).collect(),
+            )
+        };
+        let base = exporter_fingerprint(&[cmd(&["cc", "-c", "foo.c"])], &[]);
+        assert_eq!(
+            base,
+            exporter_fingerprint(&[cmd(&["cc", "-c", "foo.c"])], &[])
+        );
+        assert!(base.contains(ast_exporter::get_clang_version()));
+        assert_ne!(
+            base,
+            exporter_fingerprint(&[cmd(&["cc", "-m32", "-c", "foo.c"])], &[])
+        );
+        assert_ne!(
+            base,
+            exporter_fingerprint(&[cmd(&["cc", "-c", "foo.c"])], &["-std=c99"])
+        );
+    }
+}
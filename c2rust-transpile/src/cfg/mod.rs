@@ -1431,8 +1431,21 @@ impl CfgBuilder {
                 true_variant,
                 false_variant,
             } => {
+                // `if (setjmp(env))` runs the branch for `setjmp` returning `0` first, and then
+                // branches to the other one if it was left by a `longjmp`.
+                let setjmp =
+                    translator.convert_setjmp_if(ctx, scrutinee, true_variant, false_variant)?;
+                let (true_variant, false_variant) = match setjmp {
+                    Some(ref setjmp) => (setjmp.handler, None),
+                    None => (Some(true_variant), false_variant),
+                };
+
                 let next_entry = self.fresh_label();
-                let then_entry = self.fresh_label();
+                let then_entry = if true_variant.is_none() {
+                    next_entry.clone()
+                } else {
+                    self.fresh_label()
+                };
                 let else_entry = if false_variant.is_none() {
                     next_entry.clone()
                 } else {
@@ -1440,12 +1453,16 @@ impl CfgBuilder {
                 };
 
                 // Condition
-                let (stmts, val) = translator
-                    .convert_condition(ctx, true, scrutinee)?
-                    .discard_unsafe();
+                let (cond, cond_val) = match setjmp {
+                    Some(setjmp) => (setjmp.jumped, None),
+                    None => (
+                        translator.convert_condition(ctx, true, scrutinee)?,
+                        translator.ast_context[scrutinee].kind.get_bool(),
+                    ),
+                };
+                let (stmts, val) = cond.discard_unsafe();
                 wip.extend(stmts);
 
-                let cond_val = translator.ast_context[scrutinee].kind.get_bool();
                 self.add_wip_block(
                     wip,
                     match cond_val {
@@ -1457,16 +1474,18 @@ impl CfgBuilder {
 
                 // Then case
                 self.open_arm(then_entry.clone());
-                let then_stuff = self.convert_stmt_help(
-                    translator,
-                    ctx,
-                    true_variant,
-                    in_tail.clone(),
-                    then_entry,
-                )?;
-                if let Some(then_end) = then_stuff {
-                    let wip_then = self.new_wip_block(then_end);
-                    self.add_wip_block(wip_then, Jump(next_entry.clone()));
+                if let Some(true_var) = true_variant {
+                    let then_stuff = self.convert_stmt_help(
+                        translator,
+                        ctx,
+                        true_var,
+                        in_tail.clone(),
+                        then_entry,
+                    )?;
+                    if let Some(then_end) = then_stuff {
+                        let wip_then = self.new_wip_block(then_end);
+                        self.add_wip_block(wip_then, Jump(next_entry.clone()));
+                    }
                 }
                 let then_arm = self.close_arm();
 
@@ -1,14 +1,14 @@
-use std::collections::{HashMap, HashSet};
-use std::fs::File;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use failure::Error;
 use log::warn;
 use regex::Regex;
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug, Default, Clone)]
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
 pub struct CompileCmd {
     /// The working directory of the compilation. All paths specified in the command
     /// or file fields must be either absolute or relative to this directory.
@@ -22,17 +22,28 @@ pub struct CompileCmd {
     /// to rerun the exact compilation step for the translation unit in the environment
     /// the build system uses. Parameters use shell quoting and shell escaping of quotes,
     /// with ‘"’ and ‘\’ being the only special characters. Shell expansion is not supported.
-    #[serde(skip_deserializing)]
-    _command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
     /// The compile command executed as list of strings. Either arguments or command is required.
-    #[serde(default, skip_deserializing)]
-    _arguments: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    arguments: Vec<String>,
     /// The name of the output created by this compilation step. This field is optional. It can
     /// be used to distinguish different processing modes of the same input file.
     output: Option<String>,
 }
 
 impl CompileCmd {
+    /// A command compiling `file` in `directory`, where `arguments` starts with the compiler.
+    pub fn new(directory: PathBuf, file: PathBuf, arguments: Vec<String>) -> Self {
+        Self {
+            directory,
+            file,
+            command: None,
+            arguments,
+            output: None,
+        }
+    }
+
     pub fn abs_file(&self) -> PathBuf {
         match self.file.is_absolute() {
             true => self.file.clone(),
@@ -43,6 +54,46 @@ impl CompileCmd {
             }
         }
     }
+
+    /// The working directory of the compilation.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// The macros defined by `-D` (and not undefined by a later `-U`) flags, and their values.
+    pub fn defines(&self) -> BTreeMap<String, String> {
+        let mut defines = BTreeMap::new();
+        let args = self.args().unwrap_or_default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (flag, rest) = match arg.get(..2) {
+                Some(flag @ ("-D" | "-U")) => (flag, &arg[2..]),
+                _ => continue,
+            };
+            let def = match rest {
+                "" => match args.next() {
+                    Some(def) => def.as_str(),
+                    None => continue,
+                },
+                rest => rest,
+            };
+            let (name, value) = def.split_once('=').unwrap_or((def, "1"));
+            if flag == "-D" {
+                defines.insert(name.to_owned(), value.to_owned());
+            } else {
+                defines.remove(name);
+            }
+        }
+        defines
+    }
+
+    /// The compiler followed by its arguments, either from `arguments` or by splitting `command`.
+    pub fn args(&self) -> Option<Vec<String>> {
+        if !self.arguments.is_empty() {
+            return Some(self.arguments.clone());
+        }
+        self.command.as_deref().and_then(shlex::split)
+    }
 }
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
@@ -110,6 +161,12 @@ fn build_link_commands(mut v: Vec<Rc<CompileCmd>>) -> Result<Vec<LinkCmd>, Error
         let mut lcmd: LinkCmd = serde_bencode::from_str(lcmd)?;
 
         lcmd.output = ccmd.output.clone();
+        // `-L` directories are relative to the directory the linker ran in
+        lcmd.lib_dirs = lcmd
+            .lib_dirs
+            .iter()
+            .map(|dir| ccmd.directory.join(dir))
+            .collect();
         for inp in &lcmd.inputs {
             if let Some(ccmd_idx) = output_map.get(&inp) {
                 let inp_ccmd = Rc::clone(&v[*ccmd_idx]);
@@ -122,8 +179,6 @@ fn build_link_commands(mut v: Vec<Rc<CompileCmd>>) -> Result<Vec<LinkCmd>, Error
         seen_ccmds.insert(idx);
     }
 
-    // TODO: add binaries
-
     // Check if we have left-over compile commands; if we do,
     // bind them to the crate itself (which becomes a `staticlib` or `rlib`)
     let mut idx = 0;
@@ -149,25 +204,37 @@ fn build_link_commands(mut v: Vec<Rc<CompileCmd>>) -> Result<Vec<LinkCmd>, Error
 }
 
 /// some build scripts repeatedly compile the same input file with different
-/// command line flags thus creating multiple outputs. We remove any duplicates
-/// in the order we see them and warn the user.
+/// command line flags thus creating multiple outputs. Compilations with
+/// different `-D` flags are kept, so that each configuration can be translated
+/// and merged under `#[cfg]`s. We remove any other duplicates in the order we
+/// see them and warn the user.
 fn filter_duplicate_cmds(v: Vec<Rc<CompileCmd>>) -> Vec<Rc<CompileCmd>> {
-    let mut seen = HashSet::new();
+    let mut seen = HashMap::<_, Vec<_>>::new();
     let mut cmds = vec![];
 
     for cmd in v {
         let absf = cmd.abs_file();
-        if seen.contains(&absf) {
+        let defines = cmd.defines();
+        let seen_defines = seen.entry(absf.clone()).or_default();
+        if seen_defines.contains(&defines) {
             warn!("Skipping duplicate compilation cmd for {}", absf.display());
             continue;
         }
-        seen.insert(absf);
+        seen_defines.push(defines);
         cmds.push(cmd)
     }
 
     cmds
 }
 
+/// Write a compilation database containing only `cmd` to `dir`, so that the AST exporter only sees
+/// that command, and return its path.
+pub fn write_compile_commands(cmd: &CompileCmd, dir: &Path) -> Result<PathBuf, Error> {
+    let path = dir.join("compile_commands.json");
+    fs::write(&path, serde_json::to_vec_pretty(&[cmd])?)?;
+    Ok(path)
+}
+
 /// Read `compile_commands` file and optionally ignore any entries not matching `filter`.
 pub fn get_compile_commands(
     compile_commands: &Path,
@@ -197,3 +264,72 @@ pub fn get_compile_commands(
 
     Ok(lcmds)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(file: &str, args: &[&str]) -> CompileCmd {
+        let args = std::iter::once("cc")
+            .chain(args.iter().copied())
+            .chain(["-c", file])
+            .map(str::to_owned)
+            .collect();
+        CompileCmd::new("/src".into(), file.into(), args)
+    }
+
+    fn defines(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn test_defines() {
+        let cmd = cmd(
+            "/src/a.c",
+            &["-DA", "-D", "B", "-DLEVEL=2", "-O2", "-DC=x=y", "-UB"],
+        );
+        assert_eq!(
+            cmd.defines(),
+            defines(&[("A", "1"), ("C", "x=y"), ("LEVEL", "2")])
+        );
+    }
+
+    #[test]
+    fn test_defines_command() {
+        let cmd = CompileCmd {
+            directory: "/src".into(),
+            file: "a.c".into(),
+            command: Some(r#"cc -DNAME="a b" -DX -UX -c a.c"#.to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(cmd.defines(), defines(&[("NAME", "a b")]));
+    }
+
+    #[test]
+    fn test_filter_duplicate_cmds() {
+        let cmds = vec![
+            cmd("/src/a.c", &["-DA"]),
+            cmd("/src/a.c", &["-DA", "-O2"]),
+            cmd("/src/a.c", &["-DB"]),
+            cmd("/src/b.c", &["-DA"]),
+            cmd("/src/a.c", &["-D", "B=1"]),
+        ];
+        let cmds = filter_duplicate_cmds(cmds.into_iter().map(Rc::new).collect());
+        let kept = cmds
+            .iter()
+            .map(|cmd| (cmd.file.to_str().unwrap(), cmd.args().unwrap()[1].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kept,
+            [
+                ("/src/a.c", "-DA"),
+                ("/src/a.c", "-DB"),
+                ("/src/b.c", "-DA")
+            ]
+            .map(|(file, arg)| (file, arg.to_owned()))
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Config files that set [`TranspilerConfig`] options, so that a translation can be reproduced
+//! and checked in without a long command line.
+//!
+//! A config file is TOML, and its options are named like the fields of [`TranspilerConfig`]:
+//!
+//! ```toml
+//! emit_build_files = true
+//! output_dir = "rust"
+//! translate_const_macros = true
+//! clang_args = ["-DNDEBUG"]
+//!
+//! [[override]]
+//! paths = ["include/macros.h", "src/parser"]
+//! translate_fn_macros = true
+//! ```
+//!
+//! Relative paths are relative to the directory of the config file. Each `[[override]]` sets
+//! translation options for the source files and headers under its `paths`, and later overrides
+//! take precedence over earlier ones. Options given on the command line take precedence over the
+//! config file, and flags set in the config file can be turned off with their `--no-` forms.
+
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use failure::{format_err, Error};
+use regex::Regex;
+use serde::Deserializer;
+use serde_derive::Deserialize;
+
+use crate::{Diagnostic, LongDouble, TranspilerConfig};
+
+/// The config file that is used if none is given, in the directory of `compile_commands.json`.
+pub const DEFAULT_CONFIG_FILE: &str = "c2rust.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub emit_modules: Option<bool>,
+    pub emit_build_files: Option<bool>,
+    pub binaries: Option<Vec<String>>,
+    pub output_dir: Option<PathBuf>,
+    pub report: Option<PathBuf>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub filter: Option<Regex>,
+    pub overwrite_existing: Option<bool>,
+    pub cache: Option<bool>,
+    pub jobs: Option<usize>,
+    pub fail_on_error: Option<bool>,
+    pub fail_on_multiple: Option<bool>,
+    pub panic_on_translator_failure: Option<bool>,
+    pub incremental_relooper: Option<bool>,
+    pub use_c_loop_info: Option<bool>,
+    pub use_c_multiple_info: Option<bool>,
+    pub simplify_structures: Option<bool>,
+    pub debug_relooper_labels: Option<bool>,
+    pub prefix_function_names: Option<String>,
+    pub translate_const_macros: Option<bool>,
+    pub translate_fn_macros: Option<bool>,
+    pub preserve_unused_functions: Option<bool>,
+    pub reduce_type_annotations: Option<bool>,
+    pub reorganize_definitions: Option<bool>,
+    pub disable_refactoring: Option<bool>,
+    pub emit_no_std: Option<bool>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub long_double: Option<LongDouble>,
+    pub enabled_warnings: Option<Vec<Diagnostic>>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub log_level: Option<log::LevelFilter>,
+    /// Extra arguments to pass to clang, before the ones given on the command line.
+    #[serde(default)]
+    pub clang_args: Vec<String>,
+    #[serde(default, rename = "override")]
+    pub overrides: Vec<Override>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format_err!("Could not read {}: {}", path.display(), e))?;
+        let mut config: Self = toml::from_str(&text)
+            .map_err(|e| format_err!("Could not parse {}: {}", path.display(), e))?;
+
+        let dir = path.canonicalize()?;
+        let dir = dir.parent().unwrap();
+        for path in [&mut config.output_dir, &mut config.report]
+            .into_iter()
+            .flatten()
+        {
+            *path = dir.join(&*path);
+        }
+        for path in config.overrides.iter_mut().flat_map(|o| &mut o.paths) {
+            // Inputs are canonicalized, so overrides have to be too to match them.
+            let joined = dir.join(&*path);
+            *path = joined.canonicalize().unwrap_or(joined);
+        }
+        Ok(config)
+    }
+}
+
+/// Translation options for the source files and headers under some paths. Options that aren't
+/// set keep the value they have for the whole translation.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    /// Files, or directories containing them.
+    pub paths: Vec<PathBuf>,
+    pub prefix_function_names: Option<String>,
+    pub translate_const_macros: Option<bool>,
+    pub translate_fn_macros: Option<bool>,
+    pub preserve_unused_functions: Option<bool>,
+    pub reduce_type_annotations: Option<bool>,
+    pub incremental_relooper: Option<bool>,
+    pub use_c_loop_info: Option<bool>,
+    pub use_c_multiple_info: Option<bool>,
+    pub simplify_structures: Option<bool>,
+    pub fail_on_multiple: Option<bool>,
+    pub panic_on_translator_failure: Option<bool>,
+}
+
+impl Override {
+    pub(crate) fn applies_to(&self, path: &Path) -> bool {
+        self.paths.iter().any(|p| path.starts_with(p))
+    }
+
+    /// Set the options of `tcfg` that this overrides.
+    pub(crate) fn apply(&self, tcfg: &mut TranspilerConfig) {
+        // Destructure exhaustively, so that new options have to be applied here.
+        let Override {
+            paths: _,
+            prefix_function_names,
+            translate_const_macros,
+            translate_fn_macros,
+            preserve_unused_functions,
+            reduce_type_annotations,
+            incremental_relooper,
+            use_c_loop_info,
+            use_c_multiple_info,
+            simplify_structures,
+            fail_on_multiple,
+            panic_on_translator_failure,
+        } = self;
+        if let Some(prefix) = prefix_function_names {
+            tcfg.prefix_function_names = Some(prefix.clone());
+        }
+        let flags = [
+            (translate_const_macros, &mut tcfg.translate_const_macros),
+            (translate_fn_macros, &mut tcfg.translate_fn_macros),
+            (
+                preserve_unused_functions,
+                &mut tcfg.preserve_unused_functions,
+            ),
+            (reduce_type_annotations, &mut tcfg.reduce_type_annotations),
+            (incremental_relooper, &mut tcfg.incremental_relooper),
+            (use_c_loop_info, &mut tcfg.use_c_loop_info),
+            (use_c_multiple_info, &mut tcfg.use_c_multiple_info),
+            (simplify_structures, &mut tcfg.simplify_structures),
+            (fail_on_multiple, &mut tcfg.fail_on_multiple),
+            (
+                panic_on_translator_failure,
+                &mut tcfg.panic_on_translator_failure,
+            ),
+        ];
+        for (value, flag) in flags {
+            if let Some(value) = *value {
+                *flag = value;
+            }
+        }
+    }
+}
+
+/// Deserialize an option from a string, like it is given on the command line.
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+    s.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(text: &str) -> (tempfile::TempDir, Result<ConfigFile, Error>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DEFAULT_CONFIG_FILE);
+        fs::write(&path, text).unwrap();
+        let config = ConfigFile::load(&path);
+        (dir, config)
+    }
+
+    #[test]
+    fn load_config() {
+        let (dir, config) = load(
+            r#"
+            emit_build_files = true
+            output_dir = "rust"
+            jobs = 4
+            long_double = "x87"
+            log_level = "debug"
+            filter = "^foo_"
+            clang_args = ["-DNDEBUG"]
+
+            [[override]]
+            paths = ["src/parser"]
+            translate_fn_macros = true
+            "#,
+        );
+        let config = config.unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        assert_eq!(config.emit_build_files, Some(true));
+        assert_eq!(config.output_dir, Some(dir.join("rust")));
+        assert_eq!(config.jobs, Some(4));
+        assert_eq!(config.long_double, Some(LongDouble::X87));
+        assert_eq!(config.log_level, Some(log::LevelFilter::Debug));
+        assert!(config.filter.unwrap().is_match("foo_bar"));
+        assert_eq!(config.clang_args, ["-DNDEBUG"]);
+        assert_eq!(config.cache, None);
+        assert_eq!(config.overrides.len(), 1);
+        assert_eq!(config.overrides[0].paths, [dir.join("src/parser")]);
+        assert_eq!(config.overrides[0].translate_fn_macros, Some(true));
+    }
+
+    #[test]
+    fn load_config_errors() {
+        let (_dir, config) = load("emit_build_file = true");
+        assert!(config.is_err());
+        let (_dir, config) = load(r#"long_double = "f16""#);
+        assert!(config.is_err());
+        let (_dir, config) = load("[[override]]\ntranslate_fn_macros = true");
+        assert!(config.is_err(), "an override needs paths");
+    }
+
+    #[test]
+    fn overrides() {
+        let (dir, config) = load(
+            r#"
+            [[override]]
+            paths = ["src"]
+            translate_const_macros = true
+            prefix_function_names = "src_"
+
+            [[override]]
+            paths = ["src/parser.c", "include"]
+            translate_const_macros = false
+            translate_fn_macros = true
+            "#,
+        );
+        let dir = dir.path().canonicalize().unwrap();
+        let tcfg = TranspilerConfig {
+            overrides: config.unwrap().overrides,
+            ..Default::default()
+        };
+
+        let other = tcfg.for_file(&dir.join("other.c"));
+        assert!(!other.translate_const_macros);
+        assert_eq!(other.prefix_function_names, None);
+
+        let lexer = tcfg.for_file(&dir.join("src/lexer.c"));
+        assert!(lexer.translate_const_macros);
+        assert!(!lexer.translate_fn_macros);
+        assert_eq!(lexer.prefix_function_names.as_deref(), Some("src_"));
+
+        // The later override takes precedence, and options it doesn't set are kept.
+        let parser = tcfg.for_file(&dir.join("src/parser.c"));
+        assert!(!parser.translate_const_macros);
+        assert!(parser.translate_fn_macros);
+        assert_eq!(parser.prefix_function_names.as_deref(), Some("src_"));
+
+        // Macros in headers are translated according to the overrides for the header.
+        let header = dir.join("include/macros.h");
+        assert!(lexer.translate_fn_macros_in(Some(&header)));
+        assert!(!lexer.translate_fn_macros_in(None));
+    }
+}
@@ -4,8 +4,10 @@ use crate::diagnostics::TranslationResult;
 use crate::renamer::*;
 use c2rust_ast_builder::{mk, properties::*};
 use failure::format_err;
+use log::warn;
 use std::collections::{HashMap, HashSet};
 use std::ops::Index;
+use strum_macros::{Display, EnumString};
 use syn::*;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
@@ -14,8 +16,48 @@ enum FieldKey {
     Padding(usize),
 }
 
+/// What `long double` is translated to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Display, EnumString)]
+#[strum(serialize_all = "kebab_case")]
+pub enum LongDouble {
+    /// `f128::f128`, a software 128-bit float
+    F128,
+    /// `c2rust_x87::f80`, the x87 80-bit float that is `long double` on x86 and x86_64
+    X87,
+    /// `f64`, which loses precision on targets where `long double` is wider
+    F64,
+    /// Whichever of the others matches `long double` on the target triple
+    Auto,
+}
+
+impl LongDouble {
+    /// Resolve [`LongDouble::Auto`] for the target triple `target`.
+    pub fn for_target(self, target: &str) -> Self {
+        if self != LongDouble::Auto {
+            return self;
+        }
+        let arch = target.split('-').next().unwrap_or_default();
+        if target.contains("msvc") {
+            // MSVC's `long double` is the same as `double`, even on x86.
+            LongDouble::F64
+        } else if arch == "x86_64" || (arch.starts_with('i') && arch.ends_with("86")) {
+            LongDouble::X87
+        } else if arch.starts_with("arm") && !arch.starts_with("arm64")
+            || arch.starts_with("thumb")
+            || target.contains("apple")
+        {
+            LongDouble::F64
+        } else {
+            LongDouble::F128
+        }
+    }
+}
+
 pub struct TypeConverter {
     pub translate_valist: bool,
+    pub long_double: LongDouble,
+    /// Warn the first time `long double` is translated to `f64`, which loses precision
+    pub warn_long_double_precision: bool,
     renamer: Renamer<CDeclId>,
     fields: HashMap<CDeclId, Renamer<FieldKey>>,
     suffix_names: HashMap<(CDeclId, &'static str), String>,
@@ -142,6 +184,8 @@ impl TypeConverter {
     pub fn new() -> TypeConverter {
         TypeConverter {
             translate_valist: false,
+            long_double: LongDouble::F128,
+            warn_long_double_precision: false,
             renamer: Renamer::new(&RESERVED_NAMES),
             fields: HashMap::new(),
             suffix_names: HashMap::new(),
@@ -149,6 +193,21 @@ impl TypeConverter {
         }
     }
 
+    fn convert_long_double(&mut self) -> Box<Type> {
+        let path = match self.long_double {
+            LongDouble::X87 => vec!["c2rust_x87", "f80"],
+            LongDouble::F64 => {
+                if self.warn_long_double_precision {
+                    self.warn_long_double_precision = false;
+                    warn!("long double is wider than f64 on this target, so it loses precision");
+                }
+                vec!["libc", "c_double"]
+            }
+            LongDouble::F128 | LongDouble::Auto => vec!["f128", "f128"],
+        };
+        mk().path_ty(mk().path(path))
+    }
+
     pub fn features_used(&self) -> &HashSet<&'static str> {
         &self.features
     }
@@ -325,7 +384,7 @@ impl TypeConverter {
             CTypeKind::UChar => Ok(mk().path_ty(mk().path(vec!["libc", "c_uchar"]))),
             CTypeKind::Char => Ok(mk().path_ty(mk().path(vec!["libc", "c_char"]))),
             CTypeKind::Double => Ok(mk().path_ty(mk().path(vec!["libc", "c_double"]))),
-            CTypeKind::LongDouble => Ok(mk().path_ty(mk().path(vec!["f128", "f128"]))),
+            CTypeKind::LongDouble => Ok(self.convert_long_double()),
             CTypeKind::Float => Ok(mk().path_ty(mk().path(vec!["libc", "c_float"]))),
             CTypeKind::Int128 => Ok(mk().path_ty(mk().path(vec!["i128"]))),
             CTypeKind::UInt128 => Ok(mk().path_ty(mk().path(vec!["u128"]))),
@@ -397,6 +456,16 @@ impl TypeConverter {
 
             CTypeKind::TypeOf(ty) => self.convert(ctxt, ty),
 
+            // `num_complex::Complex` is `#[repr(C)]` and laid out like C's `_Complex`
+            CTypeKind::Complex(element) => {
+                let ty = self.convert(ctxt, element)?;
+                let param = mk().angle_bracketed_args(vec![ty]);
+                Ok(mk().path_ty(vec![
+                    mk().path_segment("num_complex"),
+                    mk().path_segment_with_args("Complex", param),
+                ]))
+            }
+
             ref t => Err(format_err!("Unsupported type {:?}", t).into()),
         }
     }
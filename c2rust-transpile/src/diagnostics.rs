@@ -2,9 +2,10 @@ use colored::Colorize;
 use failure::{err_msg, Backtrace, Context, Error, Fail};
 use fern::colors::ColoredLevelConfig;
 use log::{Level, SetLoggerError};
+use serde_derive::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::collections::HashSet;
 use std::fmt::{self, Display};
-use std::io;
 use std::str::FromStr;
 use std::sync::Arc;
 use strum_macros::{Display, EnumString};
@@ -14,8 +15,9 @@ use c2rust_ast_exporter::get_clang_major_version;
 
 const DEFAULT_WARNINGS: &[Diagnostic] = &[Diagnostic::ClangAst];
 
-#[derive(PartialEq, Eq, Hash, Debug, Display, EnumString, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Display, EnumString, Clone, Deserialize)]
 #[strum(serialize_all = "kebab_case")]
+#[serde(rename_all = "kebab-case")]
 pub enum Diagnostic {
     All,
     Comments,
@@ -62,7 +64,9 @@ pub fn init(mut enabled_warnings: HashSet<Diagnostic>, log_level: log::LevelFilt
                 .map(|d| enabled_warnings.contains(&d))
                 .unwrap_or(true)
         })
-        .chain(io::stderr())
+        .chain(fern::Output::call(|record| {
+            emit(Stream::Stderr, format!("{}\n", record.args()))
+        }))
         .into_log();
     // Ignore the [`SetLoggerError`] b/c we just want to make sure it's set at least once.
     let _: Result<(), SetLoggerError> = log_reroute::init();
@@ -70,6 +74,98 @@ pub fn init(mut enabled_warnings: HashSet<Diagnostic>, log_level: log::LevelFilt
     log::set_max_level(max_level);
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Output written by one thread while [`capture_output`] was running, to be [`replay`]ed later.
+///
+/// [`replay`]: CapturedOutput::replay
+#[derive(Debug, Default)]
+pub(crate) struct CapturedOutput {
+    chunks: Vec<(Stream, String)>,
+}
+
+impl CapturedOutput {
+    /// Write the captured output to stdout and stderr (or to the current [`capture_output`]
+    /// buffer, if there is one), in the order it was produced.
+    pub(crate) fn replay(self) {
+        for (stream, s) in self.chunks {
+            emit(stream, s);
+        }
+    }
+
+    /// The output captured from `stream`.
+    #[cfg(test)]
+    pub(crate) fn text(&self, stream: Stream) -> String {
+        self.chunks
+            .iter()
+            .filter(|&&(s, _)| s == stream)
+            .map(|(_, s)| s.as_str())
+            .collect()
+    }
+}
+
+thread_local! {
+    static CAPTURED: RefCell<Option<CapturedOutput>> = RefCell::new(None);
+}
+
+/// Run `f`, capturing the log messages and [`outln!`] and [`errln!`] output it produces on this
+/// thread instead of writing them out.  This lets translation units be transpiled in parallel
+/// while still printing their output in a deterministic order.
+pub(crate) fn capture_output<T>(f: impl FnOnce() -> T) -> (T, CapturedOutput) {
+    let prev = CAPTURED.with(|c| c.replace(Some(CapturedOutput::default())));
+    let result = f();
+    let captured = CAPTURED.with(|c| c.replace(prev)).unwrap_or_default();
+    (result, captured)
+}
+
+/// Write `s` to `stream`, or to the current [`capture_output`] buffer if there is one.
+pub(crate) fn emit(stream: Stream, s: String) {
+    let s = CAPTURED.with(|c| match &mut *c.borrow_mut() {
+        Some(captured) => {
+            captured.chunks.push((stream, s));
+            None
+        }
+        None => Some(s),
+    });
+    if let Some(s) = s {
+        write_to(stream, &s);
+    }
+}
+
+fn write_to(stream: Stream, s: &str) {
+    match stream {
+        Stream::Stdout => print!("{}", s),
+        Stream::Stderr => eprint!("{}", s),
+    }
+}
+
+/// Like `println!`, but captured by [`capture_output`].
+macro_rules! outln {
+    ($($arg:tt)*) => (
+        $crate::diagnostics::emit(
+            $crate::diagnostics::Stream::Stdout,
+            format!("{}\n", format_args!($($arg)*)),
+        )
+    )
+}
+
+/// Like `eprintln!`, but captured by [`capture_output`].
+macro_rules! errln {
+    ($($arg:tt)*) => (
+        $crate::diagnostics::emit(
+            $crate::diagnostics::Stream::Stderr,
+            format!("{}\n", format_args!($($arg)*)),
+        )
+    )
+}
+
+pub(crate) use errln;
+pub(crate) use outln;
+
 #[derive(Debug, Clone)]
 pub struct TranslationError {
     loc: Vec<DisplaySrcSpan>,
@@ -78,7 +174,7 @@ pub struct TranslationError {
 
 pub type TranslationResult<T> = Result<T, TranslationError>;
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum TranslationErrorKind {
     Generic,
 
@@ -161,6 +257,11 @@ impl TranslationError {
         self.inner.get_context().clone()
     }
 
+    /// The locations the error was propagated through, innermost first.
+    pub fn locs(&self) -> &[DisplaySrcSpan] {
+        &self.loc
+    }
+
     pub fn new(loc: Option<DisplaySrcSpan>, inner: Context<TranslationErrorKind>) -> Self {
         Self::from(inner).add_loc(loc)
     }
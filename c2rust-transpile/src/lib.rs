@@ -2,45 +2,62 @@ mod diagnostics;
 
 pub mod build_files;
 pub mod c_ast;
+mod cache;
 pub mod cfg;
 mod compile_cmds;
+pub mod config;
 pub mod convert_type;
 pub mod renamer;
+mod report;
 pub mod rust_ast;
 pub mod translator;
+mod variants;
 pub mod with_stmts;
 
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
 use std::fs::{self, File};
 use std::io;
 use std::io::prelude::*;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
-use failure::Error;
+use failure::{format_err, Error};
 use itertools::Itertools;
 use log::{info, warn};
 use regex::Regex;
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::c_ast::Printer;
 use crate::c_ast::*;
+use crate::diagnostics::outln;
 pub use crate::diagnostics::Diagnostic;
 use c2rust_ast_exporter as ast_exporter;
 
 use crate::build_files::{emit_build_files, get_build_dir, CrateConfig};
-use crate::compile_cmds::get_compile_commands;
+use crate::cache::{Cache, CachedTranslation, Lookup};
+use crate::compile_cmds::{get_compile_commands, CompileCmd, LinkType};
+pub use crate::convert_type::LongDouble;
 use crate::convert_type::RESERVED_NAMES;
+use crate::report::{FileReport, FileStatus, Report};
 pub use crate::translator::ReplaceMode;
+use crate::variants::Variants;
 use std::prelude::v1::Vec;
 
-type PragmaVec = Vec<(&'static str, Vec<&'static str>)>;
+pub type PragmaVec = Vec<(&'static str, Vec<&'static str>)>;
 type PragmaSet = indexmap::IndexSet<(&'static str, &'static str)>;
-type CrateSet = indexmap::IndexSet<ExternCrate>;
-type TranspileResult = Result<(PathBuf, PragmaVec, CrateSet), ()>;
+pub type CrateSet = indexmap::IndexSet<ExternCrate>;
+/// The output path of a translated file, its pragmas and crates, and whether it is a binary
+type TranspileResult = Result<(PathBuf, PragmaVec, CrateSet, bool), ()>;
 
 /// Configuration settings for the translation process
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TranspilerConfig {
     // Debug output options
     pub dump_untyped_context: bool,
@@ -68,17 +85,25 @@ pub struct TranspilerConfig {
     pub fail_on_error: bool,
     pub replace_unsupported_decls: ReplaceMode,
     pub translate_valist: bool,
+    /// What to translate `long double` to
+    pub long_double: LongDouble,
     pub overwrite_existing: bool,
+    /// Reuse the translations of unchanged files from earlier runs
+    pub cache: bool,
     pub reduce_type_annotations: bool,
     pub reorganize_definitions: bool,
     pub enabled_warnings: HashSet<Diagnostic>,
     pub emit_no_std: bool,
     pub output_dir: Option<PathBuf>,
+    /// Write a JSON report of what happened to each file and declaration here
+    pub report: Option<PathBuf>,
     pub translate_const_macros: bool,
     pub translate_fn_macros: bool,
     pub disable_refactoring: bool,
     pub preserve_unused_functions: bool,
     pub log_level: log::LevelFilter,
+    /// Number of translation units to transpile in parallel, or 0 to use all available cores
+    pub jobs: usize,
 
     // Options that control build files
     /// Emit `Cargo.toml` and `lib.rs`
@@ -86,9 +111,106 @@ pub struct TranspilerConfig {
     /// Names of translation units containing main functions that we should make
     /// into binaries
     pub binaries: Vec<String>,
+
+    /// Options for the source files and headers under some paths, from the config file
+    pub overrides: Vec<config::Override>,
+}
+
+/// The defaults of the `c2rust transpile` command line.
+impl Default for TranspilerConfig {
+    fn default() -> Self {
+        Self {
+            dump_untyped_context: false,
+            dump_typed_context: false,
+            pretty_typed_context: false,
+            dump_function_cfgs: false,
+            json_function_cfgs: false,
+            dump_cfg_liveness: false,
+            dump_structures: false,
+            verbose: false,
+            debug_ast_exporter: false,
+
+            incremental_relooper: true,
+            fail_on_multiple: false,
+            filter: None,
+            debug_relooper_labels: false,
+            prefix_function_names: None,
+            translate_asm: true,
+            use_c_loop_info: true,
+            use_c_multiple_info: true,
+            simplify_structures: true,
+            panic_on_translator_failure: false,
+            emit_modules: false,
+            fail_on_error: false,
+            replace_unsupported_decls: ReplaceMode::Extern,
+            translate_valist: true,
+            long_double: LongDouble::F128,
+            overwrite_existing: false,
+            cache: false,
+            reduce_type_annotations: false,
+            reorganize_definitions: false,
+            enabled_warnings: HashSet::new(),
+            emit_no_std: false,
+            output_dir: None,
+            report: None,
+            translate_const_macros: false,
+            translate_fn_macros: false,
+            disable_refactoring: false,
+            preserve_unused_functions: false,
+            log_level: log::LevelFilter::Warn,
+            jobs: 1,
+
+            emit_build_files: false,
+            binaries: vec![],
+
+            overrides: vec![],
+        }
+    }
 }
 
 impl TranspilerConfig {
+    /// The configuration for translating `file`, with the overrides that apply to it.
+    fn for_file(&self, file: &Path) -> Cow<Self> {
+        let mut overrides = self
+            .overrides
+            .iter()
+            .filter(|o| o.applies_to(file))
+            .peekable();
+        if overrides.peek().is_none() {
+            return Cow::Borrowed(self);
+        }
+        let mut tcfg = self.clone();
+        for o in overrides {
+            o.apply(&mut tcfg);
+        }
+        Cow::Owned(tcfg)
+    }
+
+    /// Whether to translate the macros defined in `file`, which may be a header with an override of
+    /// its own.
+    fn translate_const_macros_in(&self, file: Option<&Path>) -> bool {
+        self.overrides_for(file)
+            .filter_map(|o| o.translate_const_macros)
+            .last()
+            .unwrap_or(self.translate_const_macros)
+    }
+
+    fn translate_fn_macros_in(&self, file: Option<&Path>) -> bool {
+        self.overrides_for(file)
+            .filter_map(|o| o.translate_fn_macros)
+            .last()
+            .unwrap_or(self.translate_fn_macros)
+    }
+
+    fn overrides_for<'a>(
+        &'a self,
+        file: Option<&'a Path>,
+    ) -> impl Iterator<Item = &'a config::Override> {
+        self.overrides
+            .iter()
+            .filter(move |o| file.map_or(false, |file| o.applies_to(file)))
+    }
+
     fn binary_name_from_path(file: &Path) -> String {
         let file = Path::new(file.file_stem().unwrap());
         get_module_name(file, false, false, false).unwrap()
@@ -121,6 +243,37 @@ impl TranspilerConfig {
         ok
     }
 
+    /// Whether the emitted crate is run through `c2rust-refactor`'s `reorganize_definitions`.
+    fn reorganizes_definitions(&self) -> bool {
+        self.emit_build_files && self.reorganize_definitions && !self.disable_refactoring
+    }
+
+    /// Whether to use the `--cache`.  Reorganizing definitions rewrites the translated files after
+    /// they are cached, which would make them look edited on the next run, so it disables the
+    /// cache.
+    fn use_cache(&self) -> bool {
+        self.cache && !self.reorganizes_definitions()
+    }
+
+    /// The number of translation units to transpile at once.  The debug dumps write directly to
+    /// stdout and stderr, so they force sequential transpilation to keep them readable.
+    fn num_jobs(&self) -> usize {
+        let dumping = self.dump_untyped_context
+            || self.dump_typed_context
+            || self.pretty_typed_context
+            || self.dump_structures
+            || self.debug_ast_exporter;
+        if dumping {
+            return 1;
+        }
+        match self.jobs {
+            0 => thread::available_parallelism()
+                .map(NonZeroUsize::get)
+                .unwrap_or(1),
+            jobs => jobs,
+        }
+    }
+
     fn crate_name(&self) -> String {
         self.output_dir
             .as_ref()
@@ -129,12 +282,14 @@ impl TranspilerConfig {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ExternCrate {
     C2RustBitfields,
     C2RustAsmCasts,
+    C2RustX87,
     F128,
     NumTraits,
+    NumComplex,
     Memoffset,
     Libc,
 }
@@ -145,8 +300,14 @@ struct ExternCrateDetails {
     ident: String,
     macro_use: bool,
     version: &'static str,
+    /// The local path of crates that aren't published to crates.io
+    path: Option<&'static str>,
 }
 
+/// `c2rust-x87` isn't published to crates.io, so translated crates use the copy in the C2Rust
+/// source tree that the transpiler was built from.
+const C2RUST_X87_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../c2rust-x87");
+
 impl ExternCrateDetails {
     fn new(name: &'static str, version: &'static str, macro_use: bool) -> Self {
         Self {
@@ -154,6 +315,14 @@ impl ExternCrateDetails {
             ident: name.replace('-', "_"),
             macro_use,
             version,
+            path: None,
+        }
+    }
+
+    fn path(self, path: &'static str) -> Self {
+        Self {
+            path: Some(path),
+            ..self
         }
     }
 }
@@ -163,8 +332,10 @@ impl From<ExternCrate> for ExternCrateDetails {
         match extern_crate {
             ExternCrate::C2RustBitfields => Self::new("c2rust-bitfields", "0.3", true),
             ExternCrate::C2RustAsmCasts => Self::new("c2rust-asm-casts", "0.2", true),
+            ExternCrate::C2RustX87 => Self::new("c2rust-x87", "0.1", false).path(C2RUST_X87_PATH),
             ExternCrate::F128 => Self::new("f128", "0.2", false),
             ExternCrate::NumTraits => Self::new("num-traits", "0.2", true),
+            ExternCrate::NumComplex => Self::new("num-complex", "0.4", false),
             ExternCrate::Memoffset => Self::new("memoffset", "0.5", true),
             ExternCrate::Libc => Self::new("libc", "0.2", false),
         }
@@ -240,6 +411,11 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
     let mut clang_args: Vec<&str> = clang_args.iter().map(AsRef::as_ref).collect();
     clang_args.extend_from_slice(extra_clang_args);
 
+    if tcfg.cache && !tcfg.use_cache() {
+        warn!("Not using --cache, since --reorganize-definitions rewrites the translated files");
+    }
+
+    let mut report = Report::new();
     let mut top_level_ccfg = None;
     let mut workspace_members = vec![];
     let mut num_transpiled_files = 0;
@@ -287,26 +463,49 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
             }
         }
 
-        let results = cmds
-            .iter()
-            .map(|cmd| {
-                transpile_single(
-                    &tcfg,
-                    cmd.abs_file(),
-                    &ancestor_path,
-                    &build_dir,
-                    cc_db,
-                    &clang_args,
-                )
-            })
-            .collect::<Vec<TranspileResult>>();
+        // Files compiled more than once with different `-D` flags are translated once per
+        // configuration and merged.
+        let mut cmds_by_file = indexmap::IndexMap::<_, Vec<_>>::new();
+        for cmd in cmds {
+            cmds_by_file
+                .entry(cmd.abs_file())
+                .or_default()
+                .push(CompileCmd::clone(cmd));
+        }
+        let files = cmds_by_file
+            .into_values()
+            .map(Variants::new)
+            .collect::<Vec<_>>();
+        let mut features = indexmap::IndexSet::new();
+        for file in &files {
+            features.extend(file.features.iter().cloned());
+        }
+
+        // The file that defines `main` in an executable becomes a binary using the rest of the
+        // crate as a library.
+        let exe_crate = (lcmd.r#type == LinkType::Exe).then(|| lcmd_name.replace('-', "_"));
+
+        let results = transpile_all(
+            &tcfg,
+            &files,
+            &ancestor_path,
+            &build_dir,
+            cc_db,
+            &clang_args,
+            exe_crate.as_deref(),
+        );
         let mut modules = vec![];
+        let mut binaries = vec![];
         let mut modules_skipped = false;
         let mut pragmas = PragmaSet::new();
         let mut crates = CrateSet::new();
-        for res in results {
+        for (res, file_report) in results {
+            report.push(file_report);
             match res {
-                Ok((module, pragma_vec, crate_set)) => {
+                Ok((module, pragma_vec, crate_set, is_binary)) => {
+                    if is_binary {
+                        binaries.push(module.clone());
+                    }
                     modules.push(module);
                     crates.extend(crate_set);
 
@@ -331,14 +530,17 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
             if modules_skipped {
                 // If we skipped a file, we may not have collected all required pragmas
                 warn!("Can't emit build files after incremental transpiler run; skipped.");
+                write_report(&tcfg, &report);
                 return;
             }
 
             let ccfg = CrateConfig {
                 crate_name: lcmd_name.clone(),
                 modules,
+                binaries,
                 pragmas,
                 crates,
+                features,
                 link_cmd: lcmd,
             };
             if lcmd.top_level {
@@ -352,6 +554,8 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
         }
     }
 
+    write_report(&tcfg, &report);
+
     if num_transpiled_files == 0 {
         warn!("No C files found in compile_commands.json; nothing to do.");
         return;
@@ -367,6 +571,93 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
     tcfg.check_if_all_binaries_used(&transpiled_modules);
 }
 
+/// The translation of a single C file by [`transpile_file`] or [`transpile_source`].
+#[derive(Debug)]
+pub struct TranspiledFile {
+    pub rust_source: String,
+    /// The crate-level attributes the translation needs, such as `#![feature(..)]`.
+    pub pragmas: PragmaVec,
+    /// The crates the translation uses.
+    pub crates: CrateSet,
+}
+
+/// Transpile a single C file in-process, without a `compile_commands.json`. `clang_args` are the
+/// arguments the file is compiled with, such as `-I` and `-D` flags.
+///
+/// Nothing is written next to the input or to `tcfg.output_dir`, and the options that only apply
+/// to whole builds, such as `emit_build_files`, `cache` and `report`, are ignored. Log messages
+/// go to the `log` logger of the caller, if it has one. Errors, including panics in the
+/// translator, are returned rather than propagated.
+pub fn transpile_file(
+    tcfg: &TranspilerConfig,
+    input_path: &Path,
+    clang_args: &[&str],
+) -> Result<TranspiledFile, Error> {
+    let input_path = input_path
+        .canonicalize()
+        .map_err(|e| format_err!("Could not find {}: {}", input_path.display(), e))?;
+    let tcfg = &*tcfg.for_file(&input_path);
+    let directory = input_path.parent().unwrap().to_path_buf();
+    let arguments = std::iter::once("clang")
+        .chain(clang_args.iter().copied())
+        .map(String::from)
+        .chain(["-c".to_owned(), input_path.to_string_lossy().into_owned()])
+        .collect();
+    let cmd = CompileCmd::new(directory, input_path.clone(), arguments);
+    let dir = tempfile::tempdir()?;
+    let cc_db = compile_cmds::write_compile_commands(&cmd, dir.path())?;
+
+    let extra_clang_args = get_extra_args_macos();
+    let extra_clang_args = extra_clang_args
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>();
+    // Don't print the progress messages the command-line driver prints.
+    let (result, _) = diagnostics::capture_output(|| {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            translate_file(tcfg, &input_path, &cc_db, &extra_clang_args, None, None)
+        }))
+    });
+    let (rust_source, pragmas, crates, _) = result.map_err(|payload| {
+        let msg = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown error");
+        format_err!("Transpiling {} panicked: {}", input_path.display(), msg)
+    })??;
+    Ok(TranspiledFile {
+        rust_source,
+        pragmas,
+        crates,
+    })
+}
+
+/// Transpile C source code held in memory, as if it were in a file named `file_name`, which must
+/// be a plain file name without any directories. See [`transpile_file`].
+pub fn transpile_source(
+    tcfg: &TranspilerConfig,
+    file_name: &str,
+    source: &str,
+    clang_args: &[&str],
+) -> Result<TranspiledFile, Error> {
+    if Path::new(file_name).file_name() != Some(file_name.as_ref()) {
+        return Err(format_err!("Invalid file name: {:?}", file_name));
+    }
+    let dir = tempfile::tempdir()?;
+    let input_path = dir.path().join(file_name);
+    fs::write(&input_path, source)?;
+    transpile_file(tcfg, &input_path, clang_args)
+}
+
+fn write_report(tcfg: &TranspilerConfig, report: &Report) {
+    if let Some(path) = &tcfg.report {
+        report
+            .write(path)
+            .unwrap_or_else(|e| warn!("Could not write report to {}: {}", path.display(), e));
+    }
+}
+
 /// Ensure that clang can locate the system headers on macOS 10.14+.
 ///
 /// MacOS 10.14 does not have a `/usr/include` folder even if Xcode
@@ -399,7 +690,75 @@ fn get_extra_args_macos() -> Vec<String> {
     args
 }
 
-fn invoke_refactor(_build_dir: &Path) -> Result<(), Error> {
+/// Find the `c2rust-refactor` executable (built from the `c2rust-refactor` crate), which is
+/// installed next to `c2rust-transpile` (and `c2rust`), falling back to `$PATH`.
+fn refactor_exe() -> PathBuf {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.canonicalize().ok())
+        .and_then(|exe| Some(exe.parent()?.to_owned()));
+    find_refactor_exe(exe_dir.as_deref())
+}
+
+fn find_refactor_exe(exe_dir: Option<&Path>) -> PathBuf {
+    let name = format!("c2rust-refactor{}", std::env::consts::EXE_SUFFIX);
+    exe_dir
+        .map(|dir| dir.join(&name))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Append `flag` to the `RUSTFLAGS` in `rustflags`, keeping any flags the user already set.
+fn append_rustflag(rustflags: Option<OsString>, flag: &str) -> OsString {
+    let mut rustflags = rustflags.unwrap_or_default();
+    if !rustflags.is_empty() {
+        rustflags.push(" ");
+    }
+    rustflags.push(flag);
+    rustflags
+}
+
+fn invoke_refactor(build_dir: &Path) -> Result<(), Error> {
+    // The refactorer drives rustc over the whole crate, so make sure it builds cleanly first
+    let rustflags = append_rustflag(std::env::var_os("RUSTFLAGS"), "-Awarnings");
+    let status = process::Command::new("cargo")
+        .args(&["check"])
+        .env("RUSTFLAGS", rustflags)
+        .current_dir(build_dir)
+        .status()?;
+    if !status.success() {
+        return Err(failure::format_err!(
+            "Translated crate in {} does not compile, not reorganizing definitions",
+            build_dir.display()
+        ));
+    }
+
+    let args = [
+        "--cargo",
+        "--rewrite-mode",
+        "inplace",
+        "rename_unnamed",
+        ";",
+        "reorganize_definitions",
+    ];
+    let refactor = refactor_exe();
+    let status = process::Command::new(&refactor)
+        .args(&args)
+        .current_dir(build_dir)
+        .status()
+        .map_err(|e| {
+            failure::format_err!(
+                "Could not run {} (is c2rust-refactor built and installed?): {}",
+                refactor.display(),
+                e
+            )
+        })?;
+    if !status.success() {
+        return Err(failure::format_err!(
+            "Refactoring failed. Please fix errors above and re-run:\n    c2rust refactor {}",
+            args.join(" "),
+        ));
+    }
     Ok(())
 }
 
@@ -409,7 +768,7 @@ fn reorganize_definitions(
     crate_file: Option<PathBuf>,
 ) -> Result<(), Error> {
     // We only run the reorganization refactoring if we emitted a fresh crate file
-    if crate_file.is_none() || tcfg.disable_refactoring || !tcfg.reorganize_definitions {
+    if crate_file.is_none() || !tcfg.reorganizes_definitions() {
         return Ok(());
     }
 
@@ -425,52 +784,232 @@ fn reorganize_definitions(
     Ok(())
 }
 
+/// Transpile each of `files` with [`transpile_single`], using up to
+/// [`TranspilerConfig::num_jobs`] threads, and return the results and their reports in the same
+/// order.
+///
+/// Each translation unit gets its own `TypedAstContext` and output file, so they can be transpiled
+/// independently, using [`map_in_order`].  Diagnostics that clang prints while the AST exporter
+/// parses a file are written straight to stderr by the exporter, so unlike our own output, they
+/// are not captured and may interleave with the output of other translation units.
+///
+/// If `files` are linked into an executable, `exe_crate` is the name of its crate, and the file
+/// that defines `main` is translated as a binary using that crate.
+fn transpile_all(
+    tcfg: &TranspilerConfig,
+    files: &[Variants],
+    ancestor_path: &Path,
+    build_dir: &Path,
+    cc_db: &Path,
+    extra_clang_args: &[&str],
+    exe_crate: Option<&str>,
+) -> Vec<(TranspileResult, FileReport)> {
+    map_in_order(tcfg.num_jobs(), files, |file| {
+        report::collect(file.cmds[0].abs_file(), || {
+            transpile_single(
+                tcfg,
+                file,
+                ancestor_path,
+                build_dir,
+                cc_db,
+                extra_clang_args,
+                exe_crate,
+            )
+        })
+    })
+}
+
+/// Apply `f` to each of `items` on up to `jobs` threads, and return the results in the order of
+/// `items`.
+///
+/// When running in parallel, the output of each call is captured and printed all at once, in the
+/// order of `items`, so the log reads the same as a sequential run.  If a call panics, no new ones
+/// are started, and the panic is resumed once the output of all earlier ones has been printed.
+fn map_in_order<T, R, F>(jobs: usize, items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = jobs.min(items.len());
+    if jobs <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let f = &f;
+    let next = &AtomicUsize::new(0);
+    let panicked = &AtomicBool::new(false);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|s| {
+        for _ in 0..jobs {
+            let tx = tx.clone();
+            s.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= items.len() || panicked.load(Ordering::Relaxed) {
+                    break;
+                }
+                let (result, output) = diagnostics::capture_output(|| {
+                    panic::catch_unwind(AssertUnwindSafe(|| f(&items[i])))
+                });
+                if result.is_err() {
+                    panicked.store(true, Ordering::Relaxed);
+                }
+                if tx.send((i, result, output)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        // Results arrive in the order they finish; hold on to each until all earlier ones are done.
+        let mut pending = BTreeMap::new();
+        let mut results = Vec::with_capacity(items.len());
+        for (i, result, output) in rx {
+            pending.insert(i, (result, output));
+            while let Some((result, output)) = pending.remove(&results.len()) {
+                output.replay();
+                match result {
+                    Ok(result) => results.push(result),
+                    Err(payload) => panic::resume_unwind(payload),
+                }
+            }
+        }
+        results
+    })
+}
+
 fn transpile_single(
     tcfg: &TranspilerConfig,
-    input_path: PathBuf,
+    variants: &Variants,
     ancestor_path: &Path,
     build_dir: &Path,
     cc_db: &Path,
     extra_clang_args: &[&str],
+    exe_crate: Option<&str>,
 ) -> TranspileResult {
+    let input_path = variants.cmds[0].abs_file();
+    let tcfg = &*tcfg.for_file(&input_path);
+    let file = input_path.file_name().unwrap().to_str().unwrap();
     let output_path = get_output_path(tcfg, input_path.clone(), ancestor_path, build_dir);
-    if output_path.exists() && !tcfg.overwrite_existing {
+    report::with_current(|r| r.output = Some(output_path.clone()));
+
+    let cache = tcfg.use_cache().then(|| Cache::new(build_dir));
+    let cache_key = cache.as_ref().and_then(|_| {
+        cache::key(
+            tcfg,
+            &variants.cmds,
+            &output_path,
+            extra_clang_args,
+            exe_crate,
+        )
+        .map_err(|e| warn!("Not caching {}: {}", input_path.display(), e))
+        .ok()
+    });
+    let mut stale = false;
+    if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+        match cache.lookup(&output_path, key) {
+            Lookup::Hit(cached) => {
+                outln!("Reusing cached translation of {}", file);
+                report::with_current(|r| {
+                    r.status = FileStatus::Cached;
+                    r.decls = cached.decls;
+                });
+                return Ok((output_path, cached.pragmas, cached.crates, cached.is_binary));
+            }
+            Lookup::Stale => stale = true,
+            Lookup::Miss => {}
+        }
+    }
+
+    if output_path.exists() && !tcfg.overwrite_existing && !stale {
         warn!("Skipping existing file {}", output_path.display());
+        report::not_translated(FileStatus::Skipped, "output file already exists".into());
         return Err(());
     }
 
-    let file = input_path.file_name().unwrap().to_str().unwrap();
     if !input_path.exists() {
         warn!(
             "Input C file {} does not exist, skipping!",
             input_path.display()
         );
+        report::not_translated(FileStatus::Failed, "input file does not exist".into());
         return Err(());
     }
 
     if tcfg.verbose {
-        println!("Additional Clang arguments: {}", extra_clang_args.join(" "));
+        outln!("Additional Clang arguments: {}", extra_clang_args.join(" "));
     }
 
+    let (translated_string, pragmas, crates, is_binary) = if variants.cmds.len() == 1 {
+        translate_file(tcfg, &input_path, cc_db, extra_clang_args, exe_crate, None)
+            .map_err(|e| parse_failed(&input_path, &e))?
+    } else {
+        translate_variants(tcfg, variants, &input_path, extra_clang_args, exe_crate)?
+    };
+
+    let mut file = match File::create(&output_path) {
+        Ok(file) => file,
+        Err(e) => panic!(
+            "Unable to open file {} for writing: {}",
+            output_path.display(),
+            e
+        ),
+    };
+
+    match file.write_all(translated_string.as_bytes()) {
+        Ok(()) => (),
+        Err(e) => panic!(
+            "Unable to write translation to file {}: {}",
+            output_path.display(),
+            e
+        ),
+    };
+
+    if let (Some(cache), Some(key)) = (cache, cache_key) {
+        let translation = CachedTranslation {
+            pragmas: pragmas.clone(),
+            crates: crates.clone(),
+            is_binary,
+            decls: report::with_current(|r| r.decls.clone()).unwrap_or_default(),
+        };
+        cache
+            .store(&output_path, key, &translated_string, &translation)
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Could not cache the translation of {}: {}",
+                    input_path.display(),
+                    e
+                )
+            });
+    }
+
+    Ok((output_path, pragmas, crates, is_binary))
+}
+
+/// Parse and translate `input_path`, using its compile command from `cc_db`.  `cfg` is the
+/// configuration being translated, if there is more than one. Also returns whether the file was
+/// translated as a binary.
+fn translate_file(
+    tcfg: &TranspilerConfig,
+    input_path: &Path,
+    cc_db: &Path,
+    extra_clang_args: &[&str],
+    exe_crate: Option<&str>,
+    cfg: Option<&str>,
+) -> Result<(String, PragmaVec, CrateSet, bool), Error> {
     // Extract the untyped AST from the CBOR file
-    let untyped_context = match ast_exporter::get_untyped_ast(
-        input_path.as_path(),
+    let untyped_context = ast_exporter::get_untyped_ast(
+        input_path,
         cc_db,
         extra_clang_args,
         tcfg.debug_ast_exporter,
-    ) {
-        Err(e) => {
-            warn!(
-                "Error: {}. Skipping {}; is it well-formed C?",
-                e,
-                input_path.display()
-            );
-            return Err(());
-        }
-        Ok(cxt) => cxt,
-    };
+    )?;
 
-    println!("Transpiling {}", file);
+    let file = input_path.file_name().unwrap().to_str().unwrap();
+    match cfg {
+        Some(cfg) => outln!("Transpiling {} for cfg({})", file, cfg),
+        None => outln!("Transpiling {}", file),
+    }
 
     if tcfg.dump_untyped_context {
         println!("CBOR Clang AST");
@@ -496,29 +1035,78 @@ fn transpile_single(
         println!("{:#?}", Printer::new(io::stdout()).print(&typed_context));
     }
 
-    // Perform the translation
-    let (translated_string, pragmas, crates) =
-        translator::translate(typed_context, tcfg, input_path);
-
-    let mut file = match File::create(&output_path) {
-        Ok(file) => file,
-        Err(e) => panic!(
-            "Unable to open file {} for writing: {}",
-            output_path.display(),
-            e
-        ),
+    let crate_name = tcfg.crate_name();
+    let binary_crate = if tcfg.is_binary(input_path) {
+        Some(crate_name.as_str())
+    } else if typed_context.has_main_definition() {
+        exe_crate
+    } else {
+        None
     };
 
-    match file.write_all(translated_string.as_bytes()) {
-        Ok(()) => (),
-        Err(e) => panic!(
-            "Unable to write translation to file {}: {}",
-            output_path.display(),
+    // Perform the translation
+    let (translation, pragmas, crates) =
+        translator::translate(typed_context, tcfg, input_path.to_path_buf(), binary_crate);
+    Ok((translation, pragmas, crates, binary_crate.is_some()))
+}
+
+/// Translate each configuration of a file compiled with different `-D` flags, using a compilation
+/// database with just that configuration's command, and merge the results.
+fn translate_variants(
+    tcfg: &TranspilerConfig,
+    variants: &Variants,
+    input_path: &Path,
+    extra_clang_args: &[&str],
+    exe_crate: Option<&str>,
+) -> Result<(String, PragmaVec, CrateSet, bool), ()> {
+    let mut translations = vec![];
+    let mut is_binary = false;
+    for (cmd, cfg) in variants.cmds.iter().zip(variants.cfgs()) {
+        let dir = tempfile::tempdir().map_err(|e| {
+            warn!("Could not create a temporary directory: {}", e);
+            report::not_translated(FileStatus::Failed, e.to_string());
+        })?;
+        let cc_db = compile_cmds::write_compile_commands(cmd, dir.path()).map_err(|e| {
+            warn!("Could not write a compilation database: {}", e);
+            report::not_translated(FileStatus::Failed, e.to_string());
+        })?;
+        let first_decl = report::with_current(|r| r.decls.len()).unwrap_or_default();
+        let (translation, pragmas, crates, variant_is_binary) = translate_file(
+            tcfg,
+            input_path,
+            &cc_db,
+            extra_clang_args,
+            exe_crate,
+            Some(cfg),
+        )
+        .map_err(|e| parse_failed(input_path, &e))?;
+        translations.push((translation, pragmas, crates));
+        is_binary |= variant_is_binary;
+        report::with_current(|r| {
+            for decl in &mut r.decls[first_decl..] {
+                decl.cfg = Some(cfg.clone());
+            }
+        });
+    }
+    let (translation, pragmas, crates) = variants.merge(translations).map_err(|e| {
+        warn!(
+            "Could not merge the configurations of {}: {}",
+            input_path.display(),
             e
-        ),
-    };
+        );
+        report::not_translated(FileStatus::Failed, e.to_string());
+    })?;
+    Ok((translation, pragmas, crates, is_binary))
+}
 
-    Ok((output_path, pragmas, crates))
+/// Log and report that `input_path` couldn't be parsed.
+fn parse_failed(input_path: &Path, e: &Error) {
+    warn!(
+        "Error: {}. Skipping {}; is it well-formed C?",
+        e,
+        input_path.display()
+    );
+    report::not_translated(FileStatus::Failed, e.to_string());
 }
 
 fn get_output_path(
@@ -565,3 +1153,219 @@ fn get_output_path(
         input_path
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Stream;
+    use std::time::Duration;
+
+    #[test]
+    fn transpile_source_rejects_paths() {
+        let tcfg = TranspilerConfig::default();
+        for file_name in [
+            "",
+            ".",
+            "..",
+            "../foo.c",
+            "dir/foo.c",
+            "/tmp/foo.c",
+            "foo.c/",
+        ] {
+            let e = transpile_source(&tcfg, file_name, "int x;", &[])
+                .err()
+                .unwrap_or_else(|| panic!("{:?} was accepted", file_name));
+            assert!(e.to_string().starts_with("Invalid file name"), "{}", e);
+        }
+    }
+
+    #[test]
+    fn transpile_file_missing() {
+        let tcfg = TranspilerConfig::default();
+        let e = transpile_file(&tcfg, Path::new("/nonexistent/foo.c"), &[])
+            .err()
+            .unwrap();
+        assert!(e.to_string().starts_with("Could not find"), "{}", e);
+    }
+
+    #[test]
+    fn transpile_source_function() {
+        let tcfg = TranspilerConfig::default();
+        let source = "int add(int a, int b) { return a + b; }\n";
+        let file = transpile_source(&tcfg, "add.c", source, &[]).unwrap();
+        assert!(
+            file.rust_source.contains("pub unsafe extern \"C\" fn add("),
+            "{}",
+            file.rust_source
+        );
+    }
+
+    #[test]
+    fn transpile_source_long_double_by_value() {
+        let tcfg = TranspilerConfig {
+            long_double: LongDouble::X87,
+            ..Default::default()
+        };
+        let source = "\
+            long double half(long double x) { return x / 2; }\n\
+            static long double twice(long double x) { return x * 2; }\n\
+            void quarter(long double *x) { *x = twice(*x) / 8; }\n";
+        let file = transpile_source(&tcfg, "ld.c", source, &[]).unwrap();
+        // `half` can be called from C, which passes `long double` differently from `f80`.
+        assert!(
+            !file.rust_source.contains("fn half("),
+            "{}",
+            file.rust_source
+        );
+        assert!(
+            file.rust_source.contains("fn twice("),
+            "{}",
+            file.rust_source
+        );
+        assert!(
+            file.rust_source.contains("fn quarter("),
+            "{}",
+            file.rust_source
+        );
+    }
+
+    #[test]
+    fn transpile_source_long_double_f64_by_value() {
+        diagnostics::init(HashSet::new(), log::LevelFilter::Warn);
+        let tcfg = TranspilerConfig {
+            long_double: LongDouble::F64,
+            ..Default::default()
+        };
+        let source = "long double half(long double x) { return x / 2; }\n";
+        let (file, output) =
+            diagnostics::capture_output(|| transpile_source(&tcfg, "ld.c", source, &[]).unwrap());
+        assert!(
+            file.rust_source.contains("fn half("),
+            "{}",
+            file.rust_source
+        );
+        // `long double` is wider than `double` on x86 and x86_64, and passed differently.
+        if cfg!(any(target_arch = "x86", target_arch = "x86_64")) {
+            let stderr = output.text(Stream::Stderr);
+            assert!(
+                stderr.contains("half passes long double by value"),
+                "{}",
+                stderr
+            );
+        }
+    }
+
+    #[test]
+    fn transpile_file_uses_clang_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("size.c");
+        fs::write(&path, "int size = SIZE;\n").unwrap();
+        let tcfg = TranspilerConfig::default();
+        let file = transpile_file(&tcfg, &path, &["-DSIZE=42"]).unwrap();
+        assert!(file.rust_source.contains("42"), "{}", file.rust_source);
+        // Nothing is written next to the input.
+        assert!(!dir.path().join("size.rs").exists());
+    }
+
+    #[test]
+    fn find_refactor_exe_next_to_transpiler() {
+        let dir = tempfile::tempdir().unwrap();
+        let name = format!("c2rust-refactor{}", std::env::consts::EXE_SUFFIX);
+        // Not installed next to us, so look it up in `$PATH`.
+        assert_eq!(find_refactor_exe(Some(dir.path())), PathBuf::from(&name));
+        assert_eq!(find_refactor_exe(None), PathBuf::from(&name));
+
+        fs::write(dir.path().join(&name), "").unwrap();
+        assert_eq!(find_refactor_exe(Some(dir.path())), dir.path().join(&name));
+    }
+
+    #[test]
+    fn append_rustflag_keeps_existing_flags() {
+        assert_eq!(append_rustflag(None, "-Awarnings"), "-Awarnings");
+        assert_eq!(append_rustflag(Some("".into()), "-Awarnings"), "-Awarnings");
+        assert_eq!(
+            append_rustflag(Some("-C target-cpu=native".into()), "-Awarnings"),
+            "-C target-cpu=native -Awarnings"
+        );
+    }
+
+    #[test]
+    fn invoke_refactor_requires_compiling_crate() {
+        // There's no crate here, so `cargo check` fails before we try to refactor it.
+        let dir = tempfile::tempdir().unwrap();
+        let e = invoke_refactor(dir.path()).err().unwrap();
+        assert!(e.to_string().contains("does not compile"), "{}", e);
+    }
+
+    #[test]
+    fn map_in_order_keeps_order() {
+        let items = (0..16).collect::<Vec<u64>>();
+        let (results, output) = diagnostics::capture_output(|| {
+            map_in_order(4, &items, |&i| {
+                // Make later items finish first.
+                thread::sleep(Duration::from_millis(4 * (16 - i)));
+                outln!("item {}", i);
+                i * 2
+            })
+        });
+        assert_eq!(results, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+        let expected = items
+            .iter()
+            .map(|i| format!("item {}\n", i))
+            .collect::<String>();
+        assert_eq!(output.text(Stream::Stdout), expected);
+    }
+
+    #[test]
+    fn map_in_order_propagates_panics() {
+        let items = (0..16).collect::<Vec<u64>>();
+        let (result, output) = diagnostics::capture_output(|| {
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                map_in_order(4, &items, |&i| {
+                    if i == 5 {
+                        panic!("item {} failed", i);
+                    }
+                    outln!("item {}", i);
+                })
+            }))
+        });
+        let payload = result.unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<String>().map(String::as_str),
+            Some("item 5 failed")
+        );
+        // Only the output of the items before the panicking one is printed.
+        assert_eq!(
+            output.text(Stream::Stdout),
+            "item 0\nitem 1\nitem 2\nitem 3\nitem 4\n"
+        );
+    }
+
+    #[test]
+    fn reorganizing_definitions_disables_cache() {
+        let tcfg = TranspilerConfig {
+            cache: true,
+            emit_build_files: true,
+            ..Default::default()
+        };
+        assert!(tcfg.use_cache());
+
+        let reorganize = TranspilerConfig {
+            reorganize_definitions: true,
+            ..tcfg
+        };
+        assert!(!reorganize.use_cache());
+
+        // Nothing is reorganized without build files, or with refactoring disabled.
+        assert!(TranspilerConfig {
+            emit_build_files: false,
+            ..reorganize.clone()
+        }
+        .use_cache());
+        assert!(TranspilerConfig {
+            disable_refactoring: true,
+            ..reorganize
+        }
+        .use_cache());
+    }
+}
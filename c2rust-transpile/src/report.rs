@@ -0,0 +1,239 @@
+//! The report written by `--report`, which records what happened to each translation unit and to
+//! each declaration in it, so that the coverage of a translation can be tracked over time.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use failure::{Error, Fail};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::c_ast::CDeclKind;
+use crate::diagnostics::{TranslationError, TranslationErrorKind};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Report {
+    c2rust_version: &'static str,
+    clang_version: &'static str,
+    files: Vec<FileReport>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self {
+            c2rust_version: env!("CARGO_PKG_VERSION"),
+            clang_version: c2rust_ast_exporter::get_clang_version(),
+            files: vec![],
+        }
+    }
+
+    pub fn push(&mut self, file: FileReport) {
+        self.files.push(file);
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FileStatus {
+    #[default]
+    Translated,
+    /// The translation from an earlier run was reused (see `--cache`).
+    Cached,
+    /// The output file already exists.
+    Skipped,
+    /// The file couldn't be parsed or translated at all.
+    Failed,
+}
+
+/// What happened to one translation unit.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct FileReport {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub status: FileStatus,
+    /// Why the file wasn't translated, if it wasn't.
+    pub error: Option<String>,
+    pub decls: Vec<DeclReport>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DeclStatus {
+    Translated,
+    /// The definition couldn't be translated, so only an `extern` declaration was emitted (see
+    /// [`ReplaceMode::Extern`](crate::ReplaceMode::Extern)).
+    Extern,
+    Skipped,
+}
+
+/// What happened to one declaration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct DeclReport {
+    pub name: Option<String>,
+    pub kind: String,
+    pub location: Option<String>,
+    pub status: DeclStatus,
+    pub error: Option<ErrorReport>,
+    /// The configuration the declaration was translated in, for files compiled with different
+    /// `-D` flags.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfg: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ErrorReport {
+    pub kind: TranslationErrorKind,
+    pub message: String,
+    /// Where the error occurred, innermost first.
+    pub locations: Vec<String>,
+}
+
+impl From<&TranslationError> for ErrorReport {
+    fn from(e: &TranslationError) -> Self {
+        let message = match e.cause() {
+            Some(cause) => cause.to_string(),
+            None => e.kind().to_string(),
+        };
+        Self {
+            kind: e.kind(),
+            message,
+            locations: e.locs().iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// The name of a kind of declaration in the report.
+pub(crate) fn decl_kind_name(kind: &CDeclKind) -> &'static str {
+    use CDeclKind::*;
+    match kind {
+        Function { .. } => "function",
+        Variable { .. } => "variable",
+        Enum { .. } => "enum",
+        EnumConstant { .. } => "enum_constant",
+        Typedef { .. } => "typedef",
+        Struct { .. } => "struct",
+        Union { .. } => "union",
+        Field { .. } => "field",
+        MacroObject { .. } => "macro_object",
+        MacroFunction { .. } => "macro_function",
+        NonCanonicalDecl { .. } => "non_canonical_decl",
+        StaticAssert { .. } => "static_assert",
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<FileReport>> = RefCell::new(None);
+}
+
+/// Run `f`, which transpiles `input`, and return the report of what happened to it. Like
+/// [`capture_output`](crate::diagnostics::capture_output), this is per thread, so translation
+/// units can be transpiled in parallel.
+pub(crate) fn collect<T>(input: PathBuf, f: impl FnOnce() -> T) -> (T, FileReport) {
+    let file = FileReport {
+        input,
+        ..Default::default()
+    };
+    let prev = CURRENT.with(|c| c.replace(Some(file)));
+    let result = f();
+    let file = CURRENT.with(|c| c.replace(prev)).unwrap_or_default();
+    (result, file)
+}
+
+/// Update the report of the translation unit being [`collect`]ed, if any.
+pub(crate) fn with_current<R>(f: impl FnOnce(&mut FileReport) -> R) -> Option<R> {
+    CURRENT.with(|c| c.borrow_mut().as_mut().map(f))
+}
+
+/// Record why the translation unit being [`collect`]ed wasn't translated.
+pub(crate) fn not_translated(status: FileStatus, error: String) {
+    with_current(|file| {
+        file.status = status;
+        file.error = Some(error);
+    });
+}
+
+/// Record the outcome of translating a declaration.
+pub(crate) fn record_decl(decl: DeclReport) {
+    with_current(|file| file.decls.push(decl));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn decl(name: &str, status: DeclStatus, error: Option<ErrorReport>) -> DeclReport {
+        DeclReport {
+            name: Some(name.to_owned()),
+            kind: "function".to_owned(),
+            location: Some("a.c:1:1".to_owned()),
+            status,
+            error,
+            cfg: None,
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let ((), translated) = collect("a.c".into(), || {
+            record_decl(decl("f", DeclStatus::Translated, None));
+            let e = TranslationError::generic("unsupported builtin");
+            record_decl(decl("g", DeclStatus::Extern, Some((&e).into())));
+            with_current(|file| file.output = Some("a.rs".into()));
+        });
+        let ((), failed) = collect("b.c".into(), || {
+            not_translated(FileStatus::Failed, "could not parse".to_owned());
+        });
+        // Nothing is recorded outside of `collect`.
+        record_decl(decl("h", DeclStatus::Skipped, None));
+
+        let mut report = Report::new();
+        report.push(translated);
+        report.push(failed);
+        let report = serde_json::to_value(&report).unwrap();
+        assert_eq!(report["c2rust_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            report["files"],
+            json!([
+                {
+                    "input": "a.c",
+                    "output": "a.rs",
+                    "status": "translated",
+                    "error": null,
+                    "decls": [
+                        {
+                            "name": "f",
+                            "kind": "function",
+                            "location": "a.c:1:1",
+                            "status": "translated",
+                            "error": null,
+                        },
+                        {
+                            "name": "g",
+                            "kind": "function",
+                            "location": "a.c:1:1",
+                            "status": "extern",
+                            "error": {
+                                "kind": "Generic",
+                                "message": "unsupported builtin",
+                                "locations": [],
+                            },
+                        },
+                    ],
+                },
+                {
+                    "input": "b.c",
+                    "output": null,
+                    "status": "failed",
+                    "error": "could not parse",
+                    "decls": [],
+                },
+            ])
+        );
+    }
+}
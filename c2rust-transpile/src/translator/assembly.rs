@@ -1,7 +1,7 @@
 #![deny(missing_docs)]
 //! This module provides basic support for converting inline assembly statements.
 
-use crate::diagnostics::TranslationResult;
+use crate::diagnostics::{errln, outln, TranslationResult};
 
 use super::*;
 use log::warn;
@@ -709,7 +709,7 @@ impl<'c> Translation<'c> {
             {
                 mem_only
             } else {
-                println!("could not parse asm constraints: {}", operand.constraints);
+                outln!("could not parse asm constraints: {}", operand.constraints);
                 false
             }
         };
@@ -780,7 +780,7 @@ impl<'c> Translation<'c> {
                     });
                 }
                 // Constraint could not be parsed, drop it
-                Err(e) => eprintln!("{}", e),
+                Err(e) => errln!("{}", e),
             }
         }
         // Add unmatched inputs
@@ -791,7 +791,7 @@ impl<'c> Translation<'c> {
             let (dir_spec, mem_only, parsed) = match parse_constraints(&input.constraints, arch) {
                 Ok(x) => x,
                 Err(e) => {
-                    eprintln!("{}", e);
+                    errln!("{}", e);
                     continue;
                 }
             };
@@ -52,16 +52,13 @@ impl<'c> Translation<'c> {
             "__builtin_nan" => Ok(WithStmts::new_val(
                 mk().abs_path_expr(vec!["core", "f64", "NAN"]),
             )),
-            "__builtin_nanl" => {
-                self.use_crate(ExternCrate::F128);
-
-                Ok(WithStmts::new_val(
-                    mk().path_expr(vec!["f128", "f128", "NAN"]),
-                ))
-            }
+            "__builtin_nanl" => Ok(WithStmts::new_val(match self.long_double() {
+                LongDouble::F64 => mk().abs_path_expr(vec!["core", "f64", "NAN"]),
+                _ => self.long_double_path("NAN"),
+            })),
             "__builtin_signbit" | "__builtin_signbitf" | "__builtin_signbitl" => {
-                // Long doubles require the Float trait from num_traits to call this method
-                if builtin_name == "__builtin_signbitl" {
+                // f128 requires the Float trait from num_traits to call this method
+                if builtin_name == "__builtin_signbitl" && self.long_double() == LongDouble::F128 {
                     self.with_cur_file_item_store(|item_store| {
                         item_store.add_use(vec!["num_traits".into()], "Float");
                     });
@@ -0,0 +1,193 @@
+//! This module provides translations of C `_Complex` types, the GNU `__real__`/`__imag__`
+//! operators and the complex cast kinds.
+//!
+//! Complex values are represented by `num_complex::Complex<T>`, which is `#[repr(C)]` and has
+//! the same layout as the C type. The arithmetic and comparison operators that C allows on
+//! complex values map directly onto the operator impls provided by `num-complex`.
+
+use super::*;
+
+impl<'c> Translation<'c> {
+    /// Path to `num_complex::Complex::<T>`, where `T` is the Rust translation of `elt`.
+    fn complex_path(&self, elt: CTypeId) -> TranslationResult<Vec<PathSegment>> {
+        self.use_crate(ExternCrate::NumComplex);
+
+        let elt_ty = self.convert_type(elt)?;
+        Ok(vec![
+            mk().path_segment("num_complex"),
+            mk().path_segment_with_args("Complex", mk().angle_bracketed_args(vec![elt_ty])),
+        ])
+    }
+
+    /// Build `num_complex::Complex::<T>::new(re, im)`.
+    fn complex_new(
+        &self,
+        elt: CTypeId,
+        re: Box<Expr>,
+        im: Box<Expr>,
+    ) -> TranslationResult<Box<Expr>> {
+        let mut path = self.complex_path(elt)?;
+        path.push(mk().path_segment("new"));
+        Ok(mk().call_expr(mk().path_expr(path), vec![re, im]))
+    }
+
+    /// Returns the element type of the complex type `ctype`, if it is one.
+    pub fn complex_element_type(&self, ctype: CTypeId) -> Option<CTypeId> {
+        match self.ast_context.resolve_type(ctype).kind {
+            CTypeKind::Complex(elt) => Some(elt),
+            _ => None,
+        }
+    }
+
+    /// Zero of the scalar type `ctype`, suitable for either half of a complex value.
+    fn complex_part_zero(&self, ctype: CTypeId) -> TranslationResult<Box<Expr>> {
+        let zero = if self.ast_context.resolve_type(ctype).kind.is_floating_type() {
+            mk().lit_expr(mk().float_unsuffixed_lit("0."))
+        } else {
+            mk().lit_expr(mk().int_unsuffixed_lit(0))
+        };
+        Ok(mk().cast_expr(zero, self.convert_type(ctype)?))
+    }
+
+    /// The zero value of a complex type, used for implicit initialization.
+    pub fn complex_zero(&self, ctype: CTypeId) -> TranslationResult<Box<Expr>> {
+        let elt = self
+            .complex_element_type(ctype)
+            .ok_or_else(|| format_err!("Expected a complex type"))?;
+        self.complex_new(
+            elt,
+            self.complex_part_zero(elt)?,
+            self.complex_part_zero(elt)?,
+        )
+    }
+
+    /// Bind `val` to a fresh local so that it can be used more than once, unless it is trivially
+    /// duplicable or we are in a static initializer where statements are not allowed.
+    fn complex_reusable(
+        &self,
+        ctx: ExprContext,
+        val: WithStmts<Box<Expr>>,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        val.and_then(|val| {
+            if ctx.is_static || matches!(*unparen(&val), Expr::Path(..) | Expr::Lit(..)) {
+                return Ok(WithStmts::new_val(val));
+            }
+            let name = self.renamer.borrow_mut().fresh();
+            let local =
+                mk().local_stmt(Box::new(mk().local(mk().ident_pat(&name), None, Some(val))));
+            Ok(WithStmts::new(vec![local], mk().ident_expr(name)))
+        })
+    }
+
+    /// Translate the GNU `__real__` and `__imag__` operators. On complex operands these project
+    /// out the corresponding field (and are valid lvalues); on real operands `__real__` is the
+    /// identity and `__imag__` is zero.
+    pub fn convert_complex_part(
+        &self,
+        ctx: ExprContext,
+        name: c_ast::UnOp,
+        cqual_type: CQualTypeId,
+        arg: CExprId,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        let arg_ty = self.ast_context[arg]
+            .kind
+            .get_type()
+            .ok_or_else(|| format_err!("bad {} operand type", name))?;
+        let is_real = matches!(name, c_ast::UnOp::Real);
+
+        if self.complex_element_type(arg_ty).is_some() {
+            let field = if is_real { "re" } else { "im" };
+            let val = self.convert_expr(ctx.used(), arg)?;
+            return Ok(val.map(|val| mk().field_expr(val, field)));
+        }
+
+        if is_real {
+            self.convert_expr(ctx.used(), arg)
+        } else {
+            // The operand is still evaluated for its side effects
+            let zero = self.complex_part_zero(cqual_type.ctype)?;
+            self.convert_expr(ctx.unused(), arg)?
+                .and_then(|_| Ok(WithStmts::new_val(zero)))
+        }
+    }
+
+    /// Infer the kind of a cast involving a complex type, for casts where clang did not give us
+    /// one.
+    pub fn complex_cast_kind(&self, source: &CTypeKind, target: &CTypeKind) -> CastKind {
+        let is_floating = |kind: &CTypeKind| match *kind {
+            CTypeKind::Complex(elt) => self.ast_context.resolve_type(elt).kind.is_floating_type(),
+            ref kind => kind.is_floating_type(),
+        };
+
+        match (
+            source.is_complex(),
+            target.is_complex(),
+            is_floating(source),
+        ) {
+            (true, true, true) if is_floating(target) => CastKind::FloatingComplexCast,
+            (true, true, true) => CastKind::FloatingComplexToIntegralComplex,
+            (true, true, false) if is_floating(target) => {
+                CastKind::IntegralComplexToFloatingComplex
+            }
+            (true, true, false) => CastKind::IntegralComplexCast,
+            (true, false, true) if target.is_bool() => CastKind::FloatingComplexToBoolean,
+            (true, false, true) => CastKind::FloatingComplexToReal,
+            (true, false, false) if target.is_bool() => CastKind::IntegralComplexToBoolean,
+            (true, false, false) => CastKind::IntegralComplexToReal,
+            (false, _, true) => CastKind::FloatingRealToComplex,
+            (false, _, false) => CastKind::IntegralRealToComplex,
+        }
+    }
+
+    /// Translate the cast kinds that convert into, out of, or between complex types.
+    pub fn convert_complex_cast(
+        &self,
+        ctx: ExprContext,
+        kind: CastKind,
+        source_ty: CQualTypeId,
+        ty: CQualTypeId,
+        val: WithStmts<Box<Expr>>,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        match kind {
+            // `x` to `x + 0i`
+            CastKind::FloatingRealToComplex | CastKind::IntegralRealToComplex => {
+                let elt = self
+                    .complex_element_type(ty.ctype)
+                    .ok_or_else(|| format_err!("{:?} requires a complex target", kind))?;
+                let elt_ty = self.convert_type(elt)?;
+                let zero = self.complex_part_zero(elt)?;
+                val.result_map(|val| self.complex_new(elt, mk().cast_expr(val, elt_ty), zero))
+            }
+
+            // The imaginary part is discarded
+            CastKind::FloatingComplexToReal | CastKind::IntegralComplexToReal => {
+                let target_ty = self.convert_type(ty.ctype)?;
+                Ok(val.map(|val| mk().cast_expr(mk().field_expr(val, "re"), target_ty)))
+            }
+
+            // Both parts are converted separately
+            CastKind::FloatingComplexCast
+            | CastKind::FloatingComplexToIntegralComplex
+            | CastKind::IntegralComplexCast
+            | CastKind::IntegralComplexToFloatingComplex => {
+                let elt = self
+                    .complex_element_type(ty.ctype)
+                    .ok_or_else(|| format_err!("{:?} requires a complex target", kind))?;
+                let elt_ty = self.convert_type(elt)?;
+                self.complex_reusable(ctx, val)?.result_map(|val| {
+                    let re = mk().cast_expr(mk().field_expr(val.clone(), "re"), elt_ty.clone());
+                    let im = mk().cast_expr(mk().field_expr(val, "im"), elt_ty);
+                    self.complex_new(elt, re, im)
+                })
+            }
+
+            // A complex value is true if either part is nonzero
+            CastKind::FloatingComplexToBoolean | CastKind::IntegralComplexToBoolean => {
+                let zero = self.complex_zero(source_ty.ctype)?;
+                Ok(val.map(|val| mk().binary_expr(BinOp::Ne(Default::default()), val, zero)))
+            }
+
+            _ => Err(format_err!("{:?} is not a complex cast", kind).into()),
+        }
+    }
+}
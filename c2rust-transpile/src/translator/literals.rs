@@ -115,15 +115,15 @@ impl<'c> Translation<'c> {
                     c_str.to_owned()
                 };
                 let val = match self.ast_context.resolve_type(ty.ctype).kind {
-                    CTypeKind::LongDouble => {
-                        self.use_crate(ExternCrate::F128);
-
-                        let fn_path = mk().path_expr(vec!["f128", "f128", "new"]);
+                    ref kind if self.is_nonprimitive_float(kind) => {
+                        let fn_path = self.long_double_path("new");
                         let args = vec![mk().lit_expr(mk().float_unsuffixed_lit(&str))];
 
                         mk().call_expr(fn_path, args)
                     }
-                    CTypeKind::Double => mk().lit_expr(mk().float_lit(&str, "f64")),
+                    CTypeKind::Double | CTypeKind::LongDouble => {
+                        mk().lit_expr(mk().float_lit(&str, "f64"))
+                    }
                     CTypeKind::Float => mk().lit_expr(mk().float_lit(&str, "f32")),
                     ref k => panic!("Unsupported floating point literal type {:?}", k),
                 };
@@ -0,0 +1,507 @@
+//! This module provides translations of function-like macros, with `--translate-fn-macros`.
+//!
+//! A function-like macro is translated once from its expansions, with the arguments of each
+//! expansion replaced by the macro's parameters. If every expansion translates to the same body,
+//! the macro becomes an `#[inline] fn` when the parameter and result types are the same too and
+//! the arguments can be evaluated exactly once, or a `macro_rules!` otherwise, and its expansions
+//! are translated as calls to it. Other macros are expanded in place as before.
+
+use std::collections::HashSet;
+
+use syn::__private::ToTokens;
+
+use super::*;
+
+/// How a function-like macro was translated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MacroFunctionKind {
+    Fn,
+    MacroRules,
+}
+
+#[derive(Clone)]
+pub struct MacroFunction {
+    pub kind: MacroFunctionKind,
+    pub items: Vec<Item>,
+    /// The types the parameters and result were translated with, to import them.
+    pub types: Vec<CTypeId>,
+}
+
+/// One expansion of a function-like macro, translated with its arguments replaced by parameters.
+struct TranslatedExpansion {
+    body: TokenStream,
+    param_tys: Vec<CTypeId>,
+    ret_ty: CTypeId,
+    /// Whether the arguments can be evaluated once, before the body, without changing what the
+    /// expansion does.
+    args_are_simple: bool,
+}
+
+/// The identifier that stands for parameter `i` while the body of a macro is translated.
+fn placeholder(i: usize) -> String {
+    format!("c2rust_macro_arg_{}", i)
+}
+
+/// Replace the identifiers in `tokens` that `replace` returns tokens for.
+fn replace_idents(
+    tokens: TokenStream,
+    replace: &dyn Fn(&Ident) -> Option<TokenStream>,
+) -> TokenStream {
+    tokens
+        .into_iter()
+        .flat_map(|tt| match tt {
+            TokenTree::Group(group) => {
+                let stream = replace_idents(group.stream(), replace);
+                let mut new_group = proc_macro2::Group::new(group.delimiter(), stream);
+                new_group.set_span(group.span());
+                TokenStream::from(TokenTree::Group(new_group))
+            }
+            TokenTree::Ident(ident) => {
+                replace(&ident).unwrap_or_else(|| TokenStream::from(TokenTree::Ident(ident)))
+            }
+            tt => TokenStream::from(tt),
+        })
+        .collect()
+}
+
+impl<'c> Translation<'c> {
+    /// The translation of the function-like macro `macro_id`, if it could be translated as a
+    /// function or `macro_rules!`.
+    pub fn macro_function(
+        &self,
+        ctx: ExprContext,
+        macro_id: CDeclId,
+    ) -> TranslationResult<Option<MacroFunction>> {
+        if let Some(converted) = self.macro_functions.borrow().get(&macro_id) {
+            return Ok(converted.clone());
+        }
+        let converted = self.convert_macro_function(ctx, macro_id)?;
+        self.macro_functions
+            .borrow_mut()
+            .insert(macro_id, converted.clone());
+        Ok(converted)
+    }
+
+    fn convert_macro_function(
+        &self,
+        ctx: ExprContext,
+        macro_id: CDeclId,
+    ) -> TranslationResult<Option<MacroFunction>> {
+        let (params, is_variadic) = match &self.ast_context[macro_id].kind {
+            CDeclKind::MacroFunction {
+                params,
+                is_variadic,
+                ..
+            } => (params, *is_variadic),
+            _ => return Ok(None),
+        };
+        let name = self
+            .renamer
+            .borrow()
+            .get(&macro_id)
+            .ok_or_else(|| format_err!("Macro function not named"))?;
+        if is_variadic {
+            info!("Not translating variadic macro {}", name);
+            return Ok(None);
+        }
+
+        let ctx = ctx
+            .used()
+            .set_static(false)
+            .set_const(false)
+            .set_expanding_macro(macro_id);
+        // An expansion that can't be translated here is expanded in place instead, where it may
+        // still be translatable.
+        let expansions = self.with_scope(|| {
+            self.expansions_with_args(macro_id)
+                .map(|&expr_id| {
+                    self.translate_expansion(ctx, expr_id, params.len())
+                        .unwrap_or_else(|e| {
+                            info!("Could not translate an expansion of macro {}: {}", name, e);
+                            None
+                        })
+                })
+                .collect::<Option<Vec<_>>>()
+        });
+        let expansions = match expansions {
+            Some(expansions) => expansions,
+            None => {
+                info!("Not translating macro {} as a function", name);
+                return Ok(None);
+            }
+        };
+        let first = match expansions.first() {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+        let body_str = first.body.to_string();
+        if expansions.iter().any(|e| e.body.to_string() != body_str) {
+            info!("Expansions of macro {} translate differently", name);
+            return Ok(None);
+        }
+
+        let same_types = expansions
+            .iter()
+            .all(|e| e.param_tys == first.param_tys && e.ret_ty == first.ret_ty);
+        let kind = if same_types && expansions.iter().all(|e| e.args_are_simple) {
+            MacroFunctionKind::Fn
+        } else {
+            MacroFunctionKind::MacroRules
+        };
+
+        // Parameters can't shadow the globals the body refers to.
+        let param_names = self.with_scope(|| {
+            let mut renamer = self.renamer.borrow_mut();
+            params
+                .iter()
+                .map(|param| renamer.pick_name(param))
+                .collect::<Vec<_>>()
+        });
+        let param_idents = param_names
+            .iter()
+            .map(|name| mk().ident(name))
+            .collect::<Vec<Ident>>();
+        let placeholders = (0..params.len()).map(placeholder).collect::<Vec<_>>();
+        let param_index = |ident: &Ident| placeholders.iter().position(|p| ident == p);
+
+        let items = match kind {
+            MacroFunctionKind::Fn => {
+                let body = replace_idents(first.body.clone(), &|ident| {
+                    param_index(ident).map(|i| TokenTree::Ident(param_idents[i].clone()).into())
+                });
+                let block = syn::parse2::<Block>(body).map_err(|e| format_err!("{}", e))?;
+                let args = param_names
+                    .iter()
+                    .zip(&first.param_tys)
+                    .map(|(name, &ty)| Ok(mk().arg(self.convert_type(ty)?, mk().ident_pat(name))))
+                    .collect::<TranslationResult<Vec<_>>>()?;
+                let ret = if self.ast_context.resolve_type(first.ret_ty).kind == CTypeKind::Void {
+                    ReturnType::Default
+                } else {
+                    ReturnType::Type(Default::default(), self.convert_type(first.ret_ty)?)
+                };
+                let decl = mk().fn_decl(&name, args, None, ret);
+                vec![*mk()
+                    .single_attr("inline")
+                    .pub_()
+                    .unsafe_()
+                    .fn_item(decl, block)]
+            }
+            MacroFunctionKind::MacroRules => {
+                let dollar = || TokenTree::Punct(Punct::new('$', Alone));
+                let body = replace_idents(first.body.clone(), &|ident| {
+                    param_index(ident).map(|i| {
+                        [dollar(), TokenTree::Ident(param_idents[i].clone())]
+                            .into_iter()
+                            .collect()
+                    })
+                });
+                let matchers = param_idents
+                    .iter()
+                    .map(|ident| format!("${}:expr", ident))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let rules = format!("({}) => {{ {} }};", matchers, body)
+                    .parse::<TokenStream>()
+                    .map_err(|e| format_err!("{}", e))?;
+                let mut mac = mk()
+                    .call_attr("allow", vec!["unused_macros"])
+                    .mac_item(mk().mac(
+                        mk().path("macro_rules"),
+                        rules,
+                        MacroDelimiter::Brace(Default::default()),
+                    ));
+                if let Item::Macro(ref mut mac) = *mac {
+                    mac.ident = Some(mk().ident(name.as_str()));
+                    mac.semi_token = None;
+                }
+                // The `use` lets the macro be used by path like other items, rather than only
+                // after its definition.
+                let use_item = mk()
+                    .vis("pub(crate)")
+                    .use_simple_item(vec![name.as_str()], None::<Ident>);
+                vec![*mac, *use_item]
+            }
+        };
+
+        let mut types = first.param_tys.clone();
+        types.push(first.ret_ty);
+        Ok(Some(MacroFunction { kind, items, types }))
+    }
+
+    /// The expansions of `macro_id` whose arguments are known, which are those that aren't nested
+    /// in other macros.
+    fn expansions_with_args(&self, macro_id: CDeclId) -> impl Iterator<Item = &CExprId> {
+        self.ast_context
+            .macro_expansions
+            .get(&macro_id)
+            .into_iter()
+            .flatten()
+            .filter(move |expr_id| {
+                self.ast_context.macro_invocations.get(expr_id) == Some(&vec![macro_id])
+                    && self.ast_context.macro_args.contains_key(expr_id)
+            })
+    }
+
+    /// Translate the expansion `expr_id` of a macro with `num_params` parameters, with each of its
+    /// arguments replaced by a placeholder for the parameter, if it can be.
+    fn translate_expansion(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+        num_params: usize,
+    ) -> TranslationResult<Option<TranslatedExpansion>> {
+        let args = &self.ast_context.macro_args[&expr_id];
+        // Every parameter has to be used as a value, so that the body can refer to it.
+        let mut param_tys = vec![None; num_params];
+        for &(param, arg) in args {
+            if param >= num_params || self.ast_context[arg].kind.lrvalue() != LRValue::RValue {
+                return Ok(None);
+            }
+            param_tys[param] = param_tys[param].or_else(|| self.ast_context[arg].kind.get_type());
+        }
+        let param_tys = match param_tys.into_iter().collect::<Option<Vec<_>>>() {
+            Some(tys) => tys,
+            None => return Ok(None),
+        };
+        let ret_ty = match self.ast_context[expr_id].kind.get_type() {
+            Some(ty) => ty,
+            None => return Ok(None),
+        };
+
+        // The rest of the expansion can't refer to the locals of the function it is in.
+        let in_args = args
+            .iter()
+            .flat_map(|&(_, arg)| DFExpr::new(&self.ast_context, arg.into()))
+            .collect::<HashSet<_>>();
+        for id in DFExpr::new(&self.ast_context, expr_id.into()) {
+            if in_args.contains(&id) {
+                continue;
+            }
+            let expr_id = match id {
+                SomeId::Expr(expr_id) => expr_id,
+                _ => continue,
+            };
+            match self.ast_context[expr_id].kind {
+                // Statement expressions and `__func__` only make sense in the function they're in.
+                CExprKind::Statements(..) | CExprKind::Predefined(..) => return Ok(None),
+                CExprKind::DeclRef(_, decl_id, _) => {
+                    if let CDeclKind::Variable {
+                        has_static_duration: false,
+                        has_thread_duration: false,
+                        ..
+                    } = self.ast_context[decl_id].kind
+                    {
+                        return Ok(None);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        *self.macro_params.borrow_mut() = args
+            .iter()
+            .map(|&(param, arg)| (arg, placeholder(param)))
+            .collect();
+        let converted = self.convert_expr(ctx, expr_id);
+        self.macro_params.borrow_mut().clear();
+        let (mut stmts, val) = converted?.discard_unsafe();
+        stmts.push(Stmt::Expr(*val));
+        let body = mk().block(stmts).into_token_stream();
+
+        // If an argument was translated without going through `convert_expr`, the body depends on
+        // it rather than on the parameter.
+        let mut used = vec![false; num_params];
+        fn mark_used(tokens: TokenStream, used: &mut [bool]) {
+            for tt in tokens {
+                match tt {
+                    TokenTree::Group(group) => mark_used(group.stream(), used),
+                    TokenTree::Ident(ident) => {
+                        if let Some(i) = (0..used.len()).find(|&i| ident == placeholder(i)) {
+                            used[i] = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        mark_used(body.clone(), &mut used);
+        if used.contains(&false) {
+            return Ok(None);
+        }
+
+        let args_are_simple = args.iter().all(|&(_, arg)| {
+            self.ast_context.is_expr_pure(arg)
+                && DFExpr::new(&self.ast_context, arg.into()).all(|id| {
+                    !matches!(
+                        id.expr().map(|id| &self.ast_context[id].kind),
+                        Some(CExprKind::Unary(_, c_ast::UnOp::Deref, _, _))
+                            | Some(CExprKind::ArraySubscript(..))
+                            | Some(CExprKind::Member(_, _, _, MemberKind::Arrow, _))
+                    )
+                })
+        });
+
+        Ok(Some(TranslatedExpansion {
+            body,
+            param_tys,
+            ret_ty,
+            args_are_simple,
+        }))
+    }
+
+    /// Translate `expr_id` as a call to the function-like macro it is an expansion of, if that
+    /// macro was translated.
+    pub fn convert_macro_function_call(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        // Calls can't be evaluated in constants and statics.
+        if ctx.is_const || ctx.is_static {
+            return Ok(None);
+        }
+        let macro_id = match self.ast_context.macro_invocations.get(&expr_id) {
+            Some(macs) if macs.len() == 1 => macs[0],
+            _ => return Ok(None),
+        };
+        let args = match self.ast_context.macro_args.get(&expr_id) {
+            Some(args) => args,
+            None => return Ok(None),
+        };
+        if ctx.expanding_macro(&macro_id)
+            || !matches!(
+                self.ast_context[macro_id].kind,
+                CDeclKind::MacroFunction { .. }
+            )
+            || !self
+                .tcfg
+                .translate_fn_macros_in(self.override_file(macro_id).as_deref())
+        {
+            return Ok(None);
+        }
+        let mac = match self.macro_function(ctx, macro_id)? {
+            Some(mac) => mac,
+            None => return Ok(None),
+        };
+        let name = self
+            .renamer
+            .borrow()
+            .get(&macro_id)
+            .ok_or_else(|| format_err!("Macro function not named"))?;
+
+        // Pass the first use of each parameter.
+        let mut first_args = args.clone();
+        first_args.sort_by_key(|&(param, _)| param);
+        first_args.dedup_by_key(|&mut (param, _)| param);
+        let args = first_args
+            .into_iter()
+            .map(|(_, arg)| self.convert_expr(ctx.used(), arg))
+            .collect::<TranslationResult<Vec<_>>>()?;
+        let args = WithStmts::from_iter(args);
+
+        let call = match mac.kind {
+            MacroFunctionKind::Fn => {
+                args.map(|args| mk().call_expr(mk().path_expr(vec![name.as_str()]), args))
+            }
+            MacroFunctionKind::MacroRules => {
+                // The arguments are evaluated wherever the body uses them, like in C, so they
+                // can't have side effects that have to be separate statements.
+                if !args.stmts().is_empty() {
+                    return Ok(None);
+                }
+                args.map(|args| {
+                    let args = args
+                        .into_iter()
+                        .map(|arg| *arg)
+                        .collect::<punctuated::Punctuated<Expr, Token![,]>>();
+                    mk().mac_expr(mk().mac(
+                        mk().path(name.as_str()),
+                        args.into_token_stream(),
+                        MacroDelimiter::Paren(Default::default()),
+                    ))
+                })
+            }
+        };
+        let mut call = call;
+        call.set_unsafe();
+
+        if let Some(cur_file) = self.cur_file.borrow().as_ref() {
+            self.add_import(*cur_file, macro_id, &name);
+        }
+
+        self.convert_side_effects_expr(
+            ctx,
+            call,
+            "Function macro call expression is not supposed to be used",
+        )
+        .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transpile_source, TranspilerConfig};
+
+    fn translate(source: &str) -> String {
+        let tcfg = TranspilerConfig {
+            translate_fn_macros: true,
+            ..Default::default()
+        };
+        transpile_source(&tcfg, "macros.c", source, &[])
+            .unwrap()
+            .rust_source
+    }
+
+    #[test]
+    fn macro_as_fn() {
+        let rs = translate(
+            "#define ADD(a, b) ((a) + (b))\n\
+             int add(int x, int y) { return ADD(x, y); }\n\
+             int three(void) { return ADD(1, 2); }\n",
+        );
+        assert!(rs.contains("fn ADD("), "{}", rs);
+        assert!(!rs.contains("macro_rules! ADD"), "{}", rs);
+        assert!(rs.contains("ADD(x, y)"), "{}", rs);
+    }
+
+    #[test]
+    fn macro_as_macro_rules() {
+        // `next()` has side effects, so it has to be evaluated everywhere `x` is used.
+        let rs = translate(
+            "int next(void);\n\
+             #define TWICE(x) ((x) + (x))\n\
+             int twice_next(void) { return TWICE(next()); }\n",
+        );
+        assert!(rs.contains("macro_rules! TWICE"), "{}", rs);
+        assert!(!rs.contains("fn TWICE("), "{}", rs);
+        assert!(rs.contains("TWICE!(next())"), "{}", rs);
+    }
+
+    #[test]
+    fn macro_with_different_expansions_expanded() {
+        // Dividing an `int` and a `double` by `2` translate differently, so `HALF` is expanded
+        // in place.
+        let rs = translate(
+            "#define HALF(x) ((x) / 2)\n\
+             int half_int(int a) { return HALF(a); }\n\
+             double half_double(double b) { return HALF(b); }\n",
+        );
+        assert!(!rs.contains("fn HALF("), "{}", rs);
+        assert!(!rs.contains("macro_rules! HALF"), "{}", rs);
+        assert!(rs.contains("fn half_int("), "{}", rs);
+        assert!(rs.contains("fn half_double("), "{}", rs);
+    }
+
+    #[test]
+    fn macro_capturing_local_expanded() {
+        // `n` is a local of `plus_n`, so the body of `PLUS_N` only makes sense there.
+        let rs = translate(
+            "#define PLUS_N(x) ((x) + n)\n\
+             int plus_n(int a, int n) { return PLUS_N(a); }\n",
+        );
+        assert!(!rs.contains("fn PLUS_N("), "{}", rs);
+        assert!(!rs.contains("macro_rules! PLUS_N"), "{}", rs);
+        assert!(rs.contains("a + n"), "{}", rs);
+    }
+}
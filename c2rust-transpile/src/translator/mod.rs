@@ -1,6 +1,6 @@
 use std::cell::RefCell;
 use std::char;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::mem;
 use std::ops::Index;
 use std::path::{self, PathBuf};
@@ -18,20 +18,22 @@ use syn::*;
 use syn::{BinOp, UnOp}; // To override c_ast::{BinOp,UnOp} from glob import
 
 use crate::diagnostics::TranslationResult;
+use crate::report::{self, DeclReport, DeclStatus};
 use crate::rust_ast::comment_store::CommentStore;
 use crate::rust_ast::item_store::ItemStore;
 use crate::rust_ast::set_span::SetSpan;
 use crate::rust_ast::{pos_to_span, SpanExt};
 use crate::translator::atomics::ConvertAtomicArgs;
+use crate::translator::macros::MacroFunction;
 use crate::translator::named_references::NamedReference;
 use crate::translator::operators::ConvertBinaryExprArgs;
-use c2rust_ast_builder::{mk, properties::*, Builder};
+use c2rust_ast_builder::{mk, properties::*, Builder, CaptureBy};
 use c2rust_ast_printer::pprust::{self};
 
 use crate::c_ast::iterators::{DFExpr, SomeId};
 use crate::c_ast::*;
 use crate::cfg;
-use crate::convert_type::TypeConverter;
+use crate::convert_type::{LongDouble, TypeConverter};
 use crate::renamer::Renamer;
 use crate::with_stmts::WithStmts;
 use crate::{c_ast, format_translation_err};
@@ -42,10 +44,13 @@ pub mod assembly;
 pub mod atomics;
 mod builtins;
 mod comments;
+mod complex;
 mod literals;
+mod macros;
 mod main_function;
 mod named_references;
 mod operators;
+mod setjmp;
 mod simd;
 mod structs;
 mod variadic;
@@ -250,6 +255,10 @@ pub struct Translation<'c> {
     pub features: RefCell<IndexSet<&'static str>>,
     sectioned_static_initializers: RefCell<Vec<Stmt>>,
     extern_crates: RefCell<CrateSet>,
+    // Declarations that were replaced by an `extern` because they couldn't be translated
+    replaced_decls: RefCell<HashMap<CDeclId, TranslationError>>,
+    // Functions that can unwind from a `longjmp`, which need the `C-unwind` ABI
+    unwinding_fns: HashSet<CDeclId>,
 
     // Translation state and utilities
     type_converter: RefCell<TypeConverter>,
@@ -258,6 +267,9 @@ pub struct Translation<'c> {
     function_context: RefCell<FuncContext>,
     potential_flexible_array_members: RefCell<IndexSet<CDeclId>>,
     macro_expansions: RefCell<IndexMap<CDeclId, Option<MacroExpansion>>>,
+    macro_functions: RefCell<IndexMap<CDeclId, Option<MacroFunction>>>,
+    // Placeholders for the arguments of the function-like macro being translated
+    macro_params: RefCell<HashMap<CExprId, String>>,
 
     // Comment support
     pub comment_context: CommentContext,      // Incoming comments
@@ -489,10 +501,13 @@ pub fn translate_failure(tcfg: &TranspilerConfig, msg: &str) {
     }
 }
 
+/// Translate the C file `main_file`. If `binary_crate` is given, the file is translated as a
+/// binary that imports the rest of the translated code from that crate.
 pub fn translate(
     ast_context: TypedAstContext,
     tcfg: &TranspilerConfig,
     main_file: PathBuf,
+    binary_crate: Option<&str>,
 ) -> (String, PragmaVec, CrateSet) {
     let mut t = Translation::new(ast_context, tcfg, main_file.as_path());
     let ctx = ExprContext {
@@ -618,6 +633,7 @@ pub fn translate(
                     Name::Var(ident)
                 }
                 MacroObject { ref name, .. } => Name::Var(name),
+                MacroFunction { ref name, .. } => Name::Var(name),
                 _ => Name::None,
             };
             match decl_name {
@@ -644,7 +660,9 @@ pub fn translate(
                 if t.tcfg.reorganize_definitions {
                     *t.cur_file.borrow_mut() = decl_file_id;
                 }
-                match t.convert_decl(ctx, decl_id) {
+                let converted = t.convert_decl(ctx, decl_id);
+                t.report_decl(decl_id, &converted);
+                match converted {
                     Err(e) => {
                         let k = &t.ast_context.get_decl(&decl_id).map(|x| &x.kind);
                         let msg = format!("Skipping declaration {:?} due to error: {}", k, e);
@@ -704,8 +722,12 @@ pub fn translate(
             let needs_export = match t.ast_context[*top_id].kind {
                 Function { is_implicit, .. } => !is_implicit,
                 Variable { .. } => true,
-                MacroObject { .. } => tcfg.translate_const_macros,
-                MacroFunction { .. } => tcfg.translate_fn_macros,
+                MacroObject { .. } => {
+                    tcfg.translate_const_macros_in(t.override_file(*top_id).as_deref())
+                }
+                MacroFunction { .. } => {
+                    tcfg.translate_fn_macros_in(t.override_file(*top_id).as_deref())
+                }
                 _ => false,
             };
             if needs_export {
@@ -718,7 +740,9 @@ pub fn translate(
                 {
                     *t.cur_file.borrow_mut() = decl_file_id;
                 }
-                match t.convert_decl(ctx, *top_id) {
+                let converted = t.convert_decl(ctx, *top_id);
+                t.report_decl(*top_id, &converted);
+                match converted {
                     Err(e) => {
                         let decl = &t.ast_context.get_decl(top_id);
                         let msg = match decl {
@@ -859,7 +883,7 @@ pub fn translate(
 
         // pass all converted items to the Rust pretty printer
         let translation = pprust::to_string(|| {
-            let (attrs, mut all_items) = arrange_header(&t, t.tcfg.is_binary(main_file.as_path()));
+            let (attrs, mut all_items) = arrange_header(&t, binary_crate);
 
             all_items.extend(mod_items);
 
@@ -1040,10 +1064,13 @@ fn make_submodule(
 /// Pretty-print the leading pragmas and extern crate declarations
 // Fixing this would require major refactors for marginal benefit.
 #[allow(clippy::vec_box)]
-fn arrange_header(t: &Translation, is_binary: bool) -> (Vec<syn::Attribute>, Vec<Box<Item>>) {
+fn arrange_header(
+    t: &Translation,
+    binary_crate: Option<&str>,
+) -> (Vec<syn::Attribute>, Vec<Box<Item>>) {
     let mut out_attrs = vec![];
     let mut out_items = vec![];
-    if t.tcfg.emit_modules && !is_binary {
+    if t.tcfg.emit_modules && binary_crate.is_none() {
         for c in t.extern_crates.borrow().iter() {
             out_items.push(mk().use_simple_item(
                 mk().abs_path(vec![ExternCrateDetails::from(*c).ident]),
@@ -1072,7 +1099,7 @@ fn arrange_header(t: &Translation, is_binary: bool) -> (Vec<syn::Attribute>, Vec
             out_attrs.push(mk().single_attr("no_std").as_inner_attrs()[0].clone());
         }
 
-        if is_binary {
+        if let Some(binary_crate) = binary_crate {
             // TODO(kkysen) shouldn't need `extern crate`
             // Add `extern crate X;` to the top of the file
             for extern_crate in t.extern_crates.borrow().iter() {
@@ -1085,7 +1112,7 @@ fn arrange_header(t: &Translation, is_binary: bool) -> (Vec<syn::Attribute>, Vec
                 }
             }
 
-            out_items.push(mk().use_glob_item(mk().abs_path(vec![&t.tcfg.crate_name()])));
+            out_items.push(mk().use_glob_item(mk().abs_path(vec![binary_crate])));
         }
     }
     (out_attrs, out_items)
@@ -1204,6 +1231,8 @@ struct ConvertFunctionArgs<'a> {
     is_main: bool,
     is_variadic: bool,
     is_extern: bool,
+    /// Whether the function can unwind from a `longjmp`
+    unwinds: bool,
     new_name: &'a str,
     name: &'a str,
     arguments: &'a [(CDeclId, String, CQualTypeId)],
@@ -1234,9 +1263,13 @@ impl<'c> Translation<'c> {
         if tcfg.translate_valist {
             type_converter.translate_valist = true
         }
+        type_converter.long_double = tcfg.long_double.for_target(&ast_context.target);
+        type_converter.warn_long_double_precision = type_converter.long_double == LongDouble::F64
+            && LongDouble::Auto.for_target(&ast_context.target) != LongDouble::F64;
 
         let main_file = ast_context.find_file_id(main_file).unwrap_or(0);
         let items = indexmap! {main_file => ItemStore::new()};
+        let unwinding_fns = setjmp::unwinding_fns(&ast_context);
 
         Translation {
             features: RefCell::new(IndexSet::new()),
@@ -1260,6 +1293,8 @@ impl<'c> Translation<'c> {
             function_context: RefCell::new(FuncContext::new()),
             potential_flexible_array_members: RefCell::new(IndexSet::new()),
             macro_expansions: RefCell::new(IndexMap::new()),
+            macro_functions: RefCell::new(IndexMap::new()),
+            macro_params: RefCell::new(HashMap::new()),
             comment_context,
             comment_store: RefCell::new(CommentStore::new()),
             spans: HashMap::new(),
@@ -1268,10 +1303,38 @@ impl<'c> Translation<'c> {
             mod_names: RefCell::new(IndexMap::new()),
             main_file,
             extern_crates: RefCell::new(IndexSet::new()),
+            replaced_decls: RefCell::new(HashMap::new()),
+            unwinding_fns,
             cur_file: RefCell::new(None),
         }
     }
 
+    /// Record the outcome of converting `decl_id` for the `--report`.
+    fn report_decl(&self, decl_id: CDeclId, converted: &TranslationResult<ConvertedDecl>) {
+        let decl = match self.ast_context.get_decl(&decl_id) {
+            Some(decl) => decl,
+            None => return,
+        };
+        let (status, error) = match converted {
+            Err(e) => (DeclStatus::Skipped, Some(e.into())),
+            Ok(_) => match self.replaced_decls.borrow_mut().remove(&decl_id) {
+                Some(e) => (DeclStatus::Extern, Some((&e).into())),
+                None => (DeclStatus::Translated, None),
+            },
+        };
+        report::record_decl(DeclReport {
+            name: decl.kind.get_name().cloned(),
+            kind: report::decl_kind_name(&decl.kind).to_owned(),
+            location: self
+                .ast_context
+                .display_loc(&decl.loc)
+                .map(|loc| loc.to_string()),
+            status,
+            error,
+            cfg: None,
+        });
+    }
+
     fn use_crate(&self, extern_crate: ExternCrate) {
         self.extern_crates.borrow_mut().insert(extern_crate);
     }
@@ -1413,9 +1476,9 @@ impl<'c> Translation<'c> {
             None => return false,
         };
 
-        // The f128 crate doesn't currently provide a way to const initialize
-        // values, except for common mathematical constants
-        if let CTypeKind::LongDouble = self.ast_context[qtype.ctype].kind {
+        // Neither the f128 crate nor c2rust-x87 currently provide a way to const
+        // initialize values, except for common mathematical constants
+        if self.is_nonprimitive_float(&self.ast_context[qtype.ctype].kind) {
             return true;
         }
 
@@ -1926,6 +1989,7 @@ impl<'c> Translation<'c> {
                 }
 
                 let is_main = self.ast_context.c_main == Some(decl_id);
+                let unwinds = self.unwinding_fns.contains(&decl_id);
 
                 let converted_function = self.convert_function(
                     ctx,
@@ -1936,6 +2000,7 @@ impl<'c> Translation<'c> {
                         is_main,
                         is_variadic,
                         is_extern,
+                        unwinds,
                         new_name,
                         name,
                         arguments: &args,
@@ -1946,23 +2011,28 @@ impl<'c> Translation<'c> {
                 );
 
                 converted_function.or_else(|e| match self.tcfg.replace_unsupported_decls {
-                    ReplaceMode::Extern if body.is_none() => self.convert_function(
-                        ctx,
-                        ConvertFunctionArgs {
-                            span,
-                            is_global,
-                            is_inline: false,
-                            is_main,
-                            is_variadic,
-                            is_extern,
-                            new_name,
-                            name,
-                            arguments: &args,
-                            return_type: ret,
-                            body: None,
-                            attrs,
-                        },
-                    ),
+                    ReplaceMode::Extern if body.is_none() => {
+                        let converted = self.convert_function(
+                            ctx,
+                            ConvertFunctionArgs {
+                                span,
+                                is_global,
+                                is_inline: false,
+                                is_main,
+                                is_variadic,
+                                is_extern,
+                                unwinds,
+                                new_name,
+                                name,
+                                arguments: &args,
+                                return_type: ret,
+                                body: None,
+                                attrs,
+                            },
+                        )?;
+                        self.replaced_decls.borrow_mut().insert(decl_id, e);
+                        Ok(converted)
+                    }
                     _ => Err(e),
                 })
             }
@@ -2198,9 +2268,12 @@ impl<'c> Translation<'c> {
                 }
             }
 
-            // We aren't doing anything with the definitions of function-like
-            // macros yet.
-            MacroFunction { .. } => Ok(ConvertedDecl::NoItem),
+            MacroFunction { .. } => match self.macro_function(ctx, decl_id)? {
+                Some(mac) => Ok(ConvertedDecl::Items(
+                    mac.items.into_iter().map(Box::new).collect(),
+                )),
+                None => Ok(ConvertedDecl::NoItem),
+            },
 
             // Do not translate non-canonical decls. They will be translated at
             // their canonical declaration.
@@ -2273,6 +2346,7 @@ impl<'c> Translation<'c> {
             is_main,
             is_variadic,
             is_extern,
+            unwinds,
             new_name,
             name,
             arguments,
@@ -2283,6 +2357,34 @@ impl<'c> Translation<'c> {
 
         self.function_context.borrow_mut().enter_new(name);
 
+        // Only `f64`, where `long double` is `double`, is passed to and from C the same way as
+        // `long double`. C passes a wider `long double` in x87 or SSE registers or on the stack,
+        // unlike `f80` or `f128`. Functions with internal linkage are only called from the
+        // translation, so they can still take and return one.
+        if !self.long_double_abi_matches() && (body.is_none() || is_global) {
+            let is_long_double = |typ: CQualTypeId| {
+                self.ast_context.resolve_type(typ.ctype).kind == CTypeKind::LongDouble
+            };
+            if arguments.iter().any(|&(_, _, typ)| is_long_double(typ))
+                || return_type.map_or(false, is_long_double)
+            {
+                // Translations with `f128` or a narrower `f64` have always passed `long double`
+                // by value, so those only get a warning.
+                if self.long_double() == LongDouble::X87 {
+                    return Err(format_translation_err!(
+                        None,
+                        "Cannot pass long double by value to or from C with --long-double x87: {}",
+                        name
+                    ));
+                }
+                warn!(
+                    "{} passes long double by value, which C passes differently from {}",
+                    name,
+                    self.long_double(),
+                );
+            }
+        }
+
         self.with_scope(|| {
             let mut args: Vec<FnArg> = vec![];
 
@@ -2396,15 +2498,24 @@ impl<'c> Translation<'c> {
                 let is_extern_inline =
                     is_inline && is_extern && !attrs.contains(&c_ast::Attribute::GnuInline);
 
+                // A `longjmp` unwinds through the function, which is undefined behavior for the
+                // `C` ABI.
+                let abi = if unwinds {
+                    self.use_feature("c_unwind");
+                    "C-unwind"
+                } else {
+                    "C"
+                };
+
                 // Only add linkage attributes if the function is `extern`
                 let mut mk_ = if is_main {
                     mk()
                 } else if (is_global && !is_inline) || is_extern_inline {
-                    mk_linkage(false, new_name, name).extern_("C").pub_()
+                    mk_linkage(false, new_name, name).extern_(abi).pub_()
                 } else if self.cur_file.borrow().is_some() {
-                    mk().extern_("C").pub_()
+                    mk().extern_(abi).pub_()
                 } else {
-                    mk().extern_("C")
+                    mk().extern_(abi)
                 };
 
                 for attr in attrs {
@@ -2630,7 +2741,7 @@ impl<'c> Translation<'c> {
                 // a ptr (rhs) (even though the reverse works!). We could also be smarter here and just
                 // specify Yes for that particular case, given enough analysis.
                 let val = self.convert_expr(ctx.used().decay_ref(), cond_id)?;
-                Ok(val.map(|e| self.match_bool(target, ty_id, e)))
+                val.result_map(|e| self.match_bool(target, ty_id, e))
             }
         }
     }
@@ -2973,6 +3084,8 @@ impl<'c> Translation<'c> {
     fn convert_type(&self, type_id: CTypeId) -> TranslationResult<Box<Type>> {
         if let Some(cur_file) = *self.cur_file.borrow() {
             self.import_type(type_id, cur_file);
+        } else if self.ast_context.resolve_type(type_id).kind.is_complex() {
+            self.use_crate(ExternCrate::NumComplex);
         }
         self.type_converter
             .borrow_mut()
@@ -3255,16 +3368,21 @@ impl<'c> Translation<'c> {
             self.ast_context[expr_id]
         );
 
-        if self.tcfg.translate_const_macros {
-            if let Some(converted) = self.convert_macro_expansion(ctx, expr_id)? {
+        if let Some(param) = self.macro_params.borrow().get(&expr_id) {
+            return Ok(WithStmts::new_val(mk().path_expr(vec![param])));
+        }
+
+        // Macros defined in files with overrides may be translated even if others aren't.
+        let has_overrides = !self.tcfg.overrides.is_empty();
+
+        if self.tcfg.translate_fn_macros || has_overrides {
+            if let Some(converted) = self.convert_macro_function_call(ctx, expr_id)? {
                 return Ok(converted);
             }
         }
 
-        if self.tcfg.translate_fn_macros {
-            let text = self.ast_context.macro_expansion_text.get(&expr_id);
-            if let Some(converted) = text.and_then(|text| self.convert_macro_invocation(ctx, text))
-            {
+        if self.tcfg.translate_const_macros || has_overrides {
+            if let Some(converted) = self.convert_macro_expansion(ctx, expr_id)? {
                 return Ok(converted);
             }
         }
@@ -3285,7 +3403,9 @@ impl<'c> Translation<'c> {
                         e.context(TranslationErrorKind::OldLLVMSimd),
                     )
                 }),
-            ConvertVector(..) => Err(TranslationError::generic("convert vector not supported")),
+            ConvertVector(ty, ref child_expr_ids) => {
+                self.convert_convert_vector(ctx, ty, child_expr_ids)
+            }
 
             UnaryType(_ty, kind, opt_expr, arg_ty) => {
                 let result = match kind {
@@ -3600,7 +3720,7 @@ impl<'c> Translation<'c> {
                         |NamedReference {
                              rvalue: lhs_val, ..
                          }| {
-                            let cond = self.match_bool(true, ty.ctype, lhs_val.clone());
+                            let cond = self.match_bool(true, ty.ctype, lhs_val.clone())?;
                             let ite = mk().ifte_expr(
                                 cond,
                                 mk().block(vec![mk().expr_stmt(lhs_val)]),
@@ -3760,6 +3880,10 @@ impl<'c> Translation<'c> {
             }
 
             Call(call_expr_ty, func, ref args) => {
+                if let Some(converted) = self.convert_setjmp_call(ctx, expr_id, func, args)? {
+                    return Ok(converted);
+                }
+
                 let fn_ty =
                     self.ast_context
                         .get_pointee_qual_type(
@@ -4007,6 +4131,16 @@ impl<'c> Translation<'c> {
                 .first()
             {
                 trace!("  found macro expansion: {:?}", macro_id);
+                // Function-like macros are only translated as calls.
+                if !matches!(
+                    self.ast_context[*macro_id].kind,
+                    CDeclKind::MacroObject { .. }
+                ) || !self
+                    .tcfg
+                    .translate_const_macros_in(self.override_file(*macro_id).as_deref())
+                {
+                    return Ok(None);
+                }
                 // Ensure that we've converted this macro and that it has a
                 // valid definition
                 let expansion = self.macro_expansions.borrow().get(macro_id).cloned();
@@ -4067,21 +4201,14 @@ impl<'c> Translation<'c> {
         Ok(None)
     }
 
-    fn convert_macro_invocation(
-        &self,
-        _ctx: ExprContext,
-        text: &str,
-    ) -> Option<WithStmts<Box<Expr>>> {
-        let mut split = text.splitn(2, '(');
-        let ident = split.next()?;
-        let args = split.next()?.trim_end_matches(')');
-
-        let ts: TokenStream = syn::parse_str(args).ok()?;
-        Some(WithStmts::new_val(mk().mac_expr(mk().mac(
-            mk().path(ident),
-            ts,
-            MacroDelimiter::Paren(Default::default()),
-        ))))
+    /// The file that defines `decl_id`, if there are overrides that may apply to it. Override paths
+    /// are canonical, so this is too.
+    fn override_file(&self, decl_id: CDeclId) -> Option<PathBuf> {
+        if self.tcfg.overrides.is_empty() {
+            return None;
+        }
+        let file_id = self.ast_context.file_id(&self.ast_context[decl_id])?;
+        self.ast_context.get_file_path(file_id)?.canonicalize().ok()
     }
 
     /// If `ctx` is unused, convert `expr` to a semi statement, otherwise return
@@ -4274,7 +4401,11 @@ impl<'c> Translation<'c> {
 
                 (CTypeKind::Pointer(..), CTypeKind::Pointer(..)) => CastKind::BitCast,
 
-                // Ignoring Complex casts for now
+                (CTypeKind::Complex(..), _) | (_, CTypeKind::Complex(..)) => {
+                    self.complex_cast_kind(source_ty_kind, target_ty_kind)
+                }
+
+                // Anything else falls back to a bitcast
                 _ => {
                     warn!(
                         "Unknown CastKind for {:?} to {:?} cast. Defaulting to BitCast",
@@ -4328,13 +4459,11 @@ impl<'c> Translation<'c> {
                 let source_ty_ctype_id = source_ty.ctype;
 
                 let source_ty = self.convert_type(source_ty_ctype_id)?;
-                if let CTypeKind::LongDouble = target_ty_ctype {
-                    self.use_crate(ExternCrate::F128);
-
-                    let fn_path = mk().path_expr(vec!["f128", "f128", "new"]);
+                if self.is_nonprimitive_float(target_ty_ctype) {
+                    let fn_path = self.long_double_path("new");
                     Ok(val.map(|val| mk().call_expr(fn_path, vec![val])))
-                } else if let CTypeKind::LongDouble = self.ast_context[source_ty_ctype_id].kind {
-                    self.f128_cast_to(val, target_ty_ctype)
+                } else if self.is_nonprimitive_float(&self.ast_context[source_ty_ctype_id].kind) {
+                    self.long_double_cast_to(val, target_ty_ctype)
                 } else if let &CTypeKind::Enum(enum_decl_id) = target_ty_ctype {
                     // Casts targeting `enum` types...
                     let expr =
@@ -4453,7 +4582,7 @@ impl<'c> Translation<'c> {
                 if let Some(expr) = expr {
                     self.convert_condition(ctx, true, expr)
                 } else {
-                    Ok(val.map(|e| self.match_bool(true, source_ty.ctype, e)))
+                    val.result_map(|e| self.match_bool(true, source_ty.ctype, e))
                 }
             }
 
@@ -4466,31 +4595,71 @@ impl<'c> Translation<'c> {
             | CastKind::FloatingComplexToIntegralComplex
             | CastKind::FloatingComplexCast
             | CastKind::FloatingComplexToReal
+            | CastKind::FloatingComplexToBoolean
             | CastKind::IntegralComplexToReal
             | CastKind::IntegralRealToComplex
             | CastKind::IntegralComplexCast
             | CastKind::IntegralComplexToFloatingComplex
-            | CastKind::IntegralComplexToBoolean => Err(TranslationError::generic(
-                "TODO casts with complex numbers not supported",
-            )),
+            | CastKind::IntegralComplexToBoolean => {
+                self.convert_complex_cast(ctx, kind, source_ty, ty, val)
+            }
 
-            CastKind::VectorSplat => Err(TranslationError::generic(
-                "TODO vector splat casts not supported",
-            )),
+            CastKind::VectorSplat => self.convert_vector_splat(ctx, ty, val),
         }
     }
 
-    /// Cast a f128 to some other int or float type
-    fn f128_cast_to(
+    /// What `long double` is translated to
+    fn long_double(&self) -> LongDouble {
+        self.type_converter.borrow().long_double
+    }
+
+    /// Whether the type `long double` is translated to is passed to and from C the same way, which
+    /// is only the case when it is `f64` and `long double` is `double` on the target
+    fn long_double_abi_matches(&self) -> bool {
+        self.long_double() == LongDouble::F64
+            && LongDouble::Auto.for_target(&self.ast_context.target) == LongDouble::F64
+    }
+
+    /// Whether values of `kind` can't be cast with `as`, which is the case for `long double`
+    /// unless it is translated to `f64`
+    fn is_nonprimitive_float(&self, kind: &CTypeKind) -> bool {
+        *kind == CTypeKind::LongDouble && self.long_double() != LongDouble::F64
+    }
+
+    /// The path of the associated item `name` of the type `long double` is translated to, when
+    /// that is `f128::f128` or `c2rust_x87::f80`
+    fn long_double_path(&self, name: &str) -> Box<Expr> {
+        self.use_long_double_crate();
+        if self.long_double() == LongDouble::X87 {
+            mk().path_expr(vec!["c2rust_x87", "f80", name])
+        } else {
+            mk().path_expr(vec!["f128", "f128", name])
+        }
+    }
+
+    /// Use the crate that provides the type `long double` is translated to, if any
+    fn use_long_double_crate(&self) {
+        match self.long_double() {
+            LongDouble::X87 => self.use_crate(ExternCrate::C2RustX87),
+            LongDouble::F64 => {}
+            LongDouble::F128 | LongDouble::Auto => self.use_crate(ExternCrate::F128),
+        }
+    }
+
+    /// Cast a f128 or f80 to some other int or float type
+    fn long_double_cast_to(
         &self,
         val: WithStmts<Box<Expr>>,
         target_ty_ctype: &CTypeKind,
     ) -> TranslationResult<WithStmts<Box<Expr>>> {
-        self.use_crate(ExternCrate::NumTraits);
+        // c2rust-x87 provides these conversions as inherent methods
+        if self.long_double() == LongDouble::F128 {
+            self.use_crate(ExternCrate::NumTraits);
 
-        self.with_cur_file_item_store(|item_store| {
-            item_store.add_use(vec!["num_traits".into()], "ToPrimitive");
-        });
+            self.with_cur_file_item_store(|item_store| {
+                item_store.add_use(vec!["num_traits".into()], "ToPrimitive");
+            });
+        }
         let to_method_name = match target_ty_ctype {
             CTypeKind::Float => "to_f32",
             CTypeKind::Double => "to_f64",
@@ -4598,13 +4767,15 @@ impl<'c> Translation<'c> {
             ))
         } else if resolved_ty.is_floating_type() {
             match self.ast_context[ty_id].kind {
-                CTypeKind::LongDouble => Ok(WithStmts::new_val(
-                    mk().path_expr(vec!["f128", "f128", "ZERO"]),
-                )),
+                ref kind if self.is_nonprimitive_float(kind) => {
+                    Ok(WithStmts::new_val(self.long_double_path("ZERO")))
+                }
                 _ => Ok(WithStmts::new_val(
                     mk().lit_expr(mk().float_unsuffixed_lit("0.")),
                 )),
             }
+        } else if resolved_ty.is_complex() {
+            self.complex_zero(resolved_ty_id).map(WithStmts::new_val)
         } else if let &CTypeKind::Pointer(_) = resolved_ty {
             self.null_ptr(resolved_ty_id, is_static)
                 .map(WithStmts::new_val)
@@ -4775,26 +4946,38 @@ impl<'c> Translation<'c> {
     }
 
     /// Convert a boolean expression to a boolean for use in && or || or if
-    fn match_bool(&self, target: bool, ty_id: CTypeId, val: Box<Expr>) -> Box<Expr> {
+    fn match_bool(
+        &self,
+        target: bool,
+        ty_id: CTypeId,
+        val: Box<Expr>,
+    ) -> TranslationResult<Box<Expr>> {
         let ty = &self.ast_context.resolve_type(ty_id).kind;
 
         if self.ast_context.is_function_pointer(ty_id) {
             if target {
-                mk().method_call_expr(val, "is_some", vec![])
+                Ok(mk().method_call_expr(val, "is_some", vec![]))
             } else {
-                mk().method_call_expr(val, "is_none", vec![])
+                Ok(mk().method_call_expr(val, "is_none", vec![]))
             }
         } else if ty.is_pointer() {
             let mut res = mk().method_call_expr(val, "is_null", vec![]);
             if target {
                 res = mk().unary_expr(UnOp::Not(Default::default()), res)
             }
-            res
+            Ok(res)
         } else if ty.is_bool() {
             if target {
-                val
+                Ok(val)
             } else {
-                mk().unary_expr(UnOp::Not(Default::default()), val)
+                Ok(mk().unary_expr(UnOp::Not(Default::default()), val))
+            }
+        } else if ty.is_complex() {
+            let zero = self.complex_zero(ty_id)?;
+            if target {
+                Ok(mk().binary_expr(BinOp::Ne(Default::default()), val, zero))
+            } else {
+                Ok(mk().binary_expr(BinOp::Eq(Default::default()), val, zero))
             }
         } else {
             // One simplification we can make at the cost of inspecting `val` more closely: if `val`
@@ -4813,13 +4996,13 @@ impl<'c> Translation<'c> {
                         | BinOp::Ge(_) => {
                             if target {
                                 // If target == true, just return the argument
-                                return Box::new(unparen(arg).clone());
+                                return Ok(Box::new(unparen(arg).clone()));
                             } else {
                                 // If target == false, return !arg
-                                return mk().unary_expr(
+                                return Ok(mk().unary_expr(
                                     UnOp::Not(Default::default()),
                                     Box::new(unparen(arg).clone()),
-                                );
+                                ));
                             }
                         }
                         _ => {}
@@ -4841,9 +5024,9 @@ impl<'c> Translation<'c> {
             };
 
             if target {
-                mk().binary_expr(BinOp::Ne(Default::default()), val, zero)
+                Ok(mk().binary_expr(BinOp::Ne(Default::default()), val, zero))
             } else {
-                mk().binary_expr(BinOp::Eq(Default::default()), val, zero)
+                Ok(mk().binary_expr(BinOp::Eq(Default::default()), val, zero))
             }
         }
     }
@@ -4939,7 +5122,8 @@ impl<'c> Translation<'c> {
         match type_kind {
             // libc can be accessed from anywhere as of Rust 2019 by full path
             Void | Char | SChar | UChar | Short | UShort | Int | UInt | Long | ULong | LongLong
-            | ULongLong | Int128 | UInt128 | Half | BFloat16 | Float | Double | LongDouble => {}
+            | ULongLong | Int128 | UInt128 | Half | BFloat16 | Float | Double => {}
+            LongDouble => self.use_long_double_crate(),
             // Bool uses the bool type, so no dependency on libc
             Bool => {}
             Paren(ctype)
@@ -4952,8 +5136,11 @@ impl<'c> Translation<'c> {
             | VariableArray(ctype, _)
             | Reference(CQualTypeId { ctype, .. })
             | BlockPointer(CQualTypeId { ctype, .. })
-            | TypeOf(ctype)
-            | Complex(ctype) => self.import_type(*ctype, decl_file_id),
+            | TypeOf(ctype) => self.import_type(*ctype, decl_file_id),
+            Complex(ctype) => {
+                self.use_crate(ExternCrate::NumComplex);
+                self.import_type(*ctype, decl_file_id)
+            }
             Enum(decl_id) | Typedef(decl_id) | Union(decl_id) | Struct(decl_id) => {
                 let mut decl_id = *decl_id;
                 // if the `decl` has been "squashed", get the corresponding `decl_id`
@@ -5031,7 +5218,15 @@ impl<'c> Translation<'c> {
                 }
             }
 
-            CDeclKind::Function { .. } | CDeclKind::MacroFunction { .. } => {
+            CDeclKind::MacroFunction { .. } => {
+                if let Some(Some(mac)) = self.macro_functions.borrow().get(&decl_id) {
+                    for &ty in &mac.types {
+                        self.import_type(ty, decl_file_id)
+                    }
+                }
+            }
+
+            CDeclKind::Function { .. } => {
                 // TODO: We may need to explicitly skip SIMD functions here when getting types for
                 // a fn definition in a header since SIMD headers define functions but we're using imports
                 // rather than translating the original definition
@@ -254,10 +254,8 @@ impl<'c> Translation<'c> {
             let lhs_type = self.convert_type(compute_lhs_ty.ctype)?;
 
             // We can't simply as-cast into a non primitive like f128
-            let lhs = if *resolved_computed_kind == CTypeKind::LongDouble {
-                self.use_crate(ExternCrate::F128);
-
-                let fn_path = mk().path_expr(vec!["f128", "f128", "from"]);
+            let lhs = if self.is_nonprimitive_float(resolved_computed_kind) {
+                let fn_path = self.long_double_path("from");
                 let args = vec![read];
 
                 mk().call_expr(fn_path, args)
@@ -284,11 +282,11 @@ impl<'c> Translation<'c> {
                 WithStmts::new_unsafe_val(transmute_expr(lhs_type, result_type, val))
             } else {
                 // We can't as-cast from a non primitive like f128 back to the result_type
-                if *resolved_computed_kind == CTypeKind::LongDouble {
+                if self.is_nonprimitive_float(resolved_computed_kind) {
                     let resolved_lhs_kind = &self.ast_context.resolve_type(lhs_ty.ctype).kind;
                     let val = WithStmts::new_val(val);
 
-                    self.f128_cast_to(val, resolved_lhs_kind)?
+                    self.long_double_cast_to(val, resolved_lhs_kind)?
                 } else {
                     WithStmts::new_val(mk().cast_expr(val, result_type))
                 }
@@ -762,17 +760,17 @@ impl<'c> Translation<'c> {
             c_ast::BinOp::AssignSubtract
         };
         let one = match self.ast_context.resolve_type(ty.ctype).kind {
-            // TODO: If rust gets f16 support:
-            // CTypeKind::Half |
-            CTypeKind::Float | CTypeKind::Double => mk().lit_expr(mk().float_unsuffixed_lit("1.")),
-            CTypeKind::LongDouble => {
-                self.use_crate(ExternCrate::F128);
-
-                let fn_path = mk().path_expr(vec!["f128", "f128", "new"]);
+            ref kind if self.is_nonprimitive_float(kind) => {
+                let fn_path = self.long_double_path("new");
                 let args = vec![mk().lit_expr(mk().float_unsuffixed_lit("1."))];
 
                 mk().call_expr(fn_path, args)
             }
+            // TODO: If rust gets f16 support:
+            // CTypeKind::Half |
+            CTypeKind::Float | CTypeKind::Double | CTypeKind::LongDouble => {
+                mk().lit_expr(mk().float_unsuffixed_lit("1."))
+            }
             _ => mk().lit_expr(mk().int_unsuffixed_lit(1)),
         };
         let arg_type = self.ast_context[arg]
@@ -825,19 +823,17 @@ impl<'c> Translation<'c> {
                 )));
 
                 let mut one = match self.ast_context[ty.ctype].kind {
-                    // TODO: If rust gets f16 support:
-                    // CTypeKind::Half |
-                    CTypeKind::Float | CTypeKind::Double => {
-                        mk().lit_expr(mk().float_unsuffixed_lit("1."))
-                    }
-                    CTypeKind::LongDouble => {
-                        self.use_crate(ExternCrate::F128);
-
-                        let fn_path = mk().path_expr(vec!["f128", "f128", "new"]);
+                    ref kind if self.is_nonprimitive_float(kind) => {
+                        let fn_path = self.long_double_path("new");
                         let args = vec![mk().lit_expr(mk().float_unsuffixed_lit("1."))];
 
                         mk().call_expr(fn_path, args)
                     }
+                    // TODO: If rust gets f16 support:
+                    // CTypeKind::Half |
+                    CTypeKind::Float | CTypeKind::Double | CTypeKind::LongDouble => {
+                        mk().lit_expr(mk().float_unsuffixed_lit("1."))
+                    }
                     _ => mk().lit_expr(mk().int_unsuffixed_lit(1)),
                 };
 
@@ -1024,9 +1020,10 @@ impl<'c> Translation<'c> {
                 let arg = self.convert_expr(ctx, arg)?;
                 Ok(arg)
             }
-            c_ast::UnOp::Real | c_ast::UnOp::Imag | c_ast::UnOp::Coawait => {
-                panic!("Unsupported extension operator")
+            c_ast::UnOp::Real | c_ast::UnOp::Imag => {
+                self.convert_complex_part(ctx, name, cqual_type, arg)
             }
+            c_ast::UnOp::Coawait => panic!("Unsupported extension operator"),
         }
     }
 }
@@ -0,0 +1,286 @@
+//! This module provides translations of `setjmp` and `longjmp` into unwinding.
+//!
+//! `longjmp(env, val)` unwinds with a `(usize, c_int)` payload holding the address of `env`.
+//! `if (setjmp(env)) { handler } else { body }` runs `body` under `catch_unwind`, and runs
+//! `handler` if `body` unwinds with a payload for the same `env`. Other payloads keep unwinding.
+//! The negated forms, `!setjmp(env)` and comparisons of `setjmp(env)` with `0`, are supported too.
+//!
+//! Since the value `setjmp` returns after a `longjmp` is only used to choose a branch, other uses
+//! of `setjmp` aren't translated. Neither are bodies that `return` or jump out of the `if`, since
+//! they are translated into a closure.
+//!
+//! Unwinding out of an `extern "C"` function is undefined behavior, so the functions that call
+//! `longjmp`, directly or through other functions in the same translation unit, are translated
+//! with the `C-unwind` ABI instead.
+
+use syn::parse_quote;
+
+use crate::c_ast::iterators::DFNodes;
+
+use super::*;
+
+/// The functions that `setjmp` may be a macro for.
+const SETJMP_FNS: &[&str] = &["setjmp", "_setjmp", "sigsetjmp", "__sigsetjmp"];
+
+/// The functions that `longjmp` may be a macro for.
+const LONGJMP_FNS: &[&str] = &["longjmp", "_longjmp", "siglongjmp", "__longjmp_chk"];
+
+/// An `if` whose condition is a call to `setjmp`.
+pub struct SetjmpIf {
+    /// Whether the protected branch was left by a `longjmp`, after running it.
+    pub jumped: WithStmts<Box<Expr>>,
+    /// The branch to run after a `longjmp`.
+    pub handler: Option<CStmtId>,
+}
+
+/// The function `func` calls, if it is a direct call.
+fn callee(ast_context: &TypedAstContext, func: CExprId) -> Option<CDeclId> {
+    match ast_context[func].kind {
+        CExprKind::ImplicitCast(_, fexp, CastKind::FunctionToPointerDecay, _, _) => {
+            match ast_context[fexp].kind {
+                CExprKind::DeclRef(_, decl_id, _) => match ast_context[decl_id].kind {
+                    CDeclKind::Function { .. } => Some(decl_id),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The functions defined in `ast_context` that can unwind from a `longjmp`, because they call
+/// `longjmp` or another such function. Calls through function pointers and to functions defined
+/// elsewhere are assumed not to unwind.
+pub fn unwinding_fns(ast_context: &TypedAstContext) -> HashSet<CDeclId> {
+    let mut callers = HashMap::<CDeclId, Vec<CDeclId>>::new();
+    let mut unwinding = vec![];
+    for (&decl_id, decl) in ast_context.iter_decls() {
+        let body = match decl.kind {
+            CDeclKind::Function {
+                body: Some(body), ..
+            } => body,
+            _ => continue,
+        };
+        for node in DFNodes::new(ast_context, SomeId::Stmt(body)) {
+            let callee = match node {
+                SomeId::Expr(expr_id) => match ast_context[expr_id].kind {
+                    CExprKind::Call(_, func, _) => callee(ast_context, func),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let callee = match callee {
+                Some(callee) => callee,
+                None => continue,
+            };
+            match ast_context[callee].kind.get_name() {
+                Some(name) if LONGJMP_FNS.contains(&name.as_str()) => unwinding.push(decl_id),
+                _ => callers.entry(callee).or_default().push(decl_id),
+            }
+        }
+    }
+
+    let mut unwinding_fns = HashSet::new();
+    while let Some(decl_id) = unwinding.pop() {
+        if unwinding_fns.insert(decl_id) {
+            unwinding.extend(callers.get(&decl_id).into_iter().flatten());
+        }
+    }
+    unwinding_fns
+}
+
+impl<'c> Translation<'c> {
+    /// The name of the function `func` calls, if it is a direct call.
+    fn callee_name(&self, func: CExprId) -> Option<&str> {
+        let callee = callee(&self.ast_context, func)?;
+        self.ast_context[callee].kind.get_name().map(String::as_str)
+    }
+
+    /// The `jmp_buf` argument of `expr_id`, if it is a call to `setjmp`.
+    fn setjmp_env(&self, expr_id: CExprId) -> Option<CExprId> {
+        match self.ast_context[expr_id].kind {
+            CExprKind::Call(_, func, ref args) => self
+                .callee_name(func)
+                .filter(|name| SETJMP_FNS.contains(name))
+                .and_then(|_| args.first().copied()),
+            _ => None,
+        }
+    }
+
+    /// The `jmp_buf` argument of the `setjmp` call that `cond` tests, and whether `cond` is true
+    /// when `setjmp` returns from a `longjmp`.
+    fn setjmp_condition(&self, cond: CExprId) -> Option<(CExprId, bool)> {
+        let (cond, _) = self.ast_context.resolve_expr(cond);
+        if let Some(env) = self.setjmp_env(cond) {
+            return Some((env, true));
+        }
+        let is_zero = |e: CExprId| {
+            matches!(
+                self.ast_context.resolve_expr(e).1,
+                CExprKind::Literal(_, CLiteral::Integer(0, _))
+            )
+        };
+        match self.ast_context[cond].kind {
+            CExprKind::Unary(_, c_ast::UnOp::Not, arg, _) => self
+                .setjmp_condition(arg)
+                .map(|(env, jumped)| (env, !jumped)),
+            CExprKind::Binary(
+                _,
+                op @ (c_ast::BinOp::EqualEqual | c_ast::BinOp::NotEqual),
+                lhs,
+                rhs,
+                _,
+                _,
+            ) => {
+                let other = if is_zero(rhs) {
+                    lhs
+                } else if is_zero(lhs) {
+                    rhs
+                } else {
+                    return None;
+                };
+                let (env, jumped) = self.setjmp_condition(other)?;
+                Some((env, jumped == (op == c_ast::BinOp::NotEqual)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Translate `if (setjmp(env))`, if `scrutinee` is a call to `setjmp`. The branch that runs
+    /// when `setjmp` returns `0` is translated here, and the other one is left to the caller.
+    pub fn convert_setjmp_if(
+        &self,
+        ctx: ExprContext,
+        scrutinee: CExprId,
+        true_variant: CStmtId,
+        false_variant: Option<CStmtId>,
+    ) -> TranslationResult<Option<SetjmpIf>> {
+        let (env, jumped_if_true) = match self.setjmp_condition(scrutinee) {
+            Some(cond) => cond,
+            None => return Ok(None),
+        };
+        let (body, handler) = if jumped_if_true {
+            (false_variant, Some(true_variant))
+        } else {
+            (Some(true_variant), false_variant)
+        };
+
+        if let Some(body) = body {
+            if self.escapes_stmt(body, false, false) {
+                return Err(format_translation_err!(
+                    self.ast_context
+                        .display_loc(&self.ast_context[scrutinee].loc),
+                    "Cannot translate setjmp whose body returns or jumps out of it"
+                ));
+            }
+        }
+
+        // The `jmp_buf` may be changed by the body, so its address is taken first.
+        let env_name = self.renamer.borrow_mut().pick_name("jmp_buf");
+        let env = self.convert_expr(ctx.used(), env)?;
+        let body = match body {
+            Some(body) => {
+                self.convert_function_body(ctx, "<setjmp>", &[body], cfg::ImplicitReturnType::Void)?
+            }
+            None => vec![],
+        };
+        let body = mk().block(body);
+        let env_ident = mk().ident(&env_name);
+
+        let jumped = env.and_then(|env| -> TranslationResult<_> {
+            let env_local = mk().local(
+                mk().ident_pat(&env_name),
+                None,
+                Some(mk().cast_expr(env, mk().path_ty(vec!["usize"]))),
+            );
+            let jumped: Expr = parse_quote! {
+                match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| #body)) {
+                    Ok(_) => false,
+                    Err(payload) => match payload.downcast::<(usize, libc::c_int)>() {
+                        Ok(jmp) if jmp.0 == #env_ident => true,
+                        Ok(jmp) => ::std::panic::resume_unwind(jmp),
+                        Err(payload) => ::std::panic::resume_unwind(payload),
+                    },
+                }
+            };
+            Ok(WithStmts::new(
+                vec![mk().local_stmt(Box::new(env_local))],
+                Box::new(jumped),
+            ))
+        })?;
+
+        Ok(Some(SetjmpIf { jumped, handler }))
+    }
+
+    /// Whether `stmt_id` can leave the statement other than by finishing it.
+    fn escapes_stmt(&self, stmt_id: CStmtId, in_loop: bool, in_switch: bool) -> bool {
+        use CStmtKind::*;
+        match self.ast_context[stmt_id].kind {
+            Return(_) | Goto(_) | Label(_) => true,
+            Break => !in_loop && !in_switch,
+            Continue => !in_loop,
+            Compound(ref stmts) => stmts
+                .iter()
+                .any(|&stmt| self.escapes_stmt(stmt, in_loop, in_switch)),
+            Case(_, stmt, _) | Default(stmt) => self.escapes_stmt(stmt, in_loop, in_switch),
+            Attributed { substatement, .. } => self.escapes_stmt(substatement, in_loop, in_switch),
+            If {
+                true_variant,
+                false_variant,
+                ..
+            } => {
+                self.escapes_stmt(true_variant, in_loop, in_switch)
+                    || false_variant
+                        .map_or(false, |stmt| self.escapes_stmt(stmt, in_loop, in_switch))
+            }
+            Switch { body, .. } => self.escapes_stmt(body, in_loop, true),
+            While { body, .. } | DoWhile { body, .. } | ForLoop { body, .. } => {
+                self.escapes_stmt(body, true, in_switch)
+            }
+            Expr(_) | Empty | Decls(_) | Asm { .. } => false,
+        }
+    }
+
+    /// Translate a call to `longjmp` or `setjmp`, if `func` is one. `longjmp` unwinds to the
+    /// `setjmp` for its `jmp_buf`, and other calls to `setjmp` can't be translated.
+    pub fn convert_setjmp_call(
+        &self,
+        ctx: ExprContext,
+        call_id: CExprId,
+        func: CExprId,
+        args: &[CExprId],
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        let name = match self.callee_name(func) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        if SETJMP_FNS.contains(&name) {
+            return Err(format_translation_err!(
+                self.ast_context.display_loc(&self.ast_context[call_id].loc),
+                "Cannot translate {}, except as the condition of an if statement",
+                name
+            ));
+        }
+        if !LONGJMP_FNS.contains(&name) || args.len() != 2 {
+            return Ok(None);
+        }
+
+        let args = self.convert_exprs(ctx.used(), args)?;
+        let jump = args.map(|args| {
+            let mut args = args.into_iter();
+            let env = mk().cast_expr(args.next().unwrap(), mk().path_ty(vec!["usize"]));
+            let val = mk().cast_expr(args.next().unwrap(), mk().path_ty(vec!["libc", "c_int"]));
+            let payload = mk().call_expr(
+                mk().abs_path_expr(vec!["std", "boxed", "Box", "new"]),
+                vec![mk().tuple_expr(vec![env, val])],
+            );
+            mk().call_expr(
+                mk().abs_path_expr(vec!["std", "panic", "resume_unwind"]),
+                vec![payload],
+            )
+        });
+        self.convert_side_effects_expr(ctx, jump, "longjmp is not supposed to be used")
+            .map(Some)
+    }
+}
@@ -6,7 +6,9 @@ use super::*;
 use crate::c_ast::BinOp::{Add, BitAnd, ShiftRight};
 use crate::c_ast::CExprKind::{Binary, Call, Conditional, ExplicitCast, ImplicitCast, Literal};
 use crate::c_ast::CLiteral::Integer;
-use crate::c_ast::CTypeKind::{Char, Double, Float, Int, LongLong, Short};
+use crate::c_ast::CTypeKind::{
+    Char, Double, Float, Int, LongLong, SChar, Short, UChar, UInt, ULongLong, UShort,
+};
 use crate::c_ast::CastKind::{BitCast, IntegralCast};
 
 /// As of rustc 1.29, rust is known to be missing some SIMD functions.
@@ -67,6 +69,43 @@ static SIMD_X86_64_ONLY: &[&str] = &[
     "_mm_crc32_u64",
 ];
 
+/// Width in bits of an integer vector lane, used to tell apart conversions that only change the
+/// signedness of the lanes.
+fn int_lane_bits(kind: &CTypeKind) -> Option<u8> {
+    match kind {
+        Char | SChar | UChar => Some(8),
+        Short | UShort => Some(16),
+        Int | UInt => Some(32),
+        LongLong | ULongLong => Some(64),
+        _ => None,
+    }
+}
+
+/// The x86 intrinsic that converts `len` lanes of `src` to `len` lanes of `dst`, if there is one.
+///
+/// The intrinsics take and return whole `__m128`/`__m256` vectors, so only conversions where both
+/// sides are 128 or 256 bits wide are listed.  Narrower vectors, such as the two `int`s that
+/// `_mm_cvtepi32_pd` actually reads from its `__m128i`, are converted element-wise instead.
+fn convert_vector_intrinsic(src: &CTypeKind, dst: &CTypeKind, len: usize) -> Option<&'static str> {
+    Some(match (src, dst, len) {
+        (Int, Float, 4) => "_mm_cvtepi32_ps",
+        (Int, Float, 8) => "_mm256_cvtepi32_ps",
+        (Float, Int, 4) => "_mm_cvttps_epi32",
+        (Float, Int, 8) => "_mm256_cvttps_epi32",
+        (Int, Double, 4) => "_mm256_cvtepi32_pd",
+        (Double, Int, 4) => "_mm256_cvttpd_epi32",
+        (Float, Double, 4) => "_mm256_cvtps_pd",
+        (Double, Float, 4) => "_mm256_cvtpd_ps",
+        (Char | SChar, Short | UShort, 16) => "_mm256_cvtepi8_epi16",
+        (UChar, Short | UShort, 16) => "_mm256_cvtepu8_epi16",
+        (Short, Int | UInt, 8) => "_mm256_cvtepi16_epi32",
+        (UShort, Int | UInt, 8) => "_mm256_cvtepu16_epi32",
+        (Int, LongLong | ULongLong, 4) => "_mm256_cvtepi32_epi64",
+        (UInt, LongLong | ULongLong, 4) => "_mm256_cvtepu32_epi64",
+        _ => return None,
+    })
+}
+
 fn add_arch_use(store: &mut ItemStore, arch_name: &str, item_name: &str) {
     store.add_use_with_attr(
         vec!["core".into(), "arch".into(), arch_name.into()],
@@ -345,6 +384,153 @@ impl<'c> Translation<'c> {
         })
     }
 
+    /// Split a vector type into its (resolved) element type and its length.
+    fn vector_parts(&self, ctype: CTypeId) -> TranslationResult<(CTypeId, usize)> {
+        match self.ast_context.resolve_type(ctype).kind {
+            CTypeKind::Vector(CQualTypeId { ctype, .. }, len) => {
+                Ok((self.ast_context.resolve_type_id(ctype), len))
+            }
+            ref kind => Err(format_err!("Expected a vector type, found {:?}", kind).into()),
+        }
+    }
+
+    /// Translate a `VectorSplat` cast, which broadcasts a scalar into every lane of a vector.
+    ///
+    /// Vectors matching an x86 SIMD type use the corresponding `_mm_set1_*` intrinsic, anything
+    /// else (as well as static initializers, where the intrinsics can't be called) is transmuted
+    /// from a repeated array.
+    pub fn convert_vector_splat(
+        &self,
+        ctx: ExprContext,
+        ty: CQualTypeId,
+        val: WithStmts<Box<Expr>>,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        let (elt, len) = self.vector_parts(ty.ctype)?;
+
+        let intrinsic = match (&self.ast_context[elt].kind, len) {
+            (Float, 4) => Some(("_mm_set1_ps", "f32")),
+            (Float, 8) => Some(("_mm256_set1_ps", "f32")),
+            (Double, 2) => Some(("_mm_set1_pd", "f64")),
+            (Double, 4) => Some(("_mm256_set1_pd", "f64")),
+            (Char | SChar | UChar, 16) => Some(("_mm_set1_epi8", "i8")),
+            (Char | SChar | UChar, 32) => Some(("_mm256_set1_epi8", "i8")),
+            (Short | UShort, 8) => Some(("_mm_set1_epi16", "i16")),
+            (Short | UShort, 16) => Some(("_mm256_set1_epi16", "i16")),
+            (Int | UInt, 4) => Some(("_mm_set1_epi32", "i32")),
+            (Int | UInt, 8) => Some(("_mm256_set1_epi32", "i32")),
+            (LongLong | ULongLong, 2) => Some(("_mm_set1_epi64x", "i64")),
+            (LongLong | ULongLong, 4) => Some(("_mm256_set1_epi64x", "i64")),
+            _ => None,
+        };
+
+        match intrinsic {
+            Some((fn_name, arg_ty)) if !ctx.is_static => {
+                self.import_simd_function(fn_name)?;
+                Ok(val.map(|val| {
+                    let arg = mk().cast_expr(val, mk().path_ty(vec![arg_ty]));
+                    mk().call_expr(mk().ident_expr(fn_name), vec![arg])
+                }))
+            }
+            _ => {
+                let elt_ty = self.convert_type(elt)?;
+                let len = mk().lit_expr(mk().int_unsuffixed_lit(len as u128));
+                let array_ty = mk().array_ty(elt_ty, len.clone());
+                let mut splat = val.map(|val| {
+                    let array = mk().repeat_expr(val, len);
+                    transmute_expr(array_ty, mk().infer_ty(), array)
+                });
+                splat.set_unsafe();
+                Ok(splat)
+            }
+        }
+    }
+
+    /// Translate `__builtin_convertvector`, which converts each lane of a vector to the element
+    /// type of another vector with the same number of lanes.
+    ///
+    /// Where x86 has a single instruction for the conversion we use its intrinsic; otherwise the
+    /// vector is converted element-wise through an array.
+    pub fn convert_convert_vector(
+        &self,
+        ctx: ExprContext,
+        ty: CQualTypeId,
+        child_expr_ids: &[CExprId],
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        let src_expr_id = *child_expr_ids
+            .first()
+            .ok_or_else(|| format_err!("Missing __builtin_convertvector source"))?;
+        let src_ty = self.ast_context[src_expr_id]
+            .kind
+            .get_type()
+            .ok_or_else(|| format_err!("bad __builtin_convertvector source type"))?;
+        let (src_elt, src_len) = self.vector_parts(src_ty)?;
+        let (dst_elt, dst_len) = self.vector_parts(ty.ctype)?;
+
+        if src_len != dst_len {
+            return Err(format_err!(
+                "__builtin_convertvector between vectors of {} and {} elements",
+                src_len,
+                dst_len
+            )
+            .into());
+        }
+
+        // The source usually arrives through a bitcast from an `__m128`-style type to one of
+        // clang's internal lane types, which has no Rust counterpart of its own.
+        let val_expr_id = match self.ast_context[src_expr_id].kind {
+            ExplicitCast(_, expr_id, BitCast, _, _) | ImplicitCast(_, expr_id, BitCast, _, _) => {
+                expr_id
+            }
+            _ => src_expr_id,
+        };
+        let val = self.convert_expr(ctx.used(), val_expr_id)?;
+
+        let src_kind = &self.ast_context[src_elt].kind;
+        let dst_kind = &self.ast_context[dst_elt].kind;
+
+        // x86 integer vectors share a single Rust type per vector width, so a conversion that
+        // only changes the signedness of the lanes is a no-op.
+        if int_lane_bits(src_kind).is_some() && int_lane_bits(src_kind) == int_lane_bits(dst_kind) {
+            return Ok(val);
+        }
+
+        let intrinsic = convert_vector_intrinsic(src_kind, dst_kind, src_len);
+
+        match intrinsic {
+            Some(fn_name) if !ctx.is_static => {
+                self.import_simd_function(fn_name)?;
+                Ok(val.map(|val| mk().call_expr(mk().ident_expr(fn_name), vec![val])))
+            }
+            _ => {
+                // transmute::<[T; N], _>(transmute::<_, [S; N]>(v).map(|x| x as T))
+                let len = mk().lit_expr(mk().int_unsuffixed_lit(src_len as u128));
+                let src_array_ty = mk().array_ty(self.convert_type(src_elt)?, len.clone());
+                let dst_elt_ty = self.convert_type(dst_elt)?;
+                let dst_array_ty = mk().array_ty(dst_elt_ty.clone(), len);
+                let mut converted = val.map(|val| {
+                    let lanes = transmute_expr(mk().infer_ty(), src_array_ty, val);
+                    let lane = mk().ident_expr("x");
+                    let decl = mk().fn_decl(
+                        "",
+                        vec![mk().arg(mk().infer_ty(), mk().ident_pat("x"))],
+                        None,
+                        ReturnType::Default,
+                    );
+                    let convert = mk().closure_expr(
+                        CaptureBy::Ref,
+                        Movability::Movable,
+                        *decl,
+                        mk().cast_expr(lane, dst_elt_ty),
+                    );
+                    let lanes = mk().method_call_expr(lanes, "map", vec![convert]);
+                    transmute_expr(dst_array_ty, mk().infer_ty(), lanes)
+                });
+                converted.set_unsafe();
+                Ok(converted)
+            }
+        }
+    }
+
     /// Convert a shuffle operation into the equivalent Rust SIMD library calls.
     ///
     /// Because clang implements some shuffle operations as macros around intrinsic
@@ -545,7 +731,9 @@ impl<'c> Translation<'c> {
         use self::CastKind::BuiltinFnToFnPtr;
 
         match self.ast_context[expr_id].kind {
-            CExprKind::ShuffleVector(..) => is_explicit && kind == CastKind::BitCast,
+            CExprKind::ShuffleVector(..) | CExprKind::ConvertVector(..) => {
+                is_explicit && kind == CastKind::BitCast
+            }
             CExprKind::Call(_, fn_id, _) => {
                 let fn_expr = &self.ast_context[fn_id].kind;
 
@@ -567,3 +755,64 @@ impl<'c> Translation<'c> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_vector_intrinsics() {
+        let cases = [
+            (Int, Float, 4, "_mm_cvtepi32_ps"),
+            (Int, Float, 8, "_mm256_cvtepi32_ps"),
+            (Float, Int, 4, "_mm_cvttps_epi32"),
+            (Float, Int, 8, "_mm256_cvttps_epi32"),
+            (Int, Double, 4, "_mm256_cvtepi32_pd"),
+            (Double, Int, 4, "_mm256_cvttpd_epi32"),
+            (Float, Double, 4, "_mm256_cvtps_pd"),
+            (Double, Float, 4, "_mm256_cvtpd_ps"),
+            (Char, Short, 16, "_mm256_cvtepi8_epi16"),
+            (SChar, UShort, 16, "_mm256_cvtepi8_epi16"),
+            (UChar, Short, 16, "_mm256_cvtepu8_epi16"),
+            (Short, Int, 8, "_mm256_cvtepi16_epi32"),
+            (UShort, UInt, 8, "_mm256_cvtepu16_epi32"),
+            (Int, LongLong, 4, "_mm256_cvtepi32_epi64"),
+            (UInt, ULongLong, 4, "_mm256_cvtepu32_epi64"),
+        ];
+        for (src, dst, len, intrinsic) in cases {
+            assert_eq!(
+                convert_vector_intrinsic(&src, &dst, len),
+                Some(intrinsic),
+                "{src:?} x {len} -> {dst:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn convert_vector_narrow_uses_fallback() {
+        // Each of these has a 64-bit source or destination, which no intrinsic takes or returns.
+        let cases = [
+            (Int, Double, 2),
+            (Float, Double, 2),
+            (Double, Float, 2),
+            (Char, Short, 8),
+            (UChar, Short, 8),
+            (Short, Int, 4),
+            (UShort, Int, 4),
+            (Int, LongLong, 2),
+            (UInt, LongLong, 2),
+        ];
+        for (src, dst, len) in cases {
+            assert_eq!(
+                convert_vector_intrinsic(&src, &dst, len),
+                None,
+                "{src:?} x {len} -> {dst:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn convert_vector_without_intrinsic_uses_fallback() {
+        assert_eq!(convert_vector_intrinsic(&Double, &LongLong, 2), None);
+    }
+}
@@ -0,0 +1,565 @@
+//! Translation of a C file that is compiled more than once with different `-D` flags.
+//!
+//! Each configuration is translated separately, and the results are merged into a single module.
+//! Items that are the same in every configuration are emitted once, and the others are gated by a
+//! `#[cfg]` on Cargo features.  Every configuration after the first gets a feature named after how
+//! its `-D` flags differ from the first's, such as `use_avx2` for `-DUSE_AVX2` or `level_3` for
+//! `-DLEVEL=3` when the first has `-DLEVEL=2`.  The features are additive: with none of them
+//! enabled, the first configuration is used, and when several are enabled, the one for the later
+//! command wins, so every combination of features builds.  Inline modules and `extern` blocks are
+//! merged item by item, rather than duplicated.
+//!
+//! The translations are parsed with `syn` to find their items, but the merged file is assembled
+//! from the original text of each item, since `syn` drops comments.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use failure::Error;
+use indexmap::{IndexMap, IndexSet};
+use log::warn;
+use proc_macro2::{LineColumn, Span};
+use syn::__private::ToTokens;
+use syn::spanned::Spanned;
+use syn::{Attribute, ForeignItem, Item, Meta};
+
+use crate::compile_cmds::CompileCmd;
+use crate::{str_to_ident, CrateSet, PragmaVec};
+
+/// All the compilations of one C file, usually just one.
+pub(crate) struct Variants {
+    pub cmds: Vec<CompileCmd>,
+    /// The Cargo features that select between configurations, one for each of `cmds` after the
+    /// first.
+    pub features: Vec<String>,
+    /// The `cfg` predicate that selects each of `cmds`.
+    cfgs: Vec<String>,
+}
+
+impl Variants {
+    pub fn new(cmds: Vec<CompileCmd>) -> Self {
+        let defines = cmds.iter().map(CompileCmd::defines).collect::<Vec<_>>();
+        let features = match defines.split_first() {
+            Some((base, rest)) => rest.iter().map(|d| feature_name(base, d)).collect(),
+            None => vec![],
+        };
+
+        // Configuration `i` is selected by its own feature, unless a later one is also enabled.
+        let cfgs = (0..cmds.len())
+            .map(|i| {
+                let later = &features[i.min(features.len())..];
+                match (i.checked_sub(1).map(|j| &features[j]), later) {
+                    (None, later) => format!("not({})", any_feature(later)),
+                    (Some(own), []) => format!("feature = {:?}", own),
+                    (Some(own), later) => {
+                        format!("all(feature = {:?}, not({}))", own, any_feature(later))
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            cmds,
+            features,
+            cfgs,
+        }
+    }
+
+    /// The `cfg` predicate that selects each configuration, in the order of `cmds`.
+    pub fn cfgs(&self) -> &[String] {
+        &self.cfgs
+    }
+
+    /// Merge the translations of each configuration, in the order of `cmds`.
+    pub fn merge(
+        &self,
+        translations: Vec<(String, PragmaVec, CrateSet)>,
+    ) -> Result<(String, PragmaVec, CrateSet), Error> {
+        let mut pragmas = IndexMap::<_, IndexSet<_>>::new();
+        let mut crates = CrateSet::new();
+        let mut sources = vec![];
+        for (translation, pragma_vec, crate_set) in translations {
+            for (key, vals) in pragma_vec {
+                pragmas.entry(key).or_default().extend(vals);
+            }
+            crates.extend(crate_set);
+            sources.push(Source::new(translation));
+        }
+
+        let mut attrs = vec![];
+        let mut lists = vec![];
+        let mut header = "";
+        let mut trailer = "";
+        for (i, src) in sources.iter().enumerate() {
+            let file = syn::parse_file(&src.text)?;
+            let items_start = match file.attrs.last() {
+                Some(attr) => src.range(attr.span()).end,
+                None => 0,
+            };
+            if i == 0 {
+                let attrs_start = match file.attrs.first() {
+                    Some(attr) => src.range(attr.span()).start,
+                    None => 0,
+                };
+                header = &src.text[..attrs_start];
+            }
+            attrs.extend(file.attrs.into_iter().map(|attr| (src, attr)));
+            let (pieces, rest) = src.pieces(file.items, items_start..src.text.len());
+            if i == 0 {
+                trailer = rest;
+            }
+            lists.push((i, pieces));
+        }
+
+        let scope = (0..self.cmds.len()).collect();
+        let mut out = String::from(header);
+        out.push_str(&merge_inner_attrs(attrs));
+        out.push_str(&self.merge_list(lists, &scope)?);
+        out.push_str(trailer);
+
+        let pragmas = pragmas
+            .into_iter()
+            .map(|(key, vals)| (key, vals.into_iter().collect()))
+            .collect();
+        Ok((out, pragmas, crates))
+    }
+
+    /// Merge the lists of items from each configuration in `scope`.  Items that are in every
+    /// configuration in `scope` aren't gated, since whatever contains them already is.
+    fn merge_list<T: Mergeable>(
+        &self,
+        lists: Vec<(usize, Vec<Piece<T>>)>,
+        scope: &BTreeSet<usize>,
+    ) -> Result<String, Error> {
+        // The versions of each item from the configurations that have it.  Items that are only in
+        // some configurations are placed after the previous item from the same configuration, and
+        // after the items from earlier configurations that follow it but won't be merged with one
+        // from this configuration.
+        let mut entries: Vec<(String, Versions<T>)> = vec![];
+        for (variant, list) in lists {
+            let keys = list.iter().map(|p| p.item.merge_key()).collect::<Vec<_>>();
+            let mut next = 0;
+            for (i, (key, piece)) in keys.iter().zip(list).enumerate() {
+                let pos = entries.iter().position(|(k, versions)| {
+                    k == key && versions.iter().all(|&(v, _)| v != variant)
+                });
+                match pos {
+                    Some(pos) => {
+                        entries[pos].1.push((variant, piece));
+                        next = pos + 1;
+                    }
+                    None => {
+                        while next < entries.len()
+                            && entries[next].1.iter().all(|&(v, _)| v != variant)
+                            && !keys[i + 1..].contains(&entries[next].0)
+                        {
+                            next += 1;
+                        }
+                        entries.insert(next, (key.clone(), vec![(variant, piece)]));
+                        next += 1;
+                    }
+                }
+            }
+        }
+
+        let mut out = String::new();
+        for (_, versions) in entries {
+            let present = versions.iter().map(|&(v, _)| v).collect::<BTreeSet<_>>();
+            let lead = versions[0].1.lead;
+            out.push_str(lead);
+            if present != *scope {
+                if versions[0].1.item.can_have_attrs() {
+                    // Indent the item to match the `#[cfg]`.
+                    let indent = &lead[lead.rfind('\n').map_or(0, |i| i + 1)..];
+                    out.push_str(&format!("#[cfg({})]\n{}", self.cfg(&present), indent));
+                } else {
+                    warn!(
+                        "cannot add #[cfg] to item only in some configurations: {}",
+                        versions[0].1.text
+                    );
+                }
+            }
+            out.push_str(&T::merge_versions(self, versions, &present)?);
+        }
+        Ok(out)
+    }
+
+    fn cfg(&self, present: &BTreeSet<usize>) -> String {
+        let preds = present
+            .iter()
+            .map(|&i| self.cfgs[i].as_str())
+            .collect::<Vec<_>>();
+        match preds.as_slice() {
+            [pred] => pred.to_string(),
+            preds => format!("any({})", preds.join(", ")),
+        }
+    }
+}
+
+/// The name of the feature that selects the configuration with `defines`, after the ways they
+/// differ from `base`, the defines of the first configuration.
+fn feature_name(base: &BTreeMap<String, String>, defines: &BTreeMap<String, String>) -> String {
+    let defined = defines
+        .iter()
+        .filter(|&(name, value)| base.get(name) != Some(value))
+        .map(|(name, value)| match value.as_str() {
+            "1" => name.to_owned(),
+            value => format!("{}_{}", name, value),
+        });
+    let undefined = base
+        .keys()
+        .filter(|&name| !defines.contains_key(name))
+        .map(|name| format!("no_{}", name));
+    let name = defined.chain(undefined).collect::<Vec<_>>().join("_");
+    str_to_ident(name.to_lowercase())
+}
+
+/// A `cfg` predicate that holds if any of `features` is enabled.
+fn any_feature(features: &[String]) -> String {
+    let preds = features
+        .iter()
+        .map(|feature| format!("feature = {:?}", feature))
+        .collect::<Vec<_>>();
+    match preds.as_slice() {
+        [pred] => pred.clone(),
+        preds => format!("any({})", preds.join(", ")),
+    }
+}
+
+/// Merge the inner attributes of each configuration.  List attributes like `#![feature(..)]` are
+/// combined, since enabling the same feature twice is an error.
+fn merge_inner_attrs(attrs: Vec<(&Source, Attribute)>) -> String {
+    let mut lists = IndexMap::<_, IndexSet<_>>::new();
+    let mut others = IndexSet::new();
+    for (src, attr) in attrs {
+        match attr.parse_meta() {
+            Ok(Meta::List(list)) => {
+                let nested = list.nested.iter().map(|n| src.snippet(n.span()));
+                lists
+                    .entry(src.snippet(list.path.span()))
+                    .or_default()
+                    .extend(nested);
+            }
+            _ => {
+                others.insert(src.snippet(attr.span()));
+            }
+        }
+    }
+
+    let lists = lists.into_iter().map(|(path, nested)| {
+        let nested = nested.into_iter().collect::<Vec<_>>();
+        format!("#![{}({})]", path, nested.join(", "))
+    });
+    let others = others.into_iter().map(str::to_owned);
+    lists.chain(others).collect::<Vec<_>>().join("\n")
+}
+
+/// The text of one translation, for looking up the source of the items `syn` parsed from it.
+struct Source {
+    text: String,
+    /// The byte offset where each line starts.
+    line_starts: Vec<usize>,
+}
+
+impl Source {
+    fn new(text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    fn offset(&self, lc: LineColumn) -> usize {
+        let line_start = self.line_starts[lc.line - 1];
+        // Columns count characters, not bytes.
+        self.text[line_start..]
+            .char_indices()
+            .nth(lc.column)
+            .map_or(self.text.len(), |(i, _)| line_start + i)
+    }
+
+    fn range(&self, span: Span) -> Range<usize> {
+        self.offset(span.start())..self.offset(span.end())
+    }
+
+    fn snippet(&self, span: Span) -> &str {
+        &self.text[self.range(span)]
+    }
+
+    /// Split the part of the text in `range` into `items`, each with the text before it.  Also
+    /// returns the text after the last item.
+    fn pieces<T: Spanned>(&self, items: Vec<T>, range: Range<usize>) -> (Vec<Piece<T>>, &str) {
+        let mut pos = range.start;
+        let pieces = items
+            .into_iter()
+            .map(|item| {
+                let Range { start, end } = self.range(item.span());
+                let piece = Piece {
+                    src: self,
+                    lead: &self.text[pos..start],
+                    text: &self.text[start..end],
+                    item,
+                };
+                pos = end;
+                piece
+            })
+            .collect();
+        (pieces, &self.text[pos..range.end])
+    }
+}
+
+/// The versions of an item from each configuration that has it.
+type Versions<'a, T> = Vec<(usize, Piece<'a, T>)>;
+
+/// An item of one translation, along with its text and the comments and whitespace before it.
+struct Piece<'a, T> {
+    src: &'a Source,
+    lead: &'a str,
+    text: &'a str,
+    item: T,
+}
+
+trait Mergeable: Sized + ToTokens + Spanned {
+    /// The versions of an item with the same key in different configurations are merged into
+    /// one.  Usually the key is the whole item, so only identical items are merged.
+    fn merge_key(&self) -> String {
+        self.to_token_stream().to_string()
+    }
+
+    /// Get the text of the item merged from `versions`.
+    fn merge_versions(
+        variants: &Variants,
+        versions: Versions<Self>,
+        present: &BTreeSet<usize>,
+    ) -> Result<String, Error> {
+        // Versions with the same key are identical.
+        let _ = (variants, present);
+        Ok(versions[0].1.text.to_owned())
+    }
+
+    /// Whether a `#[cfg]` can be added to the item.
+    fn can_have_attrs(&self) -> bool;
+}
+
+impl Mergeable for Item {
+    fn merge_key(&self) -> String {
+        match self {
+            Item::Mod(m) if m.content.is_some() => format!("mod {}", m.ident),
+            Item::ForeignMod(fm) => format!("extern {}", fm.abi.to_token_stream()),
+            item => item.to_token_stream().to_string(),
+        }
+    }
+
+    fn merge_versions(
+        variants: &Variants,
+        versions: Versions<Self>,
+        present: &BTreeSet<usize>,
+    ) -> Result<String, Error> {
+        let first = &versions[0].1;
+        let start = first.src.range(first.item.span()).start;
+        let brace = match &first.item {
+            Item::Mod(m) => m.content.as_ref().map(|(brace, _)| brace.span),
+            Item::ForeignMod(fm) => Some(fm.brace_token.span),
+            _ => None,
+        };
+        let brace = match brace {
+            Some(brace) => first.src.range(brace),
+            None => return Ok(first.text.to_owned()),
+        };
+        let header = &first.src.text[start..brace.start + 1];
+
+        // Merge the items inside the braces.
+        let mut lists = vec![];
+        let mut trailer = "";
+        for (i, (v, piece)) in versions.into_iter().enumerate() {
+            let inner = piece.src.range(match &piece.item {
+                Item::Mod(m) => m.content.as_ref().unwrap().0.span,
+                Item::ForeignMod(fm) => fm.brace_token.span,
+                _ => unreachable!(),
+            });
+            let inner = inner.start + 1..inner.end - 1;
+            match piece.item {
+                Item::Mod(m) => {
+                    let (pieces, rest) = piece.src.pieces(m.content.unwrap().1, inner);
+                    if i == 0 {
+                        trailer = rest;
+                    }
+                    lists.push((v, Pieces::Items(pieces)));
+                }
+                Item::ForeignMod(fm) => {
+                    let (pieces, rest) = piece.src.pieces(fm.items, inner);
+                    if i == 0 {
+                        trailer = rest;
+                    }
+                    lists.push((v, Pieces::ForeignItems(pieces)));
+                }
+                _ => unreachable!(),
+            }
+        }
+        let body = match lists[0].1 {
+            Pieces::Items(_) => variants.merge_list(
+                lists
+                    .into_iter()
+                    .map(|(v, pieces)| match pieces {
+                        Pieces::Items(pieces) => (v, pieces),
+                        Pieces::ForeignItems(_) => unreachable!(),
+                    })
+                    .collect(),
+                present,
+            )?,
+            Pieces::ForeignItems(_) => variants.merge_list(
+                lists
+                    .into_iter()
+                    .map(|(v, pieces)| match pieces {
+                        Pieces::ForeignItems(pieces) => (v, pieces),
+                        Pieces::Items(_) => unreachable!(),
+                    })
+                    .collect(),
+                present,
+            )?,
+        };
+        Ok(format!("{}{}{}}}", header, body, trailer))
+    }
+
+    fn can_have_attrs(&self) -> bool {
+        !matches!(self, Item::Verbatim(_))
+    }
+}
+
+impl Mergeable for ForeignItem {
+    fn can_have_attrs(&self) -> bool {
+        !matches!(self, ForeignItem::Verbatim(_))
+    }
+}
+
+/// The contents of an inline module or an `extern` block.
+enum Pieces<'a> {
+    Items(Vec<Piece<'a, Item>>),
+    ForeignItems(Vec<Piece<'a, ForeignItem>>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variants(args: &[&[&str]]) -> Variants {
+        let cmds = args
+            .iter()
+            .map(|args| {
+                let args = std::iter::once("cc")
+                    .chain(args.iter().copied())
+                    .chain(["-c", "a.c"])
+                    .map(str::to_owned)
+                    .collect();
+                CompileCmd::new("/src".into(), "/src/a.c".into(), args)
+            })
+            .collect();
+        Variants::new(cmds)
+    }
+
+    fn merge(variants: &Variants, translations: &[&str]) -> String {
+        let translations = translations
+            .iter()
+            .map(|&t| (t.to_owned(), PragmaVec::new(), CrateSet::new()))
+            .collect();
+        variants.merge(translations).unwrap().0
+    }
+
+    #[test]
+    fn test_cfgs() {
+        let v = variants(&[&["-DLEVEL=2"], &["-DLEVEL=3"]]);
+        assert_eq!(v.features, ["level_3"]);
+        assert_eq!(
+            v.cfgs(),
+            [r#"not(feature = "level_3")"#, r#"feature = "level_3""#]
+        );
+
+        let v = variants(&[&["-DA"], &["-DA", "-DUSE_AVX2"], &[]]);
+        assert_eq!(v.features, ["use_avx2", "no_a"]);
+        assert_eq!(
+            v.cfgs(),
+            [
+                r#"not(any(feature = "use_avx2", feature = "no_a"))"#,
+                r#"all(feature = "use_avx2", not(feature = "no_a"))"#,
+                r#"feature = "no_a""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let v = variants(&[&[], &["-DBIG"]]);
+        let a = "\
+#![allow(dead_code)]
+#![feature(core_intrinsics)]
+// A comment on `x`.
+pub static x: i32 = 1;
+/// The size.
+pub static size: i32 = 2;
+pub fn f() {}
+";
+        let b = "\
+#![allow(dead_code, unused_mut)]
+#![feature(core_intrinsics)]
+// A comment on `x`.
+pub static x: i32 = 1;
+/// The size.
+pub static size: i32 = 4;
+";
+        let expected = r#"#![allow(dead_code, unused_mut)]
+#![feature(core_intrinsics)]
+// A comment on `x`.
+pub static x: i32 = 1;
+#[cfg(not(feature = "big"))]
+/// The size.
+pub static size: i32 = 2;
+#[cfg(not(feature = "big"))]
+pub fn f() {}
+#[cfg(feature = "big")]
+/// The size.
+pub static size: i32 = 4;
+"#;
+        assert_eq!(merge(&v, &[a, b]), expected);
+    }
+
+    #[test]
+    fn test_merge_blocks() {
+        let v = variants(&[&[], &["-DBIG"]]);
+        let a = "\
+extern \"C\" {
+    // Shared.
+    fn g();
+    fn h(x: i32);
+}
+pub mod m {
+    pub const N: usize = 1;
+}
+";
+        let b = "\
+extern \"C\" {
+    // Shared.
+    fn g();
+    fn h(x: i64);
+}
+pub mod m {
+    pub const N: usize = 1;
+    pub const M: usize = 2;
+}
+";
+        let expected = r#"extern "C" {
+    // Shared.
+    fn g();
+    #[cfg(not(feature = "big"))]
+    fn h(x: i32);
+    #[cfg(feature = "big")]
+    fn h(x: i64);
+}
+pub mod m {
+    pub const N: usize = 1;
+    #[cfg(feature = "big")]
+    pub const M: usize = 2;
+}
+"#;
+        assert_eq!(merge(&v, &[a, b]), expected);
+    }
+}
@@ -0,0 +1,556 @@
+#![no_std]
+//! The x87 80-bit extended precision floating point type, which is C's `long double` on x86 and
+//! x86_64. [`f80`] has the same size, alignment and representation as `long double`, so it can be
+//! shared with C through pointers and in structs. Arithmetic is done by the x87 FPU, so results
+//! are rounded exactly like in C.
+//!
+//! `long double` arguments and return values are passed differently from any Rust type, so
+//! `f80` can't be passed by value to or from C functions.
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+compile_error!("c2rust-x87 is only supported on x86 and x86_64");
+
+use core::arch::asm;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// An x87 extended precision float: a 64-bit significand with an explicit integer bit, a 15-bit
+/// exponent and a sign bit, followed by padding up to the size of `long double`.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy)]
+#[cfg_attr(target_arch = "x86_64", repr(C, align(16)))]
+#[cfg_attr(target_arch = "x86", repr(C, align(4)))]
+pub struct f80 {
+    bytes: [u8; 10],
+    #[cfg(target_arch = "x86_64")]
+    _padding: [u8; 6],
+    #[cfg(target_arch = "x86")]
+    _padding: [u8; 2],
+}
+
+/// The x87 control word bits that select rounding towards zero, which is how C converts floats
+/// to integers.
+const ROUND_TOWARD_ZERO: u16 = 0x0c00;
+
+impl f80 {
+    pub const ZERO: f80 = f80::from_parts(false, 0, 0);
+    pub const ONE: f80 = f80::from_parts(false, 0x3fff, 1 << 63);
+    pub const INFINITY: f80 = f80::from_parts(false, 0x7fff, 1 << 63);
+    pub const NEG_INFINITY: f80 = f80::from_parts(true, 0x7fff, 1 << 63);
+    pub const NAN: f80 = f80::from_parts(false, 0x7fff, 3 << 62);
+    /// The largest finite value.
+    pub const MAX: f80 = f80::from_parts(false, 0x7ffe, u64::MAX);
+    /// The smallest finite value.
+    pub const MIN: f80 = f80::from_parts(true, 0x7ffe, u64::MAX);
+
+    const fn from_parts(sign: bool, exponent: u16, significand: u64) -> f80 {
+        let sign_exponent = (sign as u16) << 15 | exponent;
+        let significand = significand.to_le_bytes();
+        let sign_exponent = sign_exponent.to_le_bytes();
+        let mut bytes = [0; 10];
+        let mut i = 0;
+        while i < 8 {
+            bytes[i] = significand[i];
+            i += 1;
+        }
+        bytes[8] = sign_exponent[0];
+        bytes[9] = sign_exponent[1];
+        f80 {
+            bytes,
+            #[cfg(target_arch = "x86_64")]
+            _padding: [0; 6],
+            #[cfg(target_arch = "x86")]
+            _padding: [0; 2],
+        }
+    }
+
+    /// Convert any number that converts to `f80`, like the `f128` crate's constructor.
+    pub fn new<T: Into<f80>>(x: T) -> f80 {
+        x.into()
+    }
+
+    /// The raw representation: the significand in the low 64 bits, then the exponent and the
+    /// sign.
+    pub fn to_bits(self) -> u128 {
+        let mut bits = [0; 16];
+        bits[..10].copy_from_slice(&self.bytes);
+        u128::from_le_bytes(bits)
+    }
+
+    pub fn from_bits(bits: u128) -> f80 {
+        let bytes = bits.to_le_bytes();
+        f80::from_parts(
+            bytes[9] & 0x80 != 0,
+            u16::from_le_bytes([bytes[8], bytes[9] & 0x7f]),
+            bits as u64,
+        )
+    }
+
+    fn exponent(self) -> u16 {
+        u16::from_le_bytes([self.bytes[8], self.bytes[9]]) & 0x7fff
+    }
+
+    pub fn is_sign_negative(self) -> bool {
+        self.bytes[9] & 0x80 != 0
+    }
+
+    pub fn is_sign_positive(self) -> bool {
+        !self.is_sign_negative()
+    }
+
+    pub fn is_nan(self) -> bool {
+        self.exponent() == 0x7fff && self.to_bits() as u64 & !(1 << 63) != 0
+    }
+
+    pub fn is_infinite(self) -> bool {
+        self.exponent() == 0x7fff && self.to_bits() as u64 == 1 << 63
+    }
+
+    pub fn is_finite(self) -> bool {
+        self.exponent() != 0x7fff
+    }
+
+    pub fn abs(mut self) -> f80 {
+        self.bytes[9] &= 0x7f;
+        self
+    }
+
+    pub fn to_f32(self) -> Option<f32> {
+        let mut out = 0f32;
+        // SAFETY: the x87 stack is empty, and left empty.
+        unsafe {
+            asm!(
+                "fld tbyte ptr [{x}]",
+                "fstp dword ptr [{out}]",
+                x = in(reg) &self,
+                out = in(reg) &mut out,
+                out("st(0)") _,
+                options(nostack),
+            );
+        }
+        Some(out)
+    }
+
+    pub fn to_f64(self) -> Option<f64> {
+        let mut out = 0f64;
+        // SAFETY: the x87 stack is empty, and left empty.
+        unsafe {
+            asm!(
+                "fld tbyte ptr [{x}]",
+                "fstp qword ptr [{out}]",
+                x = in(reg) &self,
+                out = in(reg) &mut out,
+                out("st(0)") _,
+                options(nostack),
+            );
+        }
+        Some(out)
+    }
+
+    /// Truncate to an `i64`, or `None` if the result doesn't fit.
+    pub fn to_i64(self) -> Option<i64> {
+        // -2^63 is the smallest `i64`, and 2^63 is one more than the largest.
+        let bound = f80::from_parts(false, 0x3fff + 63, 1 << 63);
+        if self.is_nan() || self >= bound || self < -bound {
+            return None;
+        }
+        let mut out = 0i64;
+        // The saved control word, and the one that rounds toward zero.
+        let mut control = [0u16; 2];
+        // SAFETY: the x87 stack is empty, and left empty. The control word is restored.
+        unsafe {
+            asm!(
+                "fnstcw word ptr [{control}]",
+                "mov {tmp:x}, word ptr [{control}]",
+                "or {tmp:x}, {round:x}",
+                "mov word ptr [{control} + 2], {tmp:x}",
+                "fldcw word ptr [{control} + 2]",
+                "fld tbyte ptr [{x}]",
+                "fistp qword ptr [{out}]",
+                "fldcw word ptr [{control}]",
+                x = in(reg) &self,
+                out = in(reg) &mut out,
+                control = in(reg) &mut control,
+                round = in(reg) ROUND_TOWARD_ZERO,
+                tmp = out(reg) _,
+                out("st(0)") _,
+                options(nostack),
+            );
+        }
+        Some(out)
+    }
+
+    /// Truncate to a `u64`, or `None` if the result doesn't fit.
+    pub fn to_u64(self) -> Option<u64> {
+        let bound = f80::from_parts(false, 0x3fff + 63, 1 << 63);
+        if self >= bound {
+            // Values from 2^63 have no fractional part, so only the top bit has to be split off.
+            let low = (self - bound).to_i64()?;
+            return Some(low as u64 | 1 << 63);
+        }
+        self.to_i64().and_then(|x| u64::try_from(x).ok())
+    }
+
+    pub fn to_i8(self) -> Option<i8> {
+        self.to_i64().and_then(|x| x.try_into().ok())
+    }
+
+    pub fn to_i16(self) -> Option<i16> {
+        self.to_i64().and_then(|x| x.try_into().ok())
+    }
+
+    pub fn to_i32(self) -> Option<i32> {
+        self.to_i64().and_then(|x| x.try_into().ok())
+    }
+
+    pub fn to_i128(self) -> Option<i128> {
+        if let Some(x) = self.to_i64() {
+            return Some(x.into());
+        }
+        let magnitude = self.large_magnitude()?;
+        if self.is_sign_negative() {
+            // `i128::MIN` has no positive counterpart.
+            (magnitude <= 1 << 127).then(|| (magnitude as i128).wrapping_neg())
+        } else {
+            magnitude.try_into().ok()
+        }
+    }
+
+    pub fn to_u8(self) -> Option<u8> {
+        self.to_u64().and_then(|x| x.try_into().ok())
+    }
+
+    pub fn to_u16(self) -> Option<u16> {
+        self.to_u64().and_then(|x| x.try_into().ok())
+    }
+
+    pub fn to_u32(self) -> Option<u32> {
+        self.to_u64().and_then(|x| x.try_into().ok())
+    }
+
+    pub fn to_u128(self) -> Option<u128> {
+        if let Some(x) = self.to_u64() {
+            return Some(x.into());
+        }
+        if self.is_sign_negative() {
+            return None;
+        }
+        self.large_magnitude()
+    }
+
+    /// The absolute value of a finite value of at least 2^63, which is always an integer.
+    fn large_magnitude(self) -> Option<u128> {
+        let exponent = self.exponent();
+        if !self.is_finite() || !(0x3fff + 63..=0x3fff + 127).contains(&exponent) {
+            return None;
+        }
+        Some(u128::from(self.to_bits() as u64) << (exponent - 0x3fff - 63))
+    }
+
+    fn from_i64(x: i64) -> f80 {
+        let mut out = f80::ZERO;
+        // SAFETY: the x87 stack is empty, and left empty.
+        unsafe {
+            asm!(
+                "fild qword ptr [{x}]",
+                "fstp tbyte ptr [{out}]",
+                x = in(reg) &x,
+                out = in(reg) &mut out,
+                out("st(0)") _,
+                options(nostack),
+            );
+        }
+        out
+    }
+
+    fn from_u64(x: u64) -> f80 {
+        if x >> 63 == 0 {
+            return f80::from_i64(x as i64);
+        }
+        // Every `u64` is exact in an `f80`, so this doesn't round.
+        f80::from_i64((x & !(1 << 63)) as i64) + f80::from_parts(false, 0x3fff + 63, 1 << 63)
+    }
+
+    fn compare(self, other: f80) -> Option<Ordering> {
+        let (less, equal, unordered): (u8, u8, u8);
+        // SAFETY: the x87 stack is empty, and left empty.
+        unsafe {
+            asm!(
+                "fld tbyte ptr [{b}]",
+                "fld tbyte ptr [{a}]",
+                "fucomip st, st(1)",
+                "fstp st(0)",
+                "setb {less}",
+                "sete {equal}",
+                "setp {unordered}",
+                a = in(reg) &self,
+                b = in(reg) &other,
+                less = out(reg_byte) less,
+                equal = out(reg_byte) equal,
+                unordered = out(reg_byte) unordered,
+                out("st(0)") _,
+                out("st(1)") _,
+                options(nostack),
+            );
+        }
+        match (unordered, less, equal) {
+            (0, 1, _) => Some(Ordering::Less),
+            (0, _, 1) => Some(Ordering::Equal),
+            (0, _, _) => Some(Ordering::Greater),
+            _ => None,
+        }
+    }
+}
+
+/// Implement a binary operator with an x87 instruction that computes `st(1) op st(0)` and pops.
+macro_rules! impl_binop {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign_method:ident, $insn:literal) => {
+        impl $trait for f80 {
+            type Output = f80;
+
+            fn $method(self, rhs: f80) -> f80 {
+                let mut out = f80::ZERO;
+                // SAFETY: the x87 stack is empty, and left empty.
+                unsafe {
+                    asm!(
+                        "fld tbyte ptr [{a}]",
+                        "fld tbyte ptr [{b}]",
+                        concat!($insn, " st(1), st"),
+                        "fstp tbyte ptr [{out}]",
+                        a = in(reg) &self,
+                        b = in(reg) &rhs,
+                        out = in(reg) &mut out,
+                        out("st(0)") _,
+                        out("st(1)") _,
+                        options(nostack),
+                    );
+                }
+                out
+            }
+        }
+
+        impl $assign_trait for f80 {
+            fn $assign_method(&mut self, rhs: f80) {
+                *self = $trait::$method(*self, rhs);
+            }
+        }
+    };
+}
+
+impl_binop!(Add, add, AddAssign, add_assign, "faddp");
+impl_binop!(Sub, sub, SubAssign, sub_assign, "fsubp");
+impl_binop!(Mul, mul, MulAssign, mul_assign, "fmulp");
+impl_binop!(Div, div, DivAssign, div_assign, "fdivp");
+
+impl Neg for f80 {
+    type Output = f80;
+
+    fn neg(mut self) -> f80 {
+        self.bytes[9] ^= 0x80;
+        self
+    }
+}
+
+impl PartialEq for f80 {
+    fn eq(&self, other: &f80) -> bool {
+        self.compare(*other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for f80 {
+    fn partial_cmp(&self, other: &f80) -> Option<Ordering> {
+        self.compare(*other)
+    }
+}
+
+impl Default for f80 {
+    fn default() -> f80 {
+        f80::ZERO
+    }
+}
+
+impl fmt::Debug for f80 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "f80({:?}, {:#022x})",
+            self.to_f64().unwrap(),
+            self.to_bits()
+        )
+    }
+}
+
+impl fmt::Display for f80 {
+    /// Displays the nearest `f64`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.to_f64().unwrap(), f)
+    }
+}
+
+impl From<f32> for f80 {
+    fn from(x: f32) -> f80 {
+        let mut out = f80::ZERO;
+        // SAFETY: the x87 stack is empty, and left empty.
+        unsafe {
+            asm!(
+                "fld dword ptr [{x}]",
+                "fstp tbyte ptr [{out}]",
+                x = in(reg) &x,
+                out = in(reg) &mut out,
+                out("st(0)") _,
+                options(nostack),
+            );
+        }
+        out
+    }
+}
+
+impl From<f64> for f80 {
+    fn from(x: f64) -> f80 {
+        let mut out = f80::ZERO;
+        // SAFETY: the x87 stack is empty, and left empty.
+        unsafe {
+            asm!(
+                "fld qword ptr [{x}]",
+                "fstp tbyte ptr [{out}]",
+                x = in(reg) &x,
+                out = in(reg) &mut out,
+                out("st(0)") _,
+                options(nostack),
+            );
+        }
+        out
+    }
+}
+
+macro_rules! impl_from_int {
+    ($($ty:ty => $via:ident),*) => {
+        $(
+            impl From<$ty> for f80 {
+                fn from(x: $ty) -> f80 {
+                    f80::$via(x.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_int!(
+    i8 => from_i64, i16 => from_i64, i32 => from_i64, i64 => from_i64,
+    u8 => from_u64, u16 => from_u64, u32 => from_u64, u64 => from_u64
+);
+
+/// 2^64, to split 128-bit integers into halves that convert exactly.
+const TWO_POW_64: f80 = f80::from_parts(false, 0x3fff + 64, 1 << 63);
+
+impl From<i128> for f80 {
+    fn from(x: i128) -> f80 {
+        // Scaling by 2^64 is exact, so only the sum rounds.
+        f80::from_i64((x >> 64) as i64) * TWO_POW_64 + f80::from_u64(x as u64)
+    }
+}
+
+impl From<u128> for f80 {
+    fn from(x: u128) -> f80 {
+        f80::from_u64((x >> 64) as u64) * TWO_POW_64 + f80::from_u64(x as u64)
+    }
+}
+
+impl From<bool> for f80 {
+    fn from(x: bool) -> f80 {
+        f80::from_i64(x as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(
+            (core::mem::size_of::<f80>(), core::mem::align_of::<f80>()),
+            (16, 16)
+        );
+        #[cfg(target_arch = "x86")]
+        assert_eq!(
+            (core::mem::size_of::<f80>(), core::mem::align_of::<f80>()),
+            (12, 4)
+        );
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(f80::ONE, f80::from(1.0f64));
+        assert_eq!(f80::ZERO, f80::from(0i32));
+        assert_eq!(f80::INFINITY, f80::from(f64::INFINITY));
+        assert_eq!(f80::NEG_INFINITY, -f80::INFINITY);
+        assert!(f80::NAN.is_nan());
+        assert!(f80::NAN != f80::NAN);
+        assert!(f80::MAX.is_finite());
+        assert_eq!(f80::MIN, -f80::MAX);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let three = f80::new(3);
+        let four = f80::new(4.0f32);
+        assert_eq!(three + four, f80::new(7));
+        assert_eq!(three - four, f80::new(-1));
+        assert_eq!(three * four, f80::new(12));
+        assert_eq!(three / four, f80::new(0.75));
+        assert_eq!(three.partial_cmp(&four), Some(Ordering::Less));
+        assert_eq!(four.partial_cmp(&three), Some(Ordering::Greater));
+        assert_eq!(three.partial_cmp(&three), Some(Ordering::Equal));
+        assert_eq!(f80::NAN.partial_cmp(&three), None);
+
+        let mut x = f80::ONE;
+        x += three;
+        x *= four;
+        x -= f80::ONE;
+        x /= three;
+        assert_eq!(x, f80::new(5));
+    }
+
+    #[test]
+    fn extended_precision() {
+        // 1 + 2^-60 is exact in an `f80`, but not in an `f64`.
+        let tiny = f80::ONE / f80::new(1u64 << 60);
+        let x = f80::ONE + tiny;
+        assert!(x > f80::ONE);
+        assert_eq!(x.to_f64(), Some(1.0));
+        assert_eq!(x - f80::ONE, tiny);
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(f80::new(-2.75f64).to_i32(), Some(-2));
+        assert_eq!(f80::new(2.75f64).to_u8(), Some(2));
+        assert_eq!(f80::new(-1).to_u32(), None);
+        assert_eq!(f80::new(300).to_u8(), None);
+        assert_eq!(f80::new(u64::MAX).to_u64(), Some(u64::MAX));
+        assert_eq!(f80::new(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(f80::new(1u64 << 63).to_i64(), None);
+        assert_eq!(f80::NAN.to_i64(), None);
+        assert_eq!(f80::new(0.1f32).to_f32(), Some(0.1f32));
+        assert_eq!(f80::new(0.1f64).to_f64(), Some(0.1f64));
+        assert_eq!(f80::from_bits(f80::new(0.1f64).to_bits()), f80::new(0.1f64));
+        assert!(f80::new(-0.0f64).is_sign_negative());
+    }
+
+    #[test]
+    fn wide_integers() {
+        for x in [0, 1 << 64, -(1 << 100), i128::MIN, (1 << 126) + (1 << 70)] {
+            assert_eq!(f80::new(x).to_i128(), Some(x));
+        }
+        for x in [u64::MAX as u128 + 1, 1 << 127, 3 << 120] {
+            assert_eq!(f80::new(x).to_u128(), Some(x));
+        }
+        assert_eq!(f80::new(u128::MAX).to_u128(), None);
+        assert_eq!(f80::new(1u128 << 127).to_i128(), None);
+        assert_eq!(f80::new(-(1i128 << 70)).to_u128(), None);
+        assert_eq!(f80::new(-2.5f64).to_i128(), Some(-2));
+    }
+}
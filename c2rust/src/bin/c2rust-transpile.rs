@@ -1,10 +1,20 @@
-use clap::{load_yaml, App};
+use clap::{load_yaml, App, ArgMatches};
 use regex::Regex;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use c2rust_transpile::{Diagnostic, ReplaceMode, TranspilerConfig};
+use c2rust_transpile::config::{ConfigFile, DEFAULT_CONFIG_FILE};
+use c2rust_transpile::{Diagnostic, LongDouble, ReplaceMode, TranspilerConfig};
+
+/// The value of `name` if it was given on the command line, ignoring its default.
+fn explicit_value<'a>(matches: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    if matches.occurrences_of(name) > 0 {
+        matches.value_of(name)
+    } else {
+        None
+    }
+}
 
 fn main() {
     let yaml = load_yaml!("../transpile.yaml");
@@ -18,25 +28,57 @@ fn main() {
             cc_json_path.display()
         )
     });
-    let extra_args: Vec<&str> = match matches.values_of("extra-clang-args") {
-        Some(args) => args.collect(),
-        None => Vec::new(),
+
+    // Options from the config file are used unless they're given on the command line.
+    let config_path = match matches.value_of("config") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(cc_json_path.with_file_name(DEFAULT_CONFIG_FILE)).filter(|p| p.exists()),
+    };
+    let config = match config_path {
+        Some(path) => ConfigFile::load(&path).unwrap_or_else(|e| panic!("{}", e)),
+        None => ConfigFile::default(),
+    };
+    // A flag, or its negation, overrides the config file, and the last one given wins.
+    let flag = |name: &str, value: Option<bool>| {
+        if matches.is_present(name) {
+            true
+        } else if matches.is_present(&format!("no-{}", name)) {
+            false
+        } else {
+            value.unwrap_or(false)
+        }
     };
+    // A flag that disables an option that is on by default, and the flag that enables it again.
+    let no_flag = |name: &str, yes_name: &str, value: Option<bool>| {
+        if matches.is_present(name) {
+            false
+        } else if matches.is_present(yes_name) {
+            true
+        } else {
+            value.unwrap_or(true)
+        }
+    };
+    let defaults = TranspilerConfig::default();
+
+    let mut extra_args: Vec<&str> = config.clang_args.iter().map(AsRef::as_ref).collect();
+    extra_args.extend(matches.values_of("extra-clang-args").unwrap_or_default());
 
-    let enabled_warnings: HashSet<Diagnostic> = matches
+    let mut enabled_warnings: HashSet<Diagnostic> = matches
         .values_of("warn")
         .unwrap_or_default()
         .map(|s| Diagnostic::from_str(s).unwrap())
         .collect();
+    enabled_warnings.extend(config.enabled_warnings.unwrap_or_default());
 
-    let log_level = match matches.value_of("log-level") {
+    let log_level = match explicit_value(&matches, "log-level") {
         Some("off") => log::LevelFilter::Off,
         Some("error") => log::LevelFilter::Error,
         Some("warn") => log::LevelFilter::Warn,
         Some("info") => log::LevelFilter::Info,
         Some("debug") => log::LevelFilter::Debug,
         Some("trace") => log::LevelFilter::Trace,
-        _ => panic!("Invalid log level"),
+        Some(_) => panic!("Invalid log level"),
+        None => config.log_level.unwrap_or(log::LevelFilter::Warn),
     };
 
     let mut tcfg = TranspilerConfig {
@@ -50,19 +92,26 @@ fn main() {
         debug_ast_exporter: matches.is_present("debug-ast-exporter"),
         verbose: matches.is_present("verbose"),
 
-        incremental_relooper: !matches.is_present("no-incremental-relooper"),
-        fail_on_error: matches.is_present("fail-on-error"),
-        fail_on_multiple: matches.is_present("fail-on-multiple"),
+        incremental_relooper: no_flag(
+            "no-incremental-relooper",
+            "incremental-relooper",
+            config.incremental_relooper,
+        ),
+        fail_on_error: flag("fail-on-error", config.fail_on_error),
+        fail_on_multiple: flag("fail-on-multiple", config.fail_on_multiple),
         filter: {
             if matches.is_present("filter") {
                 let filter = matches.value_of("filter").unwrap();
                 Some(Regex::new(filter).unwrap())
             } else {
-                None
+                config.filter
             }
         },
-        debug_relooper_labels: matches.is_present("debug-labels"),
-        prefix_function_names: matches.value_of("prefix-function-names").map(String::from),
+        debug_relooper_labels: flag("debug-labels", config.debug_relooper_labels),
+        prefix_function_names: matches
+            .value_of("prefix-function-names")
+            .map(String::from)
+            .or(config.prefix_function_names),
 
         // We used to guard asm translation with a command-line
         // option. Defaulting to enabled now, can add an option to disable if
@@ -73,36 +122,70 @@ fn main() {
         // support landed. We may still want to disable this option to target
         // stable rust output.
         translate_valist: true,
+        long_double: match explicit_value(&matches, "long-double") {
+            Some(long_double) => long_double.parse().expect("Invalid --long-double"),
+            None => config.long_double.unwrap_or(LongDouble::F128),
+        },
 
-        translate_const_macros: matches.is_present("translate-const-macros"),
-        translate_fn_macros: matches.is_present("translate-fn-macros"),
-        disable_refactoring: matches.is_present("disable-refactoring"),
-        preserve_unused_functions: matches.is_present("preserve-unused-functions"),
+        translate_const_macros: flag("translate-const-macros", config.translate_const_macros),
+        translate_fn_macros: flag("translate-fn-macros", config.translate_fn_macros),
+        disable_refactoring: flag("disable-refactoring", config.disable_refactoring),
+        preserve_unused_functions: flag(
+            "preserve-unused-functions",
+            config.preserve_unused_functions,
+        ),
 
-        use_c_loop_info: !matches.is_present("ignore-c-loop-info"),
-        use_c_multiple_info: !matches.is_present("ignore-c-multiple-info"),
-        simplify_structures: !matches.is_present("no-simplify-structures"),
-        overwrite_existing: matches.is_present("overwrite-existing"),
-        reduce_type_annotations: matches.is_present("reduce-type-annotations"),
-        reorganize_definitions: matches.is_present("reorganize-definitions"),
-        emit_modules: matches.is_present("emit-modules"),
-        emit_build_files: matches.is_present("emit-build-files"),
-        output_dir: matches.value_of("output-dir").map(PathBuf::from),
+        use_c_loop_info: no_flag(
+            "ignore-c-loop-info",
+            "use-c-loop-info",
+            config.use_c_loop_info,
+        ),
+        use_c_multiple_info: no_flag(
+            "ignore-c-multiple-info",
+            "use-c-multiple-info",
+            config.use_c_multiple_info,
+        ),
+        simplify_structures: no_flag(
+            "no-simplify-structures",
+            "simplify-structures",
+            config.simplify_structures,
+        ),
+        overwrite_existing: flag("overwrite-existing", config.overwrite_existing),
+        cache: flag("cache", config.cache),
+        reduce_type_annotations: flag("reduce-type-annotations", config.reduce_type_annotations),
+        reorganize_definitions: flag("reorganize-definitions", config.reorganize_definitions),
+        emit_modules: flag("emit-modules", config.emit_modules),
+        emit_build_files: flag("emit-build-files", config.emit_build_files),
+        output_dir: matches
+            .value_of("output-dir")
+            .map(PathBuf::from)
+            .or(config.output_dir),
+        report: matches
+            .value_of("report")
+            .map(PathBuf::from)
+            .or(config.report),
         binaries: matches
             .values_of("binary")
             .map(|values| values.map(String::from).collect())
+            .or(config.binaries)
             .unwrap_or_default(),
         panic_on_translator_failure: {
-            match matches.value_of("invalid-code") {
+            match explicit_value(&matches, "invalid-code") {
                 Some("panic") => true,
                 Some("compile_error") => false,
-                _ => panic!("Invalid option"),
+                Some(_) => panic!("Invalid option"),
+                None => config.panic_on_translator_failure.unwrap_or(false),
             }
         },
         replace_unsupported_decls: ReplaceMode::Extern,
-        emit_no_std: matches.is_present("emit-no-std"),
+        emit_no_std: flag("emit-no-std", config.emit_no_std),
         enabled_warnings,
         log_level,
+        jobs: match matches.value_of("jobs") {
+            Some(jobs) => jobs.parse().expect("--jobs must be a number"),
+            None => config.jobs.unwrap_or(defaults.jobs),
+        },
+        overrides: config.overrides,
     };
     // binaries imply emit-build-files
     if !tcfg.binaries.is_empty() {
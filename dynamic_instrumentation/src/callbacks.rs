@@ -11,13 +11,17 @@ use rustc_middle::ty::query::{ExternProviders, Providers};
 use rustc_middle::ty::WithOptConstParam;
 use rustc_session::Session;
 use rustc_span::def_id::LocalDefId;
-use rustc_span::symbol::Ident;
+use rustc_span::symbol::{Ident, Symbol};
 use rustc_span::DUMMY_SP;
+use std::env;
 
+use crate::filter::{InstrumentFilter, INSTRUMENT_ONLY_VAR, SKIP_VAR};
 use crate::instrument::Instrumenter;
 
 pub static INSTRUMENTER: Lazy<Instrumenter> = Lazy::new(Instrumenter::new);
 
+pub static INSTRUMENT_FILTER: Lazy<InstrumentFilter> = Lazy::new(InstrumentFilter::from_env);
+
 pub struct MirTransformCallbacks;
 
 impl rustc_driver::Callbacks for MirTransformCallbacks {
@@ -28,9 +32,17 @@ impl rustc_driver::Callbacks for MirTransformCallbacks {
 
     fn after_parsing<'tcx>(
         &mut self,
-        _compiler: &Compiler,
+        compiler: &Compiler,
         queries: &'tcx Queries<'tcx>,
     ) -> Compilation {
+        // Record the filter like an `env!`, so that `cargo` rebuilds the crate when it changes.
+        let mut env_depinfo = compiler.session().parse_sess.env_depinfo.borrow_mut();
+        for var in [INSTRUMENT_ONLY_VAR, SKIP_VAR] {
+            let value = env::var(var).ok().map(|value| Symbol::intern(&value));
+            env_depinfo.insert((Symbol::intern(var), value));
+        }
+        drop(env_depinfo);
+
         let parse = queries.parse().unwrap();
         let mut parse = parse.peek_mut();
         parse.items.push(P(Item {
@@ -65,7 +77,14 @@ fn override_queries(
         let body_did = def.did.to_def_id();
         let fn_ty = tcx.type_of(body_did);
         if fn_ty.is_fn() && !tcx.is_const_fn(body_did) && !tcx.is_static(body_did) {
-            INSTRUMENTER.instrument_fn(tcx, &mut mir, body_did);
+            let is_main = tcx.entry_fn(()).map(|(def_id, _)| def_id) == Some(body_did);
+            if INSTRUMENT_FILTER.should_instrument(tcx, body_did) {
+                INSTRUMENTER.instrument_fn(tcx, &mut mir, body_did);
+            } else if is_main {
+                INSTRUMENTER.instrument_entry_fn(tcx, &mut mir);
+            } else {
+                return tcx.alloc_steal_mir(mir);
+            }
 
             Validator {
                 when: "After dynamic instrumentation".to_string(),
@@ -0,0 +1,163 @@
+use std::env;
+
+use rustc_middle::ty::TyCtxt;
+use rustc_span::def_id::DefId;
+
+/// The `$`[`mod@env`] var the [`cargo_wrapper`](crate::cargo_wrapper) passes `--instrument-only`
+/// patterns in, one per line.
+pub const INSTRUMENT_ONLY_VAR: &str = "C2RUST_INSTRUMENT_ONLY";
+
+/// The `$`[`mod@env`] var the [`cargo_wrapper`](crate::cargo_wrapper) passes `--skip` patterns in,
+/// one per line.
+pub const SKIP_VAR: &str = "C2RUST_INSTRUMENT_SKIP";
+
+/// A pattern for def paths, like `pointers::simple` or `buffer::*`, as printed by `rustc`.
+///
+/// A pattern matches a def path if it is the same as the path
+/// or as a prefix of it ending at a `::`, so a module matches everything in it.
+/// A `*` matches anything within a single path segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPattern {
+    segments: Vec<String>,
+}
+
+impl PathPattern {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            segments: split_path(pattern).map(String::from).collect(),
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let mut path = split_path(path);
+        self.segments.iter().all(|pattern| {
+            path.next()
+                .map_or(false, |segment| glob_matches(pattern, segment))
+        })
+    }
+}
+
+/// The segments of a def path, without the `crate::` prefix `rustc` sometimes prints.
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    let path = path.strip_prefix("crate::").unwrap_or(path);
+    path.split("::")
+}
+
+/// Whether `text` matches `pattern`, where `*` in `pattern` matches any substring.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let text = match text.strip_prefix(prefix) {
+                Some(text) => text,
+                None => return false,
+            };
+            (0..=text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .any(|i| glob_matches(rest, &text[i..]))
+        }
+    }
+}
+
+/// Which functions to instrument, as selected by `--instrument-only` and `--skip`.
+///
+/// Functions that aren't instrumented don't produce any events,
+/// and pointers passed to them show up as [`Escape`] nodes in the PDG.
+///
+/// [`Escape`]: c2rust_analysis_rt::pdg_file::NodeKind::Escape
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InstrumentFilter {
+    /// If not empty, only instrument functions matching one of these.
+    pub only: Vec<PathPattern>,
+
+    /// Don't instrument functions matching any of these, even if they match [`Self::only`].
+    pub skip: Vec<PathPattern>,
+}
+
+impl InstrumentFilter {
+    /// Read the filter the [`cargo_wrapper`](crate::cargo_wrapper) passed in
+    /// [`INSTRUMENT_ONLY_VAR`] and [`SKIP_VAR`].
+    pub fn from_env() -> Self {
+        let patterns = |var| {
+            env::var(var)
+                .unwrap_or_default()
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(PathPattern::new)
+                .collect()
+        };
+        Self {
+            only: patterns(INSTRUMENT_ONLY_VAR),
+            skip: patterns(SKIP_VAR),
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let any_matches = |patterns: &[PathPattern]| patterns.iter().any(|p| p.matches(path));
+        (self.only.is_empty() || any_matches(&self.only)) && !any_matches(&self.skip)
+    }
+
+    pub fn should_instrument(&self, tcx: TyCtxt, did: DefId) -> bool {
+        self.matches(&tcx.def_path_str(did))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_matches("simple", "simple"));
+        assert!(!glob_matches("simple", "simple1"));
+        assert!(!glob_matches("simple1", "simple"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*", "simple"));
+        assert!(glob_matches("test_*", "test_arg"));
+        assert!(glob_matches("test_*", "test_"));
+        assert!(!glob_matches("test_*", "simple"));
+        assert!(glob_matches("*_ref", "test_shared_ref"));
+        assert!(!glob_matches("*_ref", "test_shared_ref_field"));
+        assert!(glob_matches("test_*_ref", "test_unique_ref"));
+        assert!(glob_matches("a*b*c", "axxbyyc"));
+        assert!(glob_matches("a*b*c", "abbc"));
+        assert!(!glob_matches("a*b*c", "acb"));
+        assert!(glob_matches("é*", "éa"));
+        assert!(glob_matches("*é", "aé"));
+    }
+
+    #[test]
+    fn path_pattern() {
+        let matches = |pattern, path| PathPattern::new(pattern).matches(path);
+        assert!(matches("pointers::simple", "pointers::simple"));
+        assert!(!matches("pointers::simple", "pointers::simple1"));
+        assert!(!matches("pointers::simple", "pointers"));
+        // A module matches everything in it, but not other modules starting with its name.
+        assert!(matches("pointers", "pointers::simple"));
+        assert!(matches("pointers", "pointers::inner::simple"));
+        assert!(!matches("pointers", "pointers2::simple"));
+        // `*` doesn't match across `::`.
+        assert!(matches("pointers::*", "pointers::simple"));
+        assert!(matches("*::simple", "pointers::simple"));
+        assert!(!matches("*::simple", "pointers::inner::simple"));
+        assert!(matches("pointers::test_*", "pointers::test_arg"));
+        assert!(!matches("pointers::test_*", "pointers::simple"));
+        // `rustc` sometimes prints a `crate::` prefix.
+        assert!(matches("crate::pointers", "pointers::simple"));
+        assert!(matches("pointers", "crate::pointers::simple"));
+    }
+
+    #[test]
+    fn filter() {
+        let filter = |only: &[&str], skip: &[&str]| InstrumentFilter {
+            only: only.iter().copied().map(PathPattern::new).collect(),
+            skip: skip.iter().copied().map(PathPattern::new).collect(),
+        };
+        assert!(filter(&[], &[]).matches("pointers::simple"));
+        assert!(filter(&["pointers"], &[]).matches("pointers::simple"));
+        assert!(!filter(&["stdlib"], &[]).matches("pointers::simple"));
+        assert!(!filter(&[], &["pointers::simple"]).matches("pointers::simple"));
+        assert!(!filter(&["pointers"], &["pointers::simple"]).matches("pointers::simple"));
+        assert!(filter(&["pointers"], &["pointers::simple"]).matches("pointers::simple1"));
+    }
+}
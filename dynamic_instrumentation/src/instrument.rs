@@ -17,8 +17,9 @@ use rustc_middle::mir::{
 use rustc_middle::ty::{self, Ty, TyCtxt};
 use rustc_span::def_id::{DefId, DefPathHash};
 use rustc_span::DUMMY_SP;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::io::Write;
+use std::mem;
 use std::path::Path;
 use std::sync::Mutex;
 
@@ -36,6 +37,7 @@ use crate::util::Convert;
 pub struct Instrumenter {
     mir_locs: Mutex<IndexSet<MirLoc>>,
     functions: Mutex<HashMap<FuncId, String>>,
+    instrumented: Mutex<BTreeSet<FuncId>>,
 }
 
 impl Instrumenter {
@@ -61,18 +63,36 @@ impl Instrumenter {
         debug!("Instrumenting function {}", function_name);
 
         self.add_fn(body_did, tcx);
+        self.instrumented
+            .lock()
+            .unwrap()
+            .insert(FuncId(tcx.def_path_hash(body_did).convert()));
         debug!("Body before instrumentation: {:#?}", body);
         instrument_body(self, tcx, body, body_did);
         debug!("Body after instrumentation: {:#?}", body);
     }
 
+    /// Only initialize and finalize the runtime in `main`, without instrumenting the rest of it.
+    ///
+    /// This is for when `main` itself is filtered out of instrumentation,
+    /// since the runtime would otherwise drop every event.
+    pub fn instrument_entry_fn<'tcx>(&self, tcx: TyCtxt<'tcx>, body: &mut Body<'tcx>) {
+        debug!("Only initializing and finalizing the runtime in main");
+        instrument_entry_fn(tcx, Hooks::new(tcx), body);
+    }
+
     /// Finish instrumentation and write out metadata to `metadata_file_path`.
     pub fn finalize(&self, metadata_path: &Path) -> anyhow::Result<()> {
         let mut locs = self.mir_locs.lock().unwrap();
         let mut functions = self.functions.lock().unwrap();
         let locs = locs.drain(..).collect::<Vec<_>>();
         let functions = functions.drain().collect::<HashMap<_, _>>();
-        let metadata = Metadata { locs, functions };
+        let instrumented = mem::take(&mut *self.instrumented.lock().unwrap());
+        let metadata = Metadata {
+            locs,
+            functions,
+            instrumented,
+        };
         let bytes = bincode::serialize(&metadata).context("Location serialization failed")?;
         let mut file = OpenOptions::new()
             .append(true)
@@ -373,6 +393,7 @@ impl<'tcx> Visitor<'tcx> for CollectInstrumentationPoints<'_, 'tcx> {
     fn visit_assign(&mut self, dest: &Place<'tcx>, value: &Rvalue<'tcx>, location: Location) {
         let copy_fn = self.hooks().find("ptr_copy");
         let addr_local_fn = self.hooks().find("addr_of_local");
+        let addr_static_fn = self.hooks().find("addr_of_static");
         let ptr_contrive_fn = self.hooks().find("ptr_contrive");
         let ptr_to_int_fn = self.hooks().find("ptr_to_int");
         let load_value_fn = self.hooks().find("load_value");
@@ -502,16 +523,30 @@ impl<'tcx> Visitor<'tcx> for CollectInstrumentationPoints<'_, 'tcx> {
                     .dest(&dest)
                     .add_to(self);
             }
-            Rvalue::Use(Operand::Constant(..)) => {
-                // Track (as copies) assignments that give local names to constants so that code
-                // taking references to said constants can refer to these assignments as sources.
-                // TODO: should be replaced by AddrOfStatic when support for that is added
-                self.loc(location, location.successor_within_block(), copy_fn)
-                    .arg_var(dest)
-                    .dest(&dest)
-                    .debug_mir()
-                    .add_to(self);
-            }
+            Rvalue::Use(Operand::Constant(c)) => match c.check_static_ptr(self.tcx()) {
+                Some(static_) => {
+                    // Instrument which static's address is taken
+                    let (hash_0, hash_1) = self.tcx().def_path_hash(static_).0.as_value();
+                    self.loc(location, location.successor_within_block(), addr_static_fn)
+                        .arg_var(dest)
+                        .arg_var(hash_0)
+                        .arg_var(hash_1)
+                        .dest(&dest)
+                        .debug_mir()
+                        .instrumentation_priority(InstrumentationPriority::Early)
+                        .add_to(self);
+                }
+                None => {
+                    // Track (as copies) assignments that give local names to constants so that
+                    // code taking references to said constants can refer to these assignments
+                    // as sources.
+                    self.loc(location, location.successor_within_block(), copy_fn)
+                        .arg_var(dest)
+                        .dest(&dest)
+                        .debug_mir()
+                        .add_to(self);
+                }
+            },
             Rvalue::Use(Operand::Copy(p) | Operand::Move(p)) => {
                 self.loc(location, location.successor_within_block(), copy_fn)
                     .arg_var(dest)
@@ -645,6 +680,7 @@ impl<'tcx> Visitor<'tcx> for CollectInstrumentationPoints<'_, 'tcx> {
                         // Hooked function called; trace args
                         self.loc(location, location, func_def_id)
                             .source(args)
+                            .arg_sources(args)
                             .dest(destination)
                             .after_call()
                             .transfer(TransferKind::Ret(self.func_id()))
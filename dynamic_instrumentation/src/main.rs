@@ -13,6 +13,7 @@ extern crate rustc_span;
 
 mod arg;
 mod callbacks;
+mod filter;
 mod hooks;
 mod instrument;
 mod into_operand;
@@ -22,6 +23,7 @@ mod runtime_conversions;
 mod util;
 
 use crate::callbacks::{MirTransformCallbacks, INSTRUMENTER};
+use crate::filter::{INSTRUMENT_ONLY_VAR, SKIP_VAR};
 
 use std::{
     borrow::Borrow,
@@ -74,6 +76,20 @@ struct Args {
     #[clap(long)]
     rustflags: Option<OsString>,
 
+    /// Only instrument functions whose def paths match one of these patterns,
+    /// like `pointers::simple`, or `buffer` for everything in the `buffer` module.
+    /// A `*` matches anything within one path segment.
+    ///
+    /// Pointers passed to or returned from functions that aren't instrumented
+    /// are marked as escaped in the PDG.
+    #[clap(long, value_parser)]
+    instrument_only: Vec<String>,
+
+    /// Don't instrument functions whose def paths match any of these patterns,
+    /// even if they match `--instrument-only`.
+    #[clap(long, value_parser)]
+    skip: Vec<String>,
+
     /// `cargo` args.
     cargo_args: Vec<OsString>,
 }
@@ -473,6 +489,8 @@ fn cargo_wrapper(rustc_wrapper: &Path) -> anyhow::Result<()> {
         runtime_path,
         set_runtime,
         rustflags,
+        instrument_only,
+        skip,
         mut cargo_args,
     } = Args::parse();
 
@@ -544,7 +562,9 @@ fn cargo_wrapper(rustc_wrapper: &Path) -> anyhow::Result<()> {
             .env(RUST_SYSROOT_VAR, &sysroot)
             .env("CARGO_TARGET_DIR", &cargo_target_dir)
             .env("RUSTFLAGS", &rustflags)
-            .env(METADATA_VAR, metadata_path.as_ref());
+            .env(METADATA_VAR, metadata_path.as_ref())
+            .env(INSTRUMENT_ONLY_VAR, instrument_only.join("\n"))
+            .env(SKIP_VAR, skip.join("\n"));
         Ok(())
     })?;
 
@@ -2,7 +2,7 @@ use c2rust_analysis_rt::mir_loc::{EventMetadata, TransferKind};
 use itertools::Itertools;
 use rustc_index::vec::Idx;
 use rustc_middle::{
-    mir::{Body, Location, Place, TerminatorKind},
+    mir::{Body, Location, Operand, Place, TerminatorKind},
     ty::{self, TyCtxt},
 };
 use rustc_span::def_id::DefId;
@@ -146,6 +146,12 @@ impl<'tcx> InstrumentationBuilder<'_, 'tcx> {
         self
     }
 
+    /// Set the [`EventMetadata::arg_sources`] of a hooked call to its `args`.
+    pub fn arg_sources(mut self, args: &[Operand]) -> Self {
+        self.point.metadata.arg_sources = args.iter().map(Source::source).collect();
+        self
+    }
+
     pub fn dest(mut self, p: &Place) -> Self {
         self.point.metadata.destination = Some(p.convert());
         self
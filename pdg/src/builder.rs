@@ -1,21 +1,22 @@
 use crate::graph::{Graph, GraphId, Graphs, Node, NodeId, NodeKind};
-use c2rust_analysis_rt::events::{Event, EventKind, Pointer};
+use c2rust_analysis_rt::event_log::EventReader;
+use c2rust_analysis_rt::events::{Event, EventKind, Pointer, ThreadId};
 use c2rust_analysis_rt::metadata::Metadata;
-use c2rust_analysis_rt::mir_loc::{EventMetadata, Func, FuncId, Local, MirLoc, TransferKind};
+use c2rust_analysis_rt::mir_loc::{
+    DefPathHash, EventMetadata, Func, FuncId, Local, MirLoc, TransferKind,
+};
 use color_eyre::eyre;
 use fs_err::File;
 use indexmap::IndexSet;
 use itertools::Itertools;
 use std::collections::HashMap;
-use std::io::{self, BufReader};
-use std::iter;
+use std::io;
 use std::path::Path;
 
-pub fn read_event_log(path: &Path) -> io::Result<Vec<Event>> {
+/// Read a plain or compressed event log, one [`Event`] at a time.
+pub fn read_event_log(path: &Path) -> io::Result<EventReader> {
     let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    let events = iter::from_fn(|| bincode::deserialize_from(&mut reader).ok()).collect::<Vec<_>>();
-    Ok(events)
+    EventReader::new(file)
 }
 
 pub fn read_metadata(path: &Path) -> eyre::Result<Metadata> {
@@ -26,13 +27,16 @@ pub fn read_metadata(path: &Path) -> eyre::Result<Metadata> {
 fn parent(e: &NodeKind, obj: (GraphId, NodeId)) -> Option<(GraphId, NodeId)> {
     use NodeKind::*;
     match e {
-        Alloc(..) | AddrOfLocal(..) => None,
+        Alloc(..) | AddrOfLocal(..) | AddrOfStatic(..) => None,
         _ => Some(obj),
     }
 }
 
 type AddressTaken = IndexSet<(FuncId, Local)>;
 
+/// The root [`NodeKind::AddrOfStatic`] of the graph of each static whose address has been taken.
+type StaticRoots = HashMap<DefPathHash, (GraphId, NodeId)>;
+
 pub trait EventKindExt {
     fn ptr(&self, metadata: &EventMetadata) -> Option<Pointer>;
     fn to_node_kind(&self, func: FuncId, address_taken: &mut AddressTaken) -> Option<NodeKind>;
@@ -49,6 +53,8 @@ impl EventKindExt for EventKind {
             Ret(ptr) => ptr,
             LoadAddr(ptr) => ptr,
             StoreAddr(ptr) => ptr,
+            LoadRange { ptr, .. } => ptr,
+            StoreRange { ptr, .. } => ptr,
             StoreAddrTaken(ptr) => ptr,
             LoadValue(ptr) => ptr,
             StoreValue(ptr) => ptr,
@@ -58,6 +64,7 @@ impl EventKindExt for EventKind {
             FromInt(lhs) => lhs,
             Alloc { ptr, .. } => ptr,
             AddrOfLocal(lhs, _) => lhs,
+            AddrOfStatic(lhs, _) => lhs,
             Offset(ptr, _, _) => ptr,
             Done | BeginFuncBody => return None,
         })
@@ -73,6 +80,8 @@ impl EventKindExt for EventKind {
             Field(_, field) => NodeKind::Field(field.into()),
             LoadAddr(..) => NodeKind::LoadAddr,
             StoreAddr(..) => NodeKind::StoreAddr,
+            LoadRange { size, .. } => NodeKind::LoadRange(size),
+            StoreRange { size, .. } => NodeKind::StoreRange(size),
             StoreAddrTaken(..) => NodeKind::StoreAddr,
             LoadValue(..) => NodeKind::LoadValue,
             StoreValue(..) => NodeKind::StoreValue,
@@ -86,6 +95,7 @@ impl EventKindExt for EventKind {
                     NodeKind::Copy
                 }
             }
+            AddrOfStatic(_, static_) => NodeKind::AddrOfStatic(static_),
             BeginFuncBody => {
                 // Reset the collection of address-taken locals, in order to
                 // properly consider the first instance of each address-taking
@@ -128,7 +138,7 @@ fn update_provenance(
         CopyRef => {
             provenances.insert(metadata.destination.clone().unwrap().local.into(), mapping);
         }
-        AddrOfLocal(ptr, _) => {
+        AddrOfLocal(ptr, _) | AddrOfStatic(ptr, _) => {
             provenances.insert(ptr, mapping);
         }
         _ => {}
@@ -139,6 +149,7 @@ pub fn add_node(
     graphs: &mut Graphs,
     provenances: &mut HashMap<Pointer, (GraphId, NodeId)>,
     address_taken: &mut AddressTaken,
+    static_roots: &mut StaticRoots,
     event: &Event,
     metadata: &Metadata,
 ) -> Option<NodeId> {
@@ -149,7 +160,25 @@ pub fn add_node(
         metadata: event_metadata,
     } = metadata.get(event.mir_loc);
 
-    let node_kind = event.kind.to_node_kind(func.id, address_taken)?;
+    let mut node_kind = event.kind.to_node_kind(func.id, address_taken)?;
+
+    // All but the first instance of AddrOfStatic for a given static
+    // are considered copies of the root of that static's graph
+    let static_root = match event.kind {
+        EventKind::AddrOfStatic(_, static_) => static_roots.get(&static_).cloned(),
+        _ => None,
+    };
+    if static_root.is_some() {
+        node_kind = NodeKind::Copy;
+    }
+
+    // A pointer passed to or returned from a function that isn't instrumented
+    // may be used in any way there
+    if let TransferKind::Arg(callee) | TransferKind::Ret(callee) = event_metadata.transfer_kind {
+        if !metadata.instrumented.contains(&callee) {
+            node_kind = NodeKind::Escape;
+        }
+    }
     let this_id = func.id;
     let (src_fn, dest_fn) = match event_metadata.transfer_kind {
         TransferKind::None => (this_id, this_id),
@@ -181,9 +210,12 @@ pub fn add_node(
             .map(|nid| (gid, NodeId::from(nid)))
     });
 
-    let source = direct_source.or_else(|| {
+    let source = direct_source.or(static_root).or_else(|| {
         event_metadata.source.as_ref().and_then(|src| {
-            let latest_assignment = graphs.latest_assignment.get(&(src_fn, src.local)).cloned();
+            let latest_assignment = graphs
+                .latest_assignment
+                .get(&(event.thread, src_fn, src.local))
+                .cloned();
             if !src.projection.is_empty() {
                 if let Some((gid, _)) = latest_assignment {
                     if let Some((nid, n)) = graphs.graphs[gid].nodes.iter_enumerated().rev().next()
@@ -205,6 +237,34 @@ pub fn add_node(
         })
     });
 
+    // A hook's source is only its first argument, but its range events may be for any argument,
+    // so they use the latest assignment to that argument instead.
+    // They also don't assign to the hook's destination.
+    let range_arg = match event.kind {
+        EventKind::LoadRange { arg, .. } | EventKind::StoreRange { arg, .. } => Some(arg),
+        _ => None,
+    };
+    let is_range = range_arg.is_some();
+    let source = match range_arg {
+        Some(arg) => event_metadata
+            .arg_sources
+            .get(arg as usize)
+            .and_then(|src| src.as_ref())
+            .and_then(|src| {
+                graphs
+                    .latest_assignment
+                    .get(&(event.thread, src_fn, src.local))
+                    .cloned()
+            })
+            .or(provenance),
+        None => source,
+    };
+    let destination = if is_range {
+        None
+    } else {
+        event_metadata.destination.as_ref()
+    };
+
     let function = Func {
         id: dest_fn,
         name: metadata.functions[&dest_fn].clone(),
@@ -218,7 +278,7 @@ pub fn add_node(
         source: source
             .and_then(|p| parent(&node_kind, p))
             .map(|(_, nid)| nid),
-        dest: event_metadata.destination.clone(),
+        dest: destination.cloned(),
         debug_info: event_metadata.debug_info.clone(),
         info: None,
     };
@@ -231,6 +291,10 @@ pub fn add_node(
         .unwrap_or_else(|| graphs.graphs.push(Graph::new()));
     let node_id = graphs.graphs[graph_id].nodes.push(node);
 
+    if let EventKind::AddrOfStatic(_, static_) = event.kind {
+        static_roots.entry(static_).or_insert((graph_id, node_id));
+    }
+
     update_provenance(
         provenances,
         &event.kind,
@@ -238,8 +302,8 @@ pub fn add_node(
         (graph_id, node_id),
     );
 
-    if let Some(dest) = &event_metadata.destination {
-        let unique_place = (dest_fn, dest.local);
+    if let Some(dest) = destination {
+        let unique_place = (event.thread, dest_fn, dest.local);
         let last_setting = (graph_id, node_id);
 
         if let Some(last @ (last_gid, last_nid)) =
@@ -261,16 +325,23 @@ pub fn add_node(
     Some(node_id)
 }
 
-pub fn construct_pdg(events: &[Event], metadata: &Metadata) -> Graphs {
+/// Build the PDG from `events`.
+///
+/// The events are consumed one at a time, so they needn't all be in memory at once.
+/// The events of different threads may be interleaved, so local state is tracked per thread,
+/// while pointers and statics are shared by all threads.
+pub fn construct_pdg(events: impl IntoIterator<Item = Event>, metadata: &Metadata) -> Graphs {
     let mut graphs = Graphs::new();
     let mut provenances = HashMap::new();
-    let mut address_taken = AddressTaken::new();
+    let mut address_taken = HashMap::<ThreadId, AddressTaken>::new();
+    let mut static_roots = StaticRoots::new();
     for event in events {
         add_node(
             &mut graphs,
             &mut provenances,
-            &mut address_taken,
-            event,
+            address_taken.entry(event.thread).or_default(),
+            &mut static_roots,
+            &event,
             metadata,
         );
     }
@@ -1,5 +1,7 @@
+use c2rust_analysis_rt::events::ThreadId;
 use c2rust_analysis_rt::mir_loc::{self, DefPathHash, Func};
 use c2rust_analysis_rt::mir_loc::{FuncId, MirPlace};
+use c2rust_analysis_rt::pdg_file;
 use rustc_index::newtype_index;
 use rustc_index::vec::IndexVec;
 use rustc_middle::mir::{BasicBlock, Field, Local};
@@ -51,12 +53,13 @@ pub enum NodeKind {
 
     /// Get the address of a static.
     ///
-    /// These are treated the same as [`Local`]s,
-    /// with an [`_AddressOfStatic`](Self::_AddrOfStatic) attributed to
-    /// the first [`Statement`](rustc_middle::mir::Statement).
+    /// These are treated the same as [`Local`]s, except that each static has one graph
+    /// for the whole program, rooted at the [`AddrOfStatic`](Self::AddrOfStatic)
+    /// of the first use of its address.
+    /// Later uses, on any thread and in any function, appear as copies of that root pointer.
     ///
     /// Can't have a [`Node::source`].
-    _AddrOfStatic(DefPathHash),
+    AddrOfStatic(DefPathHash),
 
     /// Heap allocation.
     ///
@@ -107,12 +110,36 @@ pub enum NodeKind {
     /// Can't be the [`Node::source`] of any other operation.
     StoreAddr,
 
+    /// The pointer appears as the address of a load of a range of bytes,
+    /// such as by `memcpy` or `strlen`.
+    ///
+    /// The [`usize`] is the number of bytes loaded.
+    ///
+    /// Can't be the [`Node::source`] of any other operation.
+    LoadRange(usize),
+
+    /// The pointer appears as the address of a store to a range of bytes,
+    /// such as by `memcpy` or `memset`.
+    ///
+    /// The [`usize`] is the number of bytes stored.
+    ///
+    /// Can't be the [`Node::source`] of any other operation.
+    StoreRange(usize),
+
     /// The pointer is stored through some other pointer.
     ///
     /// Details TBD.
     ///
     /// Can't be the [`Node::source`] of any other operation.
     StoreValue,
+
+    /// The pointer is passed to or returned from a function that isn't instrumented,
+    /// such as one excluded by `c2rust-instrument --skip` or one from another crate,
+    /// so it may be used there in ways that aren't in the PDG.
+    ///
+    /// A pointer passed to such a function can't be the [`Node::source`] of any other operation,
+    /// but one returned from it is the source of its uses in the caller.
+    Escape,
 }
 
 impl Display for NodeKind {
@@ -123,7 +150,9 @@ impl Display for NodeKind {
             Field(field) => write!(f, "field.{}", field.as_usize()),
             Offset(offset) => write!(f, "offset[{offset}]"),
             AddrOfLocal(local) => write!(f, "&{local:?}"),
-            _AddrOfStatic(static_) => write!(f, "&'static {static_:?}"),
+            // The `DefPathHash` changes with the crate's dependencies, so it would make
+            // the output unstable, and each static has its own graph anyway.
+            AddrOfStatic(_) => write!(f, "&'static"),
             Alloc(n) => {
                 // Right now we only create `Alloc(1)`, so special case it,
                 // as the increased readability helps.
@@ -140,6 +169,57 @@ impl Display for NodeKind {
             StoreValue => write!(f, "value.store"),
             LoadAddr => write!(f, "addr.load"),
             StoreAddr => write!(f, "addr.store"),
+            LoadRange(n) => write!(f, "addr.load(n = {n})"),
+            StoreRange(n) => write!(f, "addr.store(n = {n})"),
+            Escape => write!(f, "escape"),
+        }
+    }
+}
+
+impl From<NodeKind> for pdg_file::NodeKind {
+    fn from(kind: NodeKind) -> Self {
+        use NodeKind::*;
+        match kind {
+            Copy => Self::Copy,
+            Field(field) => Self::Field(field.as_u32()),
+            Offset(offset) => Self::Offset(offset),
+            AddrOfLocal(local) => Self::AddrOfLocal(local.as_u32().into()),
+            AddrOfStatic(static_) => Self::AddrOfStatic(static_),
+            Alloc(n) => Self::Alloc(n),
+            Free => Self::Free,
+            IntToPtr => Self::IntToPtr,
+            PtrToInt => Self::PtrToInt,
+            LoadValue => Self::LoadValue,
+            LoadAddr => Self::LoadAddr,
+            StoreAddr => Self::StoreAddr,
+            LoadRange(n) => Self::LoadRange(n),
+            StoreRange(n) => Self::StoreRange(n),
+            StoreValue => Self::StoreValue,
+            Escape => Self::Escape,
+        }
+    }
+}
+
+impl From<pdg_file::NodeKind> for NodeKind {
+    fn from(kind: pdg_file::NodeKind) -> Self {
+        use pdg_file::NodeKind::*;
+        match kind {
+            Copy => Self::Copy,
+            Field(field) => Self::Field(field.into()),
+            Offset(offset) => Self::Offset(offset),
+            AddrOfLocal(local) => Self::AddrOfLocal(local.as_u32().into()),
+            AddrOfStatic(static_) => Self::AddrOfStatic(static_),
+            Alloc(n) => Self::Alloc(n),
+            Free => Self::Free,
+            IntToPtr => Self::IntToPtr,
+            PtrToInt => Self::PtrToInt,
+            LoadValue => Self::LoadValue,
+            LoadAddr => Self::LoadAddr,
+            StoreAddr => Self::StoreAddr,
+            LoadRange(n) => Self::LoadRange(n),
+            StoreRange(n) => Self::StoreRange(n),
+            StoreValue => Self::StoreValue,
+            Escape => Self::Escape,
         }
     }
 }
@@ -182,6 +262,36 @@ pub struct Node {
     pub info: Option<NodeInfo>,
 }
 
+impl From<&Node> for pdg_file::Node {
+    fn from(node: &Node) -> Self {
+        Self {
+            function: node.function.clone(),
+            block: node.block.as_u32(),
+            statement_idx: node.statement_idx,
+            dest: node.dest.clone(),
+            kind: node.kind.into(),
+            source: node.source.map(|id| id.as_u32()),
+            debug_info: node.debug_info.clone(),
+            info: node.info.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl From<&pdg_file::Node> for Node {
+    fn from(node: &pdg_file::Node) -> Self {
+        Self {
+            function: node.function.clone(),
+            block: node.block.into(),
+            statement_idx: node.statement_idx,
+            dest: node.dest.clone(),
+            kind: node.kind.into(),
+            source: node.source.map(NodeId::from_u32),
+            debug_info: node.debug_info.clone(),
+            info: node.info.as_ref().map(Into::into),
+        }
+    }
+}
+
 struct BlockStatement<'a> {
     block: &'a BasicBlock,
     statement_idx: &'a usize,
@@ -299,6 +409,22 @@ impl Display for Graph {
     }
 }
 
+impl From<&Graph> for pdg_file::Graph {
+    fn from(graph: &Graph) -> Self {
+        Self {
+            nodes: graph.nodes.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<&pdg_file::Graph> for Graph {
+    fn from(graph: &pdg_file::Graph) -> Self {
+        Self {
+            nodes: graph.nodes.iter().map(Into::into).collect(),
+        }
+    }
+}
+
 newtype_index!(
     /// Implement `Idx` and other traits like MIR indices (`Local`, `BasicBlock`, etc.)
     pub struct GraphId { DEBUG_FORMAT = "GraphId({})" }
@@ -318,7 +444,8 @@ pub struct Graphs {
     pub graphs: IndexVec<GraphId, Graph>,
 
     /// Lookup table for finding all nodes in all graphs that store to a particular MIR local.
-    pub latest_assignment: HashMap<(FuncId, mir_loc::Local), (GraphId, NodeId)>,
+    /// Each thread has its own locals, so they are tracked per [`ThreadId`].
+    pub latest_assignment: HashMap<(ThreadId, FuncId, mir_loc::Local), (GraphId, NodeId)>,
 }
 
 impl Graphs {
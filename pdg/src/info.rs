@@ -1,5 +1,7 @@
 use crate::graph::{Graph, Node, NodeId, NodeKind};
 use crate::Graphs;
+use c2rust_analysis_rt::pdg_facts::{PdgFacts, PointerFacts};
+use c2rust_analysis_rt::pdg_file;
 use rustc_middle::mir::Field;
 use std::cmp::{max, min};
 use std::collections::{HashMap, HashSet};
@@ -20,6 +22,40 @@ pub struct NodeInfo {
     unique: bool,
 }
 
+impl From<&NodeInfo> for pdg_file::NodeInfo {
+    fn from(info: &NodeInfo) -> Self {
+        let NodeInfo { flows_to, unique } = info;
+        let id = |n_id: Option<NodeId>| n_id.map(|n_id| n_id.as_u32());
+        Self {
+            flows_to: pdg_file::FlowInfo {
+                load: id(flows_to.load),
+                store: id(flows_to.store),
+                pos_offset: id(flows_to.pos_offset),
+                neg_offset: id(flows_to.neg_offset),
+                escape: id(flows_to.escape),
+            },
+            unique: *unique,
+        }
+    }
+}
+
+impl From<&pdg_file::NodeInfo> for NodeInfo {
+    fn from(info: &pdg_file::NodeInfo) -> Self {
+        let pdg_file::NodeInfo { flows_to, unique } = info;
+        let id = |n_id: Option<u32>| n_id.map(NodeId::from_u32);
+        Self {
+            flows_to: FlowInfo {
+                load: id(flows_to.load),
+                store: id(flows_to.store),
+                pos_offset: id(flows_to.pos_offset),
+                neg_offset: id(flows_to.neg_offset),
+                escape: id(flows_to.escape),
+            },
+            unique: *unique,
+        }
+    }
+}
+
 impl Display for NodeInfo {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "")
@@ -27,7 +63,8 @@ impl Display for NodeInfo {
 }
 
 /// Contains information about what kinds of [`Node`]s a [`Node`] flows to.
-/// Load and store kinds contain both Load/Store-Value and Load/Store-Addr.
+/// Load and store kinds contain Load/Store-Value, Load/Store-Addr and Load/Store-Range.
+/// Escape means that the [`Node`] flows into, or was returned from, code that wasn't instrumented.
 /// A node A is said to flow into B if it is the transitive 'source' of B.
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq, Default)]
 pub struct FlowInfo {
@@ -35,6 +72,7 @@ pub struct FlowInfo {
     store: Option<NodeId>,
     pos_offset: Option<NodeId>,
     neg_offset: Option<NodeId>,
+    escape: Option<NodeId>,
 }
 
 impl FlowInfo {
@@ -42,10 +80,11 @@ impl FlowInfo {
     fn new(n_id: NodeId, k: NodeKind) -> FlowInfo {
         use NodeKind::*;
         FlowInfo {
-            load: matches!(k, LoadAddr | LoadValue).then(|| n_id),
-            store: matches!(k, StoreAddr | StoreValue).then(|| n_id),
+            load: matches!(k, LoadAddr | LoadRange(_) | LoadValue).then(|| n_id),
+            store: matches!(k, StoreAddr | StoreRange(_) | StoreValue).then(|| n_id),
             pos_offset: matches!(k, Offset(x) if x > 0).then(|| n_id),
             neg_offset: matches!(k, Offset(x) if x < 0).then(|| n_id),
+            escape: matches!(k, Escape).then(|| n_id),
         }
     }
 }
@@ -67,6 +106,7 @@ fn set_flow_info(g: &mut Graph) {
             parent.store = parent.store.or(cur_node_flow_info.store);
             parent.pos_offset = parent.pos_offset.or(cur_node_flow_info.pos_offset);
             parent.neg_offset = parent.neg_offset.or(cur_node_flow_info.neg_offset);
+            parent.escape = parent.escape.or(cur_node_flow_info.escape);
         }
         node.info = Some(NodeInfo {
             flows_to: cur_node_flow_info,
@@ -219,13 +259,68 @@ pub fn add_info(pdg: &mut Graphs) {
     }
 }
 
+/// Summarize the [`PointerFacts`] observed for each MIR local, for use by `c2rust-analyze`.
+///
+/// Each [`Node`] with a whole local as its [`Node::dest`] contributes the facts for the pointer
+/// it stored there, and the facts for all pointers stored in the same local are
+/// [`merge`](PointerFacts::merge)d.  A [`Node`]'s own [`FlowInfo`] counts the [`Node`] itself,
+/// so an [`Offset`](NodeKind::Offset) would appear to need to be offset again; instead, a pointer
+/// gets the facts of the [`Node`]s derived from it.  Writes come from
+/// [`Graph::needs_write_permission`] rather than [`FlowInfo::store`], which also counts
+/// storing the pointer itself somewhere.
+///
+/// [`add_info`] must have been run first.
+pub fn facts(pdg: &Graphs) -> PdgFacts {
+    let mut facts = PdgFacts::default();
+    for g in &pdg.graphs {
+        let needs_write = g.needs_write_permission().collect::<HashSet<_>>();
+        let mut derived = HashMap::<NodeId, PointerFacts>::new();
+        for (n_id, node) in g.nodes.iter_enumerated() {
+            let (source, info) = match (node.source, &node.info) {
+                (Some(source), Some(info)) => (source, info),
+                _ => continue,
+            };
+            let flows_to = &info.flows_to;
+            derived.entry(source).or_default().merge(&PointerFacts {
+                load: flows_to.load.is_some(),
+                store: needs_write.contains(&n_id),
+                pos_offset: flows_to.pos_offset.is_some(),
+                neg_offset: flows_to.neg_offset.is_some(),
+                unique: true,
+                escaped: flows_to.escape.is_some(),
+            });
+        }
+        for (n_id, node) in g.nodes.iter_enumerated() {
+            let dest = match &node.dest {
+                Some(dest) if dest.projection.is_empty() => dest,
+                _ => continue,
+            };
+            let info = node.info.as_ref().expect("add_info should have been run");
+            let node_facts = PointerFacts {
+                unique: info.unique,
+                ..derived.get(&n_id).copied().unwrap_or_default()
+            };
+            facts
+                .locals
+                .entry((node.function.id, dest.local))
+                .or_default()
+                .merge(&node_facts);
+        }
+    }
+    facts
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use c2rust_analysis_rt::metadata::Metadata;
     use c2rust_analysis_rt::mir_loc::Func;
     use c2rust_analysis_rt::mir_loc::FuncId;
+    use c2rust_analysis_rt::mir_loc::MirPlace;
+    use c2rust_analysis_rt::pdg_file::{PdgFile, PdgFormat};
     use rustc_middle::mir::Field;
     use rustc_middle::mir::Local;
+    use std::collections::BTreeSet;
 
     fn mk_node(g: &mut Graph, kind: NodeKind, source: Option<NodeId>) -> NodeId {
         g.nodes.push(Node {
@@ -263,6 +358,10 @@ mod test {
         mk_node(g, NodeKind::Offset(i), Some(source))
     }
 
+    fn mk_escape(g: &mut Graph, source: NodeId) -> NodeId {
+        mk_node(g, NodeKind::Escape, Some(source))
+    }
+
     fn build_pdg(g: Graph) -> Graphs {
         let mut pdg = Graphs::default();
         pdg.graphs.push(g);
@@ -1149,4 +1248,145 @@ mod test {
         assert!(!info(&pdg, y4).unique);
         assert!(!info(&pdg, y5).unique);
     }
+
+    /// ```rust
+    /// let mut a = 0;
+    /// let mut p = &mut a;  // P1
+    /// *p = 1;
+    /// p = &mut a;          // P2
+    /// let q = &mut a;      // Q
+    /// let r = p.offset(1); // R
+    /// ```
+    ///
+    /// `p` holds two different pointers, so its facts combine both.
+    #[test]
+    fn facts_merge_locals() {
+        let mut g = Graph::default();
+
+        let a = mk_addr_of_local(&mut g, 0_u32);
+        let p1 = mk_copy(&mut g, a);
+        mk_store_addr(&mut g, p1);
+        let p2 = mk_copy(&mut g, a);
+        let q = mk_copy(&mut g, a);
+        let r = mk_offset(&mut g, p2, 1);
+        for (id, local) in [(p1, 1_u32), (p2, 1), (q, 2), (r, 3)] {
+            g.nodes[id].dest = Some(MirPlace {
+                local: local.into(),
+                projection: vec![],
+            });
+        }
+
+        let facts = facts(&build_pdg(g));
+        let func = FuncId((1, 2).into());
+        let get = |local: u32| *facts.get(func, local.into()).unwrap();
+
+        let p = get(1);
+        assert!(p.store);
+        assert!(p.pos_offset);
+        assert!(!p.neg_offset);
+        assert!(!p.unique);
+        let q = get(2);
+        assert!(!q.store);
+        assert!(!q.pos_offset);
+        let r = get(3);
+        assert!(!r.store);
+        assert!(!r.pos_offset);
+        assert!(facts.get(func, 0_u32.into()).is_none());
+    }
+
+    /// ```rust
+    /// let mut a = 0;
+    /// let p = &mut a;     // P
+    /// let q = p.add(1);   // Q
+    /// let r = &mut a;     // R
+    /// uninstrumented(q);  // E
+    /// ```
+    ///
+    /// `p` and `q` escape, since `q` is derived from `p`, but `r` doesn't.
+    #[test]
+    fn facts_escape() {
+        let mut g = Graph::default();
+
+        let a = mk_addr_of_local(&mut g, 0_u32);
+        let p = mk_copy(&mut g, a);
+        let q = mk_offset(&mut g, p, 1);
+        let r = mk_copy(&mut g, a);
+        mk_escape(&mut g, q);
+        for (id, local) in [(p, 1_u32), (q, 2), (r, 3)] {
+            g.nodes[id].dest = Some(MirPlace {
+                local: local.into(),
+                projection: vec![],
+            });
+        }
+
+        let facts = facts(&build_pdg(g));
+        let func = FuncId((1, 2).into());
+        let get = |local: u32| *facts.get(func, local.into()).unwrap();
+
+        assert!(get(1).escaped);
+        assert!(get(2).escaped);
+        assert!(!get(3).escaped);
+    }
+
+    /// Saving and loading a PDG, in either format, gives back the same graphs and facts.
+    #[test]
+    fn file_round_trip() {
+        let mut g = Graph::default();
+        let a = mk_addr_of_local(&mut g, 0_u32);
+        let b = mk_field(&mut g, a, 1_u32);
+        let c = mk_offset(&mut g, b, -1);
+        mk_store_addr(&mut g, c);
+        g.nodes[c].dest = Some(MirPlace {
+            local: 2_u32.into(),
+            projection: vec![],
+        });
+        let pdg = build_pdg(g);
+
+        let graphs = pdg.graphs.iter().map(Into::into).collect::<Vec<_>>();
+        let metadata = Metadata {
+            locs: Vec::new(),
+            functions: HashMap::new(),
+            instrumented: BTreeSet::new(),
+        };
+        let file = PdgFile::new(graphs, Vec::new(), metadata, facts(&pdg));
+        for format in [PdgFormat::Bincode, PdgFormat::Json] {
+            let loaded = PdgFile::read(&file.write(format).unwrap(), format).unwrap();
+            let loaded_graphs = loaded.graphs.iter().map(Into::into).collect::<Vec<Graph>>();
+            assert_eq!(loaded_graphs, pdg.graphs.raw);
+            assert_eq!(loaded.facts.locals, file.facts.locals);
+        }
+    }
+
+    /// Saved PDGs don't depend on the iteration order of the `HashMap`s they're built from.
+    #[test]
+    fn file_deterministic() {
+        let mk_file = || {
+            let mut g = Graph::default();
+            let a = mk_addr_of_local(&mut g, 0_u32);
+            for local in 1..20_u32 {
+                let p = mk_copy(&mut g, a);
+                g.nodes[p].dest = Some(MirPlace {
+                    local: local.into(),
+                    projection: vec![],
+                });
+            }
+            let pdg = build_pdg(g);
+            let graphs = pdg.graphs.iter().map(Into::into).collect::<Vec<_>>();
+            // Each `HashMap` gets its own random hasher, so these iterate in different orders.
+            let functions = (0..20_u64)
+                .map(|i| (FuncId((i, i).into()), format!("f{i}")))
+                .collect::<HashMap<_, _>>();
+            let metadata = Metadata {
+                locs: Vec::new(),
+                instrumented: functions.keys().copied().collect(),
+                functions,
+            };
+            PdgFile::new(graphs, Vec::new(), metadata, facts(&pdg))
+        };
+        let a = mk_file();
+        let b = mk_file();
+        for format in [PdgFormat::Bincode, PdgFormat::Json] {
+            assert_eq!(a.write(format).unwrap(), b.write(format).unwrap());
+        }
+    }
 }
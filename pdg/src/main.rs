@@ -25,11 +25,12 @@ mod query;
 mod util;
 
 use builder::{construct_pdg, read_event_log};
-use c2rust_analysis_rt::{events::Event, metadata::Metadata};
+use c2rust_analysis_rt::{events::Event, metadata::Metadata, pdg_file::PdgFile};
 use clap::{Parser, ValueEnum};
-use color_eyre::eyre;
-use graph::Graphs;
+use color_eyre::eyre::{self, eyre, WrapErr};
+use graph::{GraphId, Graphs, NodeId};
 use info::add_info;
+use itertools::process_results;
 use std::{
     fmt::{self, Display, Formatter},
     path::{Path, PathBuf},
@@ -55,16 +56,32 @@ impl Display for ToPrint {
 }
 
 pub struct Pdg {
+    /// The events the PDG was built from, if they were kept.
     pub events: Vec<Event>,
     pub metadata: Metadata,
     pub graphs: Graphs,
 }
 
 impl Pdg {
-    pub fn new(metadata_path: &Path, event_log_path: &Path) -> eyre::Result<Self> {
-        let events = read_event_log(event_log_path)?;
+    /// Build a PDG from an event log, which is read incrementally.
+    /// The [`Pdg::events`] are only kept if `keep_events` is set,
+    /// since the log may be much larger than the PDG.
+    pub fn new(
+        metadata_path: &Path,
+        event_log_path: &Path,
+        keep_events: bool,
+    ) -> eyre::Result<Self> {
+        let mut events = Vec::new();
         let metadata = read_metadata(metadata_path)?;
-        let mut graphs = construct_pdg(&events, &metadata);
+        let mut graphs = process_results(read_event_log(event_log_path)?, |event_log| {
+            let event_log = event_log.inspect(|event| {
+                if keep_events {
+                    events.push(event.clone());
+                }
+            });
+            construct_pdg(event_log, &metadata)
+        })
+        .wrap_err_with(|| format!("reading event log {}", event_log_path.display()))?;
         add_info(&mut graphs);
         graphs.remove_addr_of_local_sources();
         Ok(Self {
@@ -74,6 +91,54 @@ impl Pdg {
         })
     }
 
+    /// Load a PDG saved with [`Pdg::save`].  The [`Pdg::events`] aren't saved, so they will be
+    /// empty.
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let file = PdgFile::read_path(path).map_err(|e| eyre!(e))?;
+        Ok(Self::from_file(file))
+    }
+
+    /// Save the PDG, except for the [`Pdg::events`], so that it can be [`load`](Pdg::load)ed
+    /// without rebuilding it from the event log.
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        self.to_file().write_path(path).map_err(|e| eyre!(e))
+    }
+
+    pub fn to_file(&self) -> PdgFile {
+        let graphs = self.graphs.graphs.iter().map(Into::into).collect();
+        let mut latest_assignment = self
+            .graphs
+            .latest_assignment
+            .iter()
+            .map(|(&key, &(graph_id, node_id))| (key, (graph_id.as_u32(), node_id.as_u32())))
+            .collect::<Vec<_>>();
+        // Sort so the output is deterministic.
+        latest_assignment.sort();
+        let facts = info::facts(&self.graphs);
+        PdgFile::new(graphs, latest_assignment, self.metadata.clone(), facts)
+    }
+
+    pub fn from_file(file: PdgFile) -> Self {
+        let graphs = Graphs {
+            graphs: file.graphs.iter().map(Into::into).collect(),
+            latest_assignment: file
+                .latest_assignment
+                .iter()
+                .map(|&(key, (graph_id, node_id))| {
+                    (
+                        key,
+                        (GraphId::from_u32(graph_id), NodeId::from_u32(node_id)),
+                    )
+                })
+                .collect(),
+        };
+        Self {
+            events: Vec::new(),
+            metadata: file.metadata,
+            graphs,
+        }
+    }
+
     pub fn repr<'a>(&'a self, to_print: &'a [ToPrint]) -> PdgRepr<'a> {
         PdgRepr {
             pdg: self,
@@ -108,14 +173,15 @@ impl Display for PdgRepr<'_> {
             for event in events.iter() {
                 let mir_loc = metadata.get(event.mir_loc);
                 let kind = &event.kind;
-                writeln!(f, "{mir_loc:?} -> {kind:?}")?;
+                let thread = event.thread;
+                writeln!(f, "[thread {thread}] {mir_loc:?} -> {kind:?}")?;
             }
         }
 
         if should_print(ToPrint::LatestAssignments) {
-            for ((func_hash, local), p) in &graphs.latest_assignment {
+            for ((thread, func_hash, local), p) in &graphs.latest_assignment {
                 let func = &metadata.functions[func_hash];
-                writeln!(f, "({func}:{local:?}) => {p:?}")?;
+                writeln!(f, "(thread {thread}: {func}:{local:?}) => {p:?}")?;
             }
         }
 
@@ -155,16 +221,26 @@ impl Display for PdgRepr<'_> {
 #[clap(author, version, about, long_about = None)]
 pub struct Args {
     /// Path to an event log from a run of an instrumented program.
-    #[clap(long, value_parser)]
-    event_log: PathBuf,
+    #[clap(long, value_parser, required_unless_present = "input")]
+    event_log: Option<PathBuf>,
 
     /// Path to the instrumented program's metadata generated at compile/instrumentation time.
-    #[clap(long, value_parser)]
-    metadata: PathBuf,
+    #[clap(long, value_parser, required_unless_present = "input")]
+    metadata: Option<PathBuf>,
+
+    /// Load a PDG saved with `--output` instead of building one from an event log.
+    /// Events aren't saved, so `--print events` will print nothing.
+    #[clap(long, value_parser, conflicts_with_all = &["event-log", "metadata"])]
+    input: Option<PathBuf>,
 
     /// What to print.
     #[clap(long, value_parser, default_value = "graphs")]
     print: Vec<ToPrint>,
+
+    /// Save the PDG to this path, as JSON if it ends in `.json` and as bincode otherwise.
+    /// The result can be loaded again with `--input` or by `c2rust-analyze --pdg`.
+    #[clap(long, value_parser)]
+    output: Option<PathBuf>,
 }
 
 static INIT: Once = Once::new();
@@ -187,8 +263,17 @@ pub fn init() {
 fn main() -> eyre::Result<()> {
     init();
     let args = Args::parse();
-    let pdg = Pdg::new(&args.metadata, &args.event_log)?;
+    let pdg = match (&args.input, &args.metadata, &args.event_log) {
+        (Some(input), _, _) => Pdg::load(input)?,
+        (None, Some(metadata), Some(event_log)) => {
+            Pdg::new(metadata, event_log, args.print.contains(&ToPrint::Events))?
+        }
+        _ => unreachable!("enforced by clap"),
+    };
     pdg.graphs.assert_all_tests();
+    if let Some(path) = &args.output {
+        pdg.save(path)?;
+    }
     let repr = pdg.repr(&args.print);
     println!("{repr}");
     Ok(())
@@ -202,6 +287,7 @@ mod tests {
         fmt::Display,
         path::{Path, PathBuf},
         process::Command,
+        sync::{Mutex, PoisonError},
     };
 
     use c2rust_analysis_rt::runtime::backend::BackendKind;
@@ -209,7 +295,7 @@ mod tests {
 
     use color_eyre::eyre::{self, ensure, eyre, Context};
 
-    use crate::{Pdg, ToPrint};
+    use crate::{graph::NodeKind, Pdg, ToPrint};
 
     pub enum Profile {
         Debug,
@@ -266,7 +352,11 @@ mod tests {
         Ok(repo_dir.to_owned())
     }
 
-    /// Instrument and run a test crate and return a snapshot (an `impl `[`Display`]) of its [`Pdg`].
+    /// Runs share the metadata and event log files of their [`Profile`] in `instrument.target`,
+    /// so only one may instrument and run a test crate at a time.
+    static INSTRUMENT_TARGET: Mutex<()> = Mutex::new(());
+
+    /// Instrument and run a test crate and return its [`Pdg`].
     ///
     /// # Args
     /// * `test_crate_dir` is the directory of the test crate.
@@ -274,7 +364,14 @@ mod tests {
     ///
     /// * `profile` is the [`Profile`] the test crate is compiled and run as.
     ///
-    /// * `to_print` are the [`ToPrint`]s that should be printed in the [`Pdg`] snapshot.
+    /// * `instrument_args` are passed to `c2rust-instrument`, like `--instrument-only`.
+    ///
+    /// * `args` are passed to the test binary.
+    ///
+    /// * `keep_events` is whether the [`Pdg`] keeps its [`Event`](c2rust_analysis_rt::events::Event)s.
+    ///
+    /// * `backend_kind` is the [`BackendKind`] that writes the event log.
+    ///   It must write to a file, so it can't be [`BackendKind::Debug`].
     ///
     /// # Overview
     ///
@@ -286,8 +383,6 @@ mod tests {
     /// is then run via a `cargo run`, but done through `c2rust-instrument ... -- run`.
     ///
     /// Then the metadata and event log files created are read in by the `c2rust-pdg` code here.
-    /// All assertion tests are checked on the [`Pdg`]'s [`Graphs`](crate::Graphs).
-    /// Then, finally, the [`Pdg`] is snapshotted into an `impl `[`Display`], printing the [`ToPrint`]s in `to_print`.
     ///
     /// # Details
     ///
@@ -301,13 +396,15 @@ mod tests {
     ///
     /// `$INSTRUMENT_OUTPUT_APPEND` is set to `false` as this runs the test binary only once,
     /// so appending is not yet necessary.
-    fn pdg_snapshot(
+    fn instrument_and_run(
         test_crate_dir: &Path,
         profile: Profile,
+        instrument_args: &[&str],
         args: impl IntoIterator<Item = impl AsRef<OsStr>>,
-        to_print: &[ToPrint],
+        keep_events: bool,
         runtime_kind: RuntimeKind,
-    ) -> eyre::Result<impl Display> {
+        backend_kind: BackendKind,
+    ) -> eyre::Result<Pdg> {
         let runtime_path = repo_dir()?.join("analysis/runtime");
         let manifest_path = test_crate_dir.join("Cargo.toml");
         let target_dir = test_crate_dir.join("instrument.target");
@@ -315,6 +412,9 @@ mod tests {
         let metadata_path = exe_dir.join("metadata.bc");
         let event_log_path = exe_dir.join("event.log.bc");
 
+        let _guard = INSTRUMENT_TARGET
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
         let mut cmd = Command::new("cargo");
         cmd.current_dir(repo_dir()?)
             .args(&[
@@ -331,20 +431,49 @@ mod tests {
             .arg(&metadata_path)
             .args(&["--set-runtime", "--runtime-path"])
             .arg(&runtime_path)
+            .args(instrument_args)
             .args(&["--", "run", "--manifest-path"])
             .arg(&manifest_path)
             .args(&["--profile", profile.name()])
+            .args(match backend_kind {
+                BackendKind::CompressedLog => &["--features", "c2rust-analysis-rt/compressed-log"],
+                _ => &[] as &[&str],
+            })
             .arg("--")
             .args(args)
             .env("METADATA_FILE", &metadata_path)
             .env("INSTRUMENT_RUNTIME", runtime_kind.as_str())
-            .env("INSTRUMENT_BACKEND", BackendKind::Log.as_str())
+            .env("INSTRUMENT_BACKEND", backend_kind.as_str())
             .env("INSTRUMENT_OUTPUT", &event_log_path)
             .env("INSTRUMENT_OUTPUT_APPEND", "false");
         let status = cmd.status()?;
         ensure!(status.success(), eyre!("{cmd:?} failed: {status}"));
 
-        let pdg = Pdg::new(&metadata_path, &event_log_path)?;
+        Pdg::new(&metadata_path, &event_log_path, keep_events)
+    }
+
+    /// [`instrument_and_run`] a test crate and return a snapshot (an `impl `[`Display`]) of its [`Pdg`].
+    ///
+    /// All assertion tests are checked on the [`Pdg`]'s [`Graphs`](crate::Graphs).
+    /// Then, finally, the [`Pdg`] is snapshotted into an `impl `[`Display`],
+    /// printing the [`ToPrint`]s in `to_print`.
+    fn pdg_snapshot(
+        test_crate_dir: &Path,
+        profile: Profile,
+        args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+        to_print: &[ToPrint],
+        runtime_kind: RuntimeKind,
+        backend_kind: BackendKind,
+    ) -> eyre::Result<impl Display> {
+        let pdg = instrument_and_run(
+            test_crate_dir,
+            profile,
+            &[],
+            args,
+            to_print.contains(&ToPrint::Events),
+            runtime_kind,
+            backend_kind,
+        )?;
         pdg.graphs.assert_all_tests();
         let repr = pdg.repr(to_print);
         Ok(repr.to_string())
@@ -353,6 +482,7 @@ mod tests {
     fn analysis_test_pdg_snapshot(
         profile: Profile,
         runtime_kind: RuntimeKind,
+        backend_kind: BackendKind,
     ) -> eyre::Result<impl Display> {
         pdg_snapshot(
             repo_dir()?.join("analysis/test").as_path(),
@@ -363,6 +493,7 @@ mod tests {
                 &[Graphs, WritePermissions, Counts]
             },
             runtime_kind,
+            backend_kind,
         )
     }
 
@@ -371,7 +502,7 @@ mod tests {
     #[test]
     fn analysis_test_pdg_snapshot_debug() -> eyre::Result<()> {
         init();
-        let pdg = analysis_test_pdg_snapshot(Profile::Debug, Default::default())?;
+        let pdg = analysis_test_pdg_snapshot(Profile::Debug, Default::default(), BackendKind::Log)?;
         insta::assert_display_snapshot!(pdg);
         Ok(())
     }
@@ -379,11 +510,90 @@ mod tests {
     #[test]
     fn analysis_test_pdg_snapshot_release() -> eyre::Result<()> {
         init();
-        let pdg = analysis_test_pdg_snapshot(Profile::Release, Default::default())?;
+        let pdg = analysis_test_pdg_snapshot(
+            Profile::Release,
+            Default::default(),
+            // The snapshots are the same, so also check that compressed logs are read correctly.
+            BackendKind::CompressedLog,
+        )?;
         insta::assert_display_snapshot!(pdg);
         Ok(())
     }
 
+    /// Check that each thread's uses of a local are traced back to its own assignments,
+    /// even though all threads run the same function at once.
+    ///
+    /// Identical graphs are deduplicated, so every thread's allocation should have the same graph.
+    #[test]
+    fn analysis_test_threads() -> eyre::Result<()> {
+        init();
+        let pdg = instrument_and_run(
+            repo_dir()?.join("analysis/test").as_path(),
+            Profile::Debug,
+            &[],
+            &["threads", "4"],
+            false,
+            Default::default(),
+            BackendKind::Log,
+        )?;
+        pdg.graphs.assert_all_tests();
+        let worker_graphs = pdg
+            .graphs
+            .graphs
+            .iter()
+            .filter(|graph| {
+                let head = &graph.nodes[0usize.into()];
+                head.function.name == "thread_worker" && matches!(head.kind, NodeKind::Alloc(..))
+            })
+            .collect::<Vec<_>>();
+        ensure!(
+            worker_graphs.len() == 1,
+            eyre!("threads' allocations have different graphs: {worker_graphs:#?}")
+        );
+        insta::assert_display_snapshot!(worker_graphs[0]);
+        Ok(())
+    }
+
+    /// Check that only instrumenting the `pointers` module still records its events,
+    /// even though `main` isn't instrumented and must still initialize and finalize the runtime,
+    /// and that pointers passed to the skipped `pointers::foo` escape.
+    #[test]
+    fn analysis_test_instrument_only() -> eyre::Result<()> {
+        init();
+        let pdg = instrument_and_run(
+            repo_dir()?.join("analysis/test").as_path(),
+            Profile::Debug,
+            &["--instrument-only", "pointers", "--skip", "pointers::foo"],
+            &[] as &[&OsStr],
+            false,
+            Default::default(),
+            BackendKind::Log,
+        )?;
+        pdg.graphs.assert_all_tests();
+        ensure!(
+            !pdg.graphs.graphs.is_empty(),
+            eyre!("no events were recorded")
+        );
+        let escape_graphs = pdg
+            .graphs
+            .graphs
+            .iter()
+            .filter(|graph| {
+                graph
+                    .nodes
+                    .iter()
+                    .any(|node| matches!(node.kind, NodeKind::Escape))
+            })
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        ensure!(
+            !escape_graphs.is_empty(),
+            eyre!("no pointers escaped: {:#?}", pdg.graphs)
+        );
+        insta::assert_display_snapshot!(escape_graphs.join("\n\n"));
+        Ok(())
+    }
+
     #[test]
     #[ignore]
     fn analysis_test_miri() -> eyre::Result<()> {
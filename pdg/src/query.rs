@@ -33,7 +33,7 @@ impl Graph {
         let mut not_needs_write = LinkedHashSet::new();
         for (node_id, node) in self.nodes.iter_enumerated().rev() {
             if !needs_write.contains(&node_id) && !not_needs_write.contains(&node_id) {
-                if let NodeKind::StoreAddr = node.kind {
+                if let NodeKind::StoreAddr | NodeKind::StoreRange(_) = node.kind {
                     let mut cur = node_id;
                     loop {
                         needs_write.insert(cur);
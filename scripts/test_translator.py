@@ -72,8 +72,11 @@ class CFile:
         self.disable_incremental_relooper = "disable_incremental_relooper" in flags
         self.disallow_current_block = "disallow_current_block" in flags
         self.translate_const_macros = "translate_const_macros" in flags
+        self.translate_fn_macros = "translate_fn_macros" in flags
         self.reorganize_definitions = "reorganize_definitions" in flags
         self.emit_build_files = "emit_build_files" in flags
+        self.long_double = next(
+            (flag[12:] for flag in flags if flag.startswith("long_double_")), None)
 
     def translate(self, cc_db, ld_lib_path, extra_args: List[str] = []) -> RustFile:
         extensionless_file, _ = os.path.splitext(self.path)
@@ -94,10 +97,14 @@ class CFile:
             args.append("--fail-on-multiple")
         if self.translate_const_macros:
             args.append("--translate-const-macros")
+        if self.translate_fn_macros:
+            args.append("--translate-fn-macros")
         if self.reorganize_definitions:
             args.append("--reorganize-definitions")
         if self.emit_build_files:
             args.append("--emit-build-files")
+        if self.long_double:
+            args.append("--long-double=" + self.long_double)
 
         if self.log_level == 'DEBUG':
             args.append("--log-level=debug")
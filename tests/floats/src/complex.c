@@ -0,0 +1,84 @@
+#include <complex.h>
+
+// Complex values are written out as (real, imag) pairs so that the test
+// does not depend on how `_Complex` is passed across the FFI boundary.
+
+static void store(double *out, double _Complex z) {
+    out[0] = __real__ z;
+    out[1] = __imag__ z;
+}
+
+void complex_arith(double *out) {
+    double _Complex a, b;
+    __real__ a = 1.5;
+    __imag__ a = -2.0;
+    __real__ b = 0.5;
+    __imag__ b = 0.5;
+
+    store(out + 0, a + b);
+    store(out + 2, a - b);
+    store(out + 4, a * b);
+    store(out + 6, a / b);
+    store(out + 8, -a);
+
+    a += b;
+    store(out + 10, a);
+    a *= b;
+    store(out + 12, a);
+}
+
+void complex_casts(double *out) {
+    float _Complex f;
+    double _Complex d;
+    int _Complex i;
+
+    __real__ i = 3;
+    __imag__ i = -7;
+
+    // real -> complex
+    d = 2.25;
+    store(out + 0, d);
+
+    // complex -> real
+    out[2] = (double)d;
+
+    // float complex <-> double complex
+    f = d;
+    d = f;
+    store(out + 3, d);
+
+    // integral complex -> floating complex
+    d = i;
+    store(out + 5, d);
+
+    // floating complex -> integral complex
+    __real__ d = 9.75;
+    __imag__ d = -1.5;
+    i = d;
+    out[7] = __real__ i;
+    out[8] = __imag__ i;
+
+    // integral complex -> real
+    out[9] = (int)i;
+}
+
+int complex_compare(void) {
+    double _Complex a = 0, b = 1;
+    int result = 0;
+
+    if (a == 0)
+        result |= 1;
+    if (a != b)
+        result |= 2;
+    if (b)
+        result |= 4;
+    if (!a)
+        result |= 8;
+
+    return result;
+}
+
+double real_imag_of_real(void) {
+    double x = 4.5;
+    return __real__ x + __imag__ x;
+}
@@ -0,0 +1,59 @@
+use crate::complex::{
+    rust_complex_arith, rust_complex_casts, rust_complex_compare, rust_real_imag_of_real,
+};
+use libc::{c_double, c_int};
+
+#[link(name = "test")]
+extern "C" {
+    fn complex_arith(_: *mut c_double);
+    fn complex_casts(_: *mut c_double);
+    fn complex_compare() -> c_int;
+    fn real_imag_of_real() -> c_double;
+}
+
+const ARITH_SIZE: usize = 14;
+const CASTS_SIZE: usize = 10;
+
+pub fn test_complex_arith() {
+    let mut buffer = [0.; ARITH_SIZE];
+    let mut rust_buffer = [0.; ARITH_SIZE];
+    let expected_buffer = [
+        2.0, -1.5, 1.0, -2.5, 1.75, -0.25, -0.5, -3.5, -1.5, 2.0, 2.0, -1.5, 1.75, 0.25,
+    ];
+
+    unsafe {
+        complex_arith(buffer.as_mut_ptr());
+        rust_complex_arith(rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_complex_casts() {
+    let mut buffer = [0.; CASTS_SIZE];
+    let mut rust_buffer = [0.; CASTS_SIZE];
+    let expected_buffer = [2.25, 0.0, 2.25, 2.25, 0.0, 3.0, -7.0, 9.0, -1.0, 9.0];
+
+    unsafe {
+        complex_casts(buffer.as_mut_ptr());
+        rust_complex_casts(rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_complex_compare() {
+    unsafe {
+        assert_eq!(complex_compare(), 15);
+        assert_eq!(rust_complex_compare(), 15);
+    }
+}
+
+pub fn test_real_imag_of_real() {
+    unsafe {
+        assert_eq!(real_imag_of_real(), 4.5);
+        assert_eq!(rust_real_imag_of_real(), 4.5);
+    }
+}
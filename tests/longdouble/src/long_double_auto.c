@@ -0,0 +1,58 @@
+//! long_double_auto
+
+// long double is returned differently from any Rust type, so these only pass
+// it through pointers to and from C.
+
+struct tagged {
+    char tag;
+    long double value;
+    int count;
+};
+
+const long double third = 1.0L / 3;
+
+// 2^-60 is lost when added to 1 in a double, but not in a wider long double
+void add_tiny(long double *x) {
+    long double tiny = 1.0L / (1ULL << 60);
+    *x += tiny;
+    *x *= 3;
+    (*x)++;
+    *x -= 3 * tiny;
+}
+
+void mixed_ops(long double *x, int *i, double *d, unsigned long long *u) {
+    *i += *x;
+    *d *= *x;
+    *x /= *i;
+    *u = *x * 1e6L;
+    --*x;
+    if (*x < *d) {
+        *x = -*x;
+    }
+}
+
+void update_tagged(struct tagged *t, int n) {
+    for (int i = 0; i < n; i++) {
+        t->value = t->value * 2 + third;
+        t->count++;
+    }
+    t->tag = t->value > 100 ? 'b' : 's';
+}
+
+int is_nan(long double *x) {
+    return *x != *x;
+}
+
+void make_nan(long double *x) {
+    *x = __builtin_nanl("");
+}
+
+// Functions with internal linkage are only called from the translation, so
+// they can pass long double by value.
+static long double scaled(long double x, int n) {
+    return x * n + third;
+}
+
+void scale(long double *x, int n) {
+    *x = scaled(*x, n);
+}
@@ -0,0 +1,121 @@
+use crate::long_double_auto::{
+    rust_add_tiny, rust_is_nan, rust_make_nan, rust_mixed_ops, rust_scale, rust_third,
+    rust_update_tagged, tagged,
+};
+use libc::{c_char, c_int, c_ulonglong};
+use std::mem::{align_of, size_of};
+
+// `--long-double auto` picks the type that matches `long double` on the target.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use c2rust_x87::f80 as long_double;
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+use f128::f128 as long_double;
+
+#[repr(C)]
+struct CTagged {
+    tag: c_char,
+    value: long_double,
+    count: c_int,
+}
+
+#[link(name = "test")]
+extern "C" {
+    static third: long_double;
+
+    fn add_tiny(_: *mut long_double);
+
+    fn mixed_ops(_: *mut long_double, _: *mut c_int, _: *mut f64, _: *mut c_ulonglong);
+
+    fn update_tagged(_: *mut CTagged, _: c_int);
+
+    fn is_nan(_: *mut long_double) -> c_int;
+
+    fn make_nan(_: *mut long_double);
+
+    fn scale(_: *mut long_double, _: c_int);
+}
+
+pub fn test_layout() {
+    assert_eq!(size_of::<tagged>(), size_of::<CTagged>());
+    assert_eq!(align_of::<tagged>(), align_of::<CTagged>());
+}
+
+pub fn test_constant() {
+    unsafe {
+        assert_eq!(rust_third, third);
+    }
+}
+
+pub fn test_extended_precision() {
+    let mut x = long_double::new(1.0f64);
+    let mut rust_x = x;
+    unsafe {
+        add_tiny(&mut x);
+        rust_add_tiny(&mut rust_x);
+    }
+
+    assert_eq!(x, rust_x);
+    assert_eq!(x, long_double::new(4.0f64));
+}
+
+pub fn test_mixed_ops() {
+    for start in [2.5f64, -7.25, 1e5] {
+        let (mut x, mut i, mut d, mut u) = (long_double::new(start), 3, 0.1f64, 0);
+        let (mut rust_x, mut rust_i, mut rust_d, mut rust_u) = (x, i, d, u);
+        unsafe {
+            mixed_ops(&mut x, &mut i, &mut d, &mut u);
+            rust_mixed_ops(&mut rust_x, &mut rust_i, &mut rust_d, &mut rust_u);
+        }
+
+        assert_eq!(x, rust_x);
+        assert_eq!(i, rust_i);
+        assert_eq!(d, rust_d);
+        assert_eq!(u, rust_u);
+    }
+}
+
+pub fn test_struct() {
+    let mut t = CTagged {
+        tag: 0,
+        value: long_double::new(1.0f64),
+        count: 0,
+    };
+    let mut rust_t = tagged {
+        tag: 0,
+        value: t.value,
+        count: 0,
+    };
+    unsafe {
+        update_tagged(&mut t, 7);
+        rust_update_tagged(&mut rust_t, 7);
+    }
+
+    assert_eq!(t.tag, rust_t.tag);
+    assert_eq!(t.value, rust_t.value);
+    assert_eq!(t.count, rust_t.count);
+}
+
+pub fn test_nan() {
+    let mut x = long_double::new(0.0f64);
+    let mut rust_x = x;
+    unsafe {
+        make_nan(&mut x);
+        rust_make_nan(&mut rust_x);
+
+        assert_eq!(is_nan(&mut x), 1);
+        assert_eq!(rust_is_nan(&mut x), 1);
+        assert_eq!(is_nan(&mut rust_x), 1);
+        assert_eq!(rust_is_nan(&mut rust_x), 1);
+    }
+}
+
+pub fn test_static_by_value() {
+    let mut x = long_double::new(1.5f64);
+    let mut rust_x = x;
+    unsafe {
+        scale(&mut x, 3);
+        rust_scale(&mut rust_x, 3);
+    }
+
+    assert_eq!(x, rust_x);
+}
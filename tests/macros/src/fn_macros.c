@@ -0,0 +1,33 @@
+//! translate_fn_macros
+
+#define SQUARE(x) ((x) * (x))
+#define AVERAGE(a, b) (((a) + (b)) / 2)
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+
+int scale = 3;
+#define SCALED(x) ((x) * scale)
+
+int square_sum(int a, int b) {
+  return SQUARE(a) + SQUARE(b);
+}
+
+int average(int a, int b) {
+  return AVERAGE(a, b) + AVERAGE(b, 4);
+}
+
+int scaled(int x) {
+  return SCALED(x) + SCALED(2);
+}
+
+// The arguments have side effects, so they must be evaluated as often as in C
+int max_inc(int *counter) {
+  int x = 1;
+  int m = MAX(x++, 0);
+  *counter = x;
+  return m;
+}
+
+// Used with different types, so it can't be one function
+double max_mixed(int a, double b) {
+  return MAX(a, 1) + MAX(b, 1.5);
+}
@@ -0,0 +1,31 @@
+use crate::fn_macros::{rust_average, rust_max_inc, rust_max_mixed, rust_scaled, rust_square_sum};
+use libc::{c_double, c_int};
+
+#[link(name = "test")]
+extern "C" {
+    fn square_sum(a: c_int, b: c_int) -> c_int;
+    fn average(a: c_int, b: c_int) -> c_int;
+    fn scaled(x: c_int) -> c_int;
+    fn max_inc(counter: *mut c_int) -> c_int;
+    fn max_mixed(a: c_int, b: c_double) -> c_double;
+}
+
+pub fn test_fn_macros() {
+    unsafe {
+        assert_eq!(rust_square_sum(3, -4), square_sum(3, -4));
+        assert_eq!(rust_average(7, 10), average(7, 10));
+        assert_eq!(rust_scaled(5), scaled(5));
+        assert_eq!(rust_max_mixed(0, 2.5), max_mixed(0, 2.5));
+        assert_eq!(rust_max_mixed(4, 0.5), max_mixed(4, 0.5));
+    }
+}
+
+pub fn test_fn_macro_side_effects() {
+    let mut counter = 0;
+    let mut rust_counter = 0;
+    let ret = unsafe { max_inc(&mut counter) };
+    let rust_ret = unsafe { rust_max_inc(&mut rust_counter) };
+
+    assert_eq!(ret, rust_ret);
+    assert_eq!(counter, rust_counter);
+}
@@ -0,0 +1,55 @@
+#include <setjmp.h>
+
+static void check(jmp_buf env, int value) {
+  if (value < 0)
+    longjmp(env, 1);
+}
+
+// Calls `longjmp` through `check`, so it unwinds too
+static void check_pair(jmp_buf env, int a, int b) {
+  check(env, a);
+  check(env, b);
+}
+
+int checked_sum(const int *values, unsigned len) {
+  jmp_buf env;
+  int sum = 0;
+  if (setjmp(env) == 0) {
+    for (unsigned i = 0; i < len; i++) {
+      check(env, values[i]);
+      sum += values[i];
+    }
+  } else {
+    sum = -1;
+  }
+  return sum;
+}
+
+// A longjmp to the outer setjmp passes through the inner one
+int nested_jumps(int value) {
+  jmp_buf outer, inner;
+  int result = 0;
+  if (!setjmp(outer)) {
+    if (setjmp(inner)) {
+      result = 10;
+    } else {
+      check(outer, value);
+      result = 1;
+    }
+  } else {
+    result = -1;
+  }
+  return result;
+}
+
+int checked_pair_sum(int a, int b) {
+  jmp_buf env;
+  int sum;
+  if (setjmp(env) == 0) {
+    check_pair(env, a, b);
+    sum = a + b;
+  } else {
+    sum = -1;
+  }
+  return sum;
+}
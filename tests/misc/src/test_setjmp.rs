@@ -0,0 +1,41 @@
+//! feature_c_unwind
+
+use crate::setjmp::{rust_checked_pair_sum, rust_checked_sum, rust_nested_jumps};
+use libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    fn checked_sum(_: *const c_int, _: c_uint) -> c_int;
+
+    fn nested_jumps(_: c_int) -> c_int;
+
+    fn checked_pair_sum(_: c_int, _: c_int) -> c_int;
+}
+
+pub fn test_setjmp() {
+    let values = [1, 2, 3, -4, 5];
+    for len in 0..=values.len() {
+        let sum = unsafe { checked_sum(values.as_ptr(), len as c_uint) };
+        let rust_sum = unsafe { rust_checked_sum(values.as_ptr(), len as c_uint) };
+
+        assert_eq!(sum, rust_sum);
+    }
+}
+
+pub fn test_nested_setjmp() {
+    for value in [-1, 0, 1] {
+        let result = unsafe { nested_jumps(value) };
+        let rust_result = unsafe { rust_nested_jumps(value) };
+
+        assert_eq!(result, rust_result);
+    }
+}
+
+pub fn test_setjmp_through_helper() {
+    for (a, b) in [(1, 2), (-1, 2), (1, -2)] {
+        let sum = unsafe { checked_pair_sum(a, b) };
+        let rust_sum = unsafe { rust_checked_pair_sum(a, b) };
+
+        assert_eq!(sum, rust_sum);
+    }
+}
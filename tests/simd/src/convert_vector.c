@@ -0,0 +1,99 @@
+#include <immintrin.h>
+
+// Results are stored through pointers so that the comparison only depends on
+// the lane values and not on how vectors are returned.
+
+void convert_int_float(const int *in, float *out) {
+    __m128i ints = _mm_loadu_si128((const __m128i *)in);
+    __m128 floats = (__m128)__builtin_convertvector((__v4si)ints, __v4sf);
+    _mm_storeu_ps(out, floats);
+}
+
+void convert_float_int(const float *in, int *out) {
+    __m128 floats = _mm_loadu_ps(in);
+    __m128i ints = (__m128i)__builtin_convertvector((__v4sf)floats, __v4si);
+    _mm_storeu_si128((__m128i *)out, ints);
+}
+
+void convert_float_double(const float *in, double *out) {
+    __m128 floats = _mm_loadu_ps(in);
+    __m256d doubles = (__m256d)__builtin_convertvector((__v4sf)floats, __v4df);
+    _mm256_storeu_pd(out, doubles);
+}
+
+// There is no single x86 instruction for this one, so it exercises the
+// element-wise fallback.
+void convert_double_long(const double *in, long long *out) {
+    __m128d doubles = _mm_loadu_pd(in);
+    __m128i longs = (__m128i)__builtin_convertvector((__v2df)doubles, __v2di);
+    _mm_storeu_si128((__m128i *)out, longs);
+}
+
+void convert_int_float_256(const int *in, float *out) {
+    __m256i ints = _mm256_loadu_si256((const __m256i *)in);
+    __m256 floats = (__m256)__builtin_convertvector((__v8si)ints, __v8sf);
+    _mm256_storeu_ps(out, floats);
+}
+
+void convert_float_int_256(const float *in, int *out) {
+    __m256 floats = _mm256_loadu_ps(in);
+    __m256i ints = (__m256i)__builtin_convertvector((__v8sf)floats, __v8si);
+    _mm256_storeu_si256((__m256i *)out, ints);
+}
+
+void convert_int_double(const int *in, double *out) {
+    __m128i ints = _mm_loadu_si128((const __m128i *)in);
+    __m256d doubles = (__m256d)__builtin_convertvector((__v4si)ints, __v4df);
+    _mm256_storeu_pd(out, doubles);
+}
+
+void convert_double_int(const double *in, int *out) {
+    __m256d doubles = _mm256_loadu_pd(in);
+    __m128i ints = (__m128i)__builtin_convertvector((__v4df)doubles, __v4si);
+    _mm_storeu_si128((__m128i *)out, ints);
+}
+
+void convert_double_float(const double *in, float *out) {
+    __m256d doubles = _mm256_loadu_pd(in);
+    __m128 floats = (__m128)__builtin_convertvector((__v4df)doubles, __v4sf);
+    _mm_storeu_ps(out, floats);
+}
+
+// The integer widening conversions to 256-bit vectors need AVX2.
+#ifdef __AVX2__
+void convert_i8_i16(const signed char *in, short *out) {
+    __m128i bytes = _mm_loadu_si128((const __m128i *)in);
+    __m256i shorts = (__m256i)__builtin_convertvector((__v16qs)bytes, __v16hi);
+    _mm256_storeu_si256((__m256i *)out, shorts);
+}
+
+void convert_u8_i16(const unsigned char *in, short *out) {
+    __m128i bytes = _mm_loadu_si128((const __m128i *)in);
+    __m256i shorts = (__m256i)__builtin_convertvector((__v16qu)bytes, __v16hi);
+    _mm256_storeu_si256((__m256i *)out, shorts);
+}
+
+void convert_i16_i32(const short *in, int *out) {
+    __m128i shorts = _mm_loadu_si128((const __m128i *)in);
+    __m256i ints = (__m256i)__builtin_convertvector((__v8hi)shorts, __v8si);
+    _mm256_storeu_si256((__m256i *)out, ints);
+}
+
+void convert_u16_i32(const unsigned short *in, int *out) {
+    __m128i shorts = _mm_loadu_si128((const __m128i *)in);
+    __m256i ints = (__m256i)__builtin_convertvector((__v8hu)shorts, __v8si);
+    _mm256_storeu_si256((__m256i *)out, ints);
+}
+
+void convert_i32_i64(const int *in, long long *out) {
+    __m128i ints = _mm_loadu_si128((const __m128i *)in);
+    __m256i longs = (__m256i)__builtin_convertvector((__v4si)ints, __v4di);
+    _mm256_storeu_si256((__m256i *)out, longs);
+}
+
+void convert_u32_i64(const unsigned int *in, long long *out) {
+    __m128i ints = _mm_loadu_si128((const __m128i *)in);
+    __m256i longs = (__m256i)__builtin_convertvector((__v4su)ints, __v4di);
+    _mm256_storeu_si256((__m256i *)out, longs);
+}
+#endif
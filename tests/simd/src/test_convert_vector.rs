@@ -0,0 +1,222 @@
+use crate::convert_vector::{
+    rust_convert_double_float, rust_convert_double_int, rust_convert_double_long,
+    rust_convert_float_double, rust_convert_float_int, rust_convert_float_int_256,
+    rust_convert_int_double, rust_convert_int_float, rust_convert_int_float_256,
+};
+#[cfg(target_feature = "avx2")]
+use crate::convert_vector::{
+    rust_convert_i16_i32, rust_convert_i32_i64, rust_convert_i8_i16, rust_convert_u16_i32,
+    rust_convert_u32_i64, rust_convert_u8_i16,
+};
+use libc::{c_double, c_float, c_int, c_longlong};
+#[cfg(target_feature = "avx2")]
+use libc::{c_schar, c_short, c_uchar, c_uint, c_ushort};
+
+#[link(name = "test")]
+extern "C" {
+    fn convert_int_float(_: *const c_int, _: *mut c_float);
+    fn convert_float_int(_: *const c_float, _: *mut c_int);
+    fn convert_float_double(_: *const c_float, _: *mut c_double);
+    fn convert_double_long(_: *const c_double, _: *mut c_longlong);
+    fn convert_int_float_256(_: *const c_int, _: *mut c_float);
+    fn convert_float_int_256(_: *const c_float, _: *mut c_int);
+    fn convert_int_double(_: *const c_int, _: *mut c_double);
+    fn convert_double_int(_: *const c_double, _: *mut c_int);
+    fn convert_double_float(_: *const c_double, _: *mut c_float);
+}
+
+// The C versions of these are only built with AVX2, so the tests below do nothing without it.
+#[cfg(target_feature = "avx2")]
+#[link(name = "test")]
+extern "C" {
+    fn convert_i8_i16(_: *const c_schar, _: *mut c_short);
+    fn convert_u8_i16(_: *const c_uchar, _: *mut c_short);
+    fn convert_i16_i32(_: *const c_short, _: *mut c_int);
+    fn convert_u16_i32(_: *const c_ushort, _: *mut c_int);
+    fn convert_i32_i64(_: *const c_int, _: *mut c_longlong);
+    fn convert_u32_i64(_: *const c_uint, _: *mut c_longlong);
+}
+
+static UNSAFETY_ERROR: &str =
+    "Prevented unsafe calling of SIMD functions when architecture support doesn't exist";
+
+const FLOATS: [c_float; 4] = [1.5, -2.5, 3.75, -4.25];
+
+pub fn test_convert_int_float() {
+    assert!(is_x86_feature_detected!("sse2"), "{}", UNSAFETY_ERROR);
+
+    let input = [1, -2, 3, -4];
+    let mut buffer = [0.; 4];
+    let mut rust_buffer = [0.; 4];
+
+    unsafe {
+        convert_int_float(input.as_ptr(), buffer.as_mut_ptr());
+        rust_convert_int_float(input.as_ptr(), rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, [1., -2., 3., -4.]);
+}
+
+pub fn test_convert_float_int() {
+    assert!(is_x86_feature_detected!("sse2"), "{}", UNSAFETY_ERROR);
+
+    let mut buffer = [0; 4];
+    let mut rust_buffer = [0; 4];
+
+    unsafe {
+        convert_float_int(FLOATS.as_ptr(), buffer.as_mut_ptr());
+        rust_convert_float_int(FLOATS.as_ptr(), rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, [1, -2, 3, -4]);
+}
+
+pub fn test_convert_float_double() {
+    assert!(is_x86_feature_detected!("avx"), "{}", UNSAFETY_ERROR);
+
+    let mut buffer = [0.; 4];
+    let mut rust_buffer = [0.; 4];
+
+    unsafe {
+        convert_float_double(FLOATS.as_ptr(), buffer.as_mut_ptr());
+        rust_convert_float_double(FLOATS.as_ptr(), rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, [1.5, -2.5, 3.75, -4.25]);
+}
+
+pub fn test_convert_double_long() {
+    assert!(is_x86_feature_detected!("sse2"), "{}", UNSAFETY_ERROR);
+
+    let input = [7.9, -8.9];
+    let mut buffer = [0; 2];
+    let mut rust_buffer = [0; 2];
+
+    unsafe {
+        convert_double_long(input.as_ptr(), buffer.as_mut_ptr());
+        rust_convert_double_long(input.as_ptr(), rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, [7, -8]);
+}
+
+/// Check that the C and Rust versions of a conversion agree, and return the result.
+unsafe fn compare<I, O: Copy + Default + PartialEq + std::fmt::Debug, const N: usize>(
+    input: &[I],
+    c: unsafe extern "C" fn(*const I, *mut O),
+    rust: unsafe extern "C" fn(*const I, *mut O),
+) -> [O; N] {
+    let mut buffer = [O::default(); N];
+    let mut rust_buffer = [O::default(); N];
+    c(input.as_ptr(), buffer.as_mut_ptr());
+    rust(input.as_ptr(), rust_buffer.as_mut_ptr());
+    assert_eq!(buffer, rust_buffer);
+    buffer
+}
+
+pub fn test_convert_int_float_256() {
+    assert!(is_x86_feature_detected!("avx"), "{}", UNSAFETY_ERROR);
+
+    let input = [1, -2, 3, -4, 5, -6, 7, -8];
+    let output: [c_float; 8] =
+        unsafe { compare(&input, convert_int_float_256, rust_convert_int_float_256) };
+    assert_eq!(output, [1., -2., 3., -4., 5., -6., 7., -8.]);
+}
+
+pub fn test_convert_float_int_256() {
+    assert!(is_x86_feature_detected!("avx"), "{}", UNSAFETY_ERROR);
+
+    let input = [1.5, -2.5, 3.75, -4.25, 5.5, -6.5, 7.75, -8.25];
+    let output: [c_int; 8] =
+        unsafe { compare(&input, convert_float_int_256, rust_convert_float_int_256) };
+    assert_eq!(output, [1, -2, 3, -4, 5, -6, 7, -8]);
+}
+
+pub fn test_convert_int_double() {
+    assert!(is_x86_feature_detected!("avx"), "{}", UNSAFETY_ERROR);
+
+    let input = [1, -2, 3, -4];
+    let output: [c_double; 4] =
+        unsafe { compare(&input, convert_int_double, rust_convert_int_double) };
+    assert_eq!(output, [1., -2., 3., -4.]);
+}
+
+pub fn test_convert_double_int() {
+    assert!(is_x86_feature_detected!("avx"), "{}", UNSAFETY_ERROR);
+
+    let input = [1.5, -2.5, 3.75, -4.25];
+    let output: [c_int; 4] =
+        unsafe { compare(&input, convert_double_int, rust_convert_double_int) };
+    assert_eq!(output, [1, -2, 3, -4]);
+}
+
+pub fn test_convert_double_float() {
+    assert!(is_x86_feature_detected!("avx"), "{}", UNSAFETY_ERROR);
+
+    let input = [1.5, -2.5, 3.75, -4.25];
+    let output: [c_float; 4] =
+        unsafe { compare(&input, convert_double_float, rust_convert_double_float) };
+    assert_eq!(output, [1.5, -2.5, 3.75, -4.25]);
+}
+
+pub fn test_convert_i8_i16() {
+    #[cfg(target_feature = "avx2")]
+    {
+        let input: [c_schar; 16] = [
+            1, -2, 3, -4, 5, -6, 7, -8, 9, -10, 11, -12, 13, -14, 127, -128,
+        ];
+        let output: [c_short; 16] = unsafe { compare(&input, convert_i8_i16, rust_convert_i8_i16) };
+        assert_eq!(output, input.map(c_short::from));
+    }
+}
+
+pub fn test_convert_u8_i16() {
+    #[cfg(target_feature = "avx2")]
+    {
+        let input: [c_uchar; 16] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 128, 255];
+        let output: [c_short; 16] = unsafe { compare(&input, convert_u8_i16, rust_convert_u8_i16) };
+        assert_eq!(output, input.map(c_short::from));
+    }
+}
+
+pub fn test_convert_i16_i32() {
+    #[cfg(target_feature = "avx2")]
+    {
+        let input: [c_short; 8] = [1, -2, 3, -4, 5, -6, 32767, -32768];
+        let output: [c_int; 8] = unsafe { compare(&input, convert_i16_i32, rust_convert_i16_i32) };
+        assert_eq!(output, input.map(c_int::from));
+    }
+}
+
+pub fn test_convert_u16_i32() {
+    #[cfg(target_feature = "avx2")]
+    {
+        let input: [c_ushort; 8] = [1, 2, 3, 4, 5, 6, 32768, 65535];
+        let output: [c_int; 8] = unsafe { compare(&input, convert_u16_i32, rust_convert_u16_i32) };
+        assert_eq!(output, input.map(c_int::from));
+    }
+}
+
+pub fn test_convert_i32_i64() {
+    #[cfg(target_feature = "avx2")]
+    {
+        let input: [c_int; 4] = [1, -2, c_int::MAX, c_int::MIN];
+        let output: [c_longlong; 4] =
+            unsafe { compare(&input, convert_i32_i64, rust_convert_i32_i64) };
+        assert_eq!(output, input.map(c_longlong::from));
+    }
+}
+
+pub fn test_convert_u32_i64() {
+    #[cfg(target_feature = "avx2")]
+    {
+        let input: [c_uint; 4] = [1, 2, 1 << 31, c_uint::MAX];
+        let output: [c_longlong; 4] =
+            unsafe { compare(&input, convert_u32_i64, rust_convert_u32_i64) };
+        assert_eq!(output, input.map(c_longlong::from));
+    }
+}